@@ -2,7 +2,7 @@
 
 use criterion::{criterion_group, criterion_main, Criterion};
 
-use yarte::yarte;
+use yarte::{yarte, Template, TemplateBytes};
 
 criterion_group!(benches, functions);
 criterion_main!(benches);
@@ -14,6 +14,19 @@ fn functions(c: &mut Criterion) {
     // Big table
     const SIZE: usize = 100;
     c.bench_function("Big table", |b| big_table(b, SIZE));
+
+    // Big table allocation, by size hint or by a measuring pass
+    let table = BigTable {
+        table: build_big_table(SIZE),
+    };
+    c.bench_function("Big table size hint", |b| big_table_hint(b, &table));
+    c.bench_function("Big table exact", |b| big_table_exact(b, &table));
+    c.bench_function("Big table bytes size hint", |b| {
+        big_table_bytes_hint(b, &table)
+    });
+    c.bench_function("Big table bytes exact", |b| {
+        big_table_bytes_exact(b, &table)
+    });
 }
 
 // Helpers
@@ -51,6 +64,12 @@ fn build_teams() -> Vec<Team> {
     ]
 }
 
+#[derive(Template, TemplateBytes)]
+#[template(path = "big-table")]
+struct BigTable {
+    table: Vec<Vec<usize>>,
+}
+
 struct Team {
     name: String,
     score: u8,
@@ -73,3 +92,19 @@ fn big_table(b: &mut criterion::Bencher, size: usize) {
         "{{> big-table }}";
     });
 }
+
+fn big_table_hint(b: &mut criterion::Bencher, table: &BigTable) {
+    b.iter(|| Template::call(table).unwrap());
+}
+
+fn big_table_exact(b: &mut criterion::Bencher, table: &BigTable) {
+    b.iter(|| Template::call_exact(table).unwrap());
+}
+
+fn big_table_bytes_hint(b: &mut criterion::Bencher, table: &BigTable) {
+    b.iter(|| TemplateBytes::call::<String>(table, <BigTable as Template>::size_hint()));
+}
+
+fn big_table_bytes_exact(b: &mut criterion::Bencher, table: &BigTable) {
+    b.iter(|| TemplateBytes::call_exact::<String>(table));
+}
@@ -0,0 +1,45 @@
+//! Front end work done by `#[derive(Template)]` when many templates share a layout
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use yarte_helpers::config::{get_source, read_config, read_config_file, Config};
+use yarte_parser::{cache, parse, source_map::SourceMap};
+
+criterion_group!(benches, functions);
+criterion_main!(benches);
+
+const DERIVES: [usize; 2] = [30, 300];
+
+fn functions(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Shared layout");
+    for n in DERIVES {
+        group.bench_with_input(BenchmarkId::new("Uncached", n), &n, |b, n| uncached(b, *n));
+        group.bench_with_input(BenchmarkId::new("Cached", n), &n, |b, n| cached(b, *n));
+    }
+    group.finish();
+}
+
+fn uncached(b: &mut criterion::Bencher, n: usize) {
+    b.iter(|| {
+        for _ in 0..n {
+            let config = &Config::new(&read_config_file());
+            let path = config.get_dir().join("big-table.hbs");
+            let src = get_source(&path);
+            let nodes = parse(SourceMap::default().get_cursor(&path, &src)).unwrap();
+            assert!(!nodes.is_empty());
+        }
+    });
+}
+
+fn cached(b: &mut criterion::Bencher, n: usize) {
+    b.iter(|| {
+        for _ in 0..n {
+            let config = &read_config();
+            let path = config.get_dir().join("big-table.hbs");
+            let src = cache::read(&path);
+            let (nodes, errors) =
+                cache::parse(&mut SourceMap::default(), &path, &src, config.syntax);
+            assert!(!nodes.is_empty() && errors.is_empty());
+        }
+    });
+    cache::clean();
+}
@@ -0,0 +1,3 @@
+fn main() {
+    yarte_build::build();
+}
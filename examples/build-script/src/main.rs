@@ -0,0 +1,52 @@
+use yarte::Template;
+
+struct Card<'a> {
+    title: &'a str,
+    body: &'a str,
+}
+
+include!(concat!(env!("OUT_DIR"), "/templates.rs"));
+
+fn main() {
+    let card = Card {
+        title: "My Title",
+        body: "My <Body>",
+    };
+    println!("{}", card.call().unwrap());
+
+    let page = Page {
+        title: "My Page".into(),
+        items: vec!["foo".into(), "bar".into()],
+    };
+    println!("{page:?}\n{page}");
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_card() {
+        let card = Card {
+            title: "My Title",
+            body: "My <Body>",
+        };
+        let expected = "<div class=\"entry\">\n  <h1>My Title</h1>\n  <div class=\"body\">";
+        assert_eq!(
+            Template::call(&card).unwrap(),
+            format!("{expected}My &lt;Body&gt;</div>\n</div>")
+        );
+    }
+
+    #[test]
+    fn test_page() {
+        let page = Page {
+            title: "My Page".into(),
+            items: vec!["foo".into(), "bar".into()],
+        };
+        assert_eq!(
+            page.call().unwrap(),
+            "<h1>My Page</h1>\n<ul><li>foo</li><li>bar</li></ul>"
+        );
+    }
+}
@@ -27,25 +27,81 @@ pub use yarte_derive::auto;
     feature = "html-min"
 ))]
 pub use yarte_derive::ywrite_min;
+pub use yarte_derive::{include_template, yformat, yformat_html};
 #[cfg(any(feature = "bytes-buf", feature = "bytes-buf-tokio2"))]
 pub use yarte_derive::{yarte, ywrite, ywrite_html};
-pub use yarte_derive::{yformat, yformat_html};
 pub use yarte_helpers::at_helpers::*;
+#[cfg(any(feature = "bytes-buf", feature = "bytes-buf-tokio2"))]
+pub use yarte_helpers::helpers::escape::EscapeBuf;
 pub use yarte_helpers::{
     helpers::{
-        display_fn::DisplayFn, io_fmt::IoFmt, Aligned256, IntoCopyIterator, Render, RenderA,
+        capacity::Capacity,
+        display_fn::DisplayFn,
+        escape::{
+            CsvEscaper, EscapeFmt, Escaper, JsonEscaper, LatexEscaper, ShellEscaper, XmlEscaper,
+        },
+        io_fmt::IoFmt,
+        render_len::LenWriter,
+        safe::{SafeHtml, TrustedHtml},
+        sanitize::{AsSanitize, Sanitize, SanitizePolicy},
+        template_error::{Location, TemplateError, TryBuf, TrySlot},
+        Aligned256, IntoCopyIterator, Render, RenderA,
     },
     recompile, Error, Result,
 };
 
 /// Template trait, will implement by derive `fmt::Display`
 pub trait TemplateTrait: fmt::Display {
+    /// MIME type of the output, derives set it by the template inner extension,
+    /// like `xml` in `feed.xml.hbs`, or by its format
+    const MIME: &'static str = "text/html";
+
     /// which will write this template
     fn call(&self) -> Result<String> {
         let mut buf = String::with_capacity(Self::size_hint());
         write!(buf, "{self}").map(|_| buf)
     }
 
+    /// Length of the output, rendered to a counting writer
+    ///
+    /// Derives sum the lengths of the literals at compile time and only render the
+    /// expressions to the counting writer
+    fn render_len(&self) -> usize {
+        let mut w = LenWriter::default();
+        let _ = write!(w, "{self}");
+        w.0
+    }
+
+    /// which will write this template with the capacity learned from previous renders of
+    /// this type, `size_hint` at first. Instantiations of a generic type share it
+    fn call_auto(&self) -> Result<String> {
+        let capacity = Self::__capacity();
+        let mut buf = match capacity.map_or(0, Capacity::get) {
+            0 => String::with_capacity(Self::size_hint()),
+            n => String::with_capacity(n),
+        };
+        write!(buf, "{self}")?;
+        if let Some(capacity) = capacity {
+            capacity.update(buf.len());
+        }
+        Ok(buf)
+    }
+
+    /// Output length mark of the type, derives implement it
+    #[doc(hidden)]
+    fn __capacity() -> Option<&'static Capacity> {
+        None
+    }
+
+    /// which will write this template allocated once by a previous measuring pass
+    ///
+    /// The pass renders the expressions twice, it pays off when reallocations are more
+    /// expensive than rendering, like big outputs under memory pressure
+    fn call_exact(&self) -> Result<String> {
+        let mut buf = String::with_capacity(self.render_len());
+        write!(buf, "{self}").map(|_| buf)
+    }
+
     /// Approximation of output size used in method `call`.
     /// Yarte implements an heuristic algorithm of allocation.
     fn size_hint() -> usize;
@@ -55,6 +111,41 @@ pub use yarte_derive::{Template, TemplateText};
 pub use TemplateTrait as Template;
 pub use TemplateTrait as TemplateText;
 
+/// Template trait of derives with `error` attribute, `?` expressions return `Self::Error`
+pub trait TryTemplateTrait {
+    /// Error of `?` expressions, converted with `From`
+    type Error;
+
+    /// MIME type of the output, like [`TemplateTrait::MIME`]
+    const MIME: &'static str = "text/html";
+
+    #[doc(hidden)]
+    fn __try_fmt(&self, _fmt: &mut fmt::Formatter, slot: &TrySlot<Self::Error>) -> fmt::Result;
+
+    /// which will write this template
+    fn try_call(&self) -> std::result::Result<String, TemplateError<Self::Error>> {
+        let slot = TrySlot::new();
+        let mut buf = String::with_capacity(Self::size_hint());
+        write!(buf, "{}", DisplayFn::new(|f| self.__try_fmt(f, &slot)))
+            .map(|_| buf)
+            .map_err(|e| slot.take(e))
+    }
+
+    /// Writes this template to `w`
+    fn try_write<W: Write + ?Sized>(
+        &self,
+        w: &mut W,
+    ) -> std::result::Result<(), TemplateError<Self::Error>> {
+        let slot = TrySlot::new();
+        write!(w, "{}", DisplayFn::new(|f| self.__try_fmt(f, &slot))).map_err(|e| slot.take(e))
+    }
+
+    /// Approximation of output size used in method `try_call`.
+    fn size_hint() -> usize;
+}
+
+pub use TryTemplateTrait as TryTemplate;
+
 #[cfg(feature = "html-min")]
 pub use yarte_derive::TemplateMin;
 #[cfg(feature = "html-min")]
@@ -116,6 +207,9 @@ pub use TemplateFixedTrait as TemplateFixedMin;
 #[cfg(any(feature = "bytes-buf", feature = "bytes-buf-tokio2"))]
 /// Template trait
 pub trait TemplateBytesTrait {
+    /// MIME type of the output, like [`TemplateTrait::MIME`]
+    const MIME: &'static str = "text/html";
+
     /// Writes to buffer and return it freeze
     ///
     /// # Panics
@@ -136,8 +230,93 @@ pub trait TemplateBytesTrait {
     /// # Panics
     /// Render length overflows usize
     fn write_ccall<B: Buffer>(self, buf: &mut B);
+
+    /// Length of the output, rendered to a counting buffer
+    ///
+    /// Derives sum the lengths of the literals at compile time, like
+    /// `TemplateTrait::render_len`
+    fn render_len(&self) -> usize {
+        self.call::<LenBuffer>(0)
+    }
+
+    /// Writes to buffer with the capacity learned from previous renders of this type
+    /// and return it freeze. Instantiations of a generic type share it
+    ///
+    /// # Panics
+    /// Render length overflows usize
+    fn call_auto<B: Buffer>(&self) -> B::Freeze {
+        let capacity = Self::__capacity();
+        let mut buf = B::with_capacity(capacity.map_or(0, Capacity::get));
+        self.write_call(&mut buf);
+        if let Some(capacity) = capacity {
+            capacity.update(buf.len());
+        }
+        buf.freeze()
+    }
+
+    /// Output length mark of the type, derives implement it
+    #[doc(hidden)]
+    fn __capacity() -> Option<&'static Capacity> {
+        None
+    }
+
+    /// Writes to buffer allocated once by a previous measuring pass and return it freeze
+    ///
+    /// The pass renders the expressions twice, like `TemplateTrait::call_exact`
+    ///
+    /// # Panics
+    /// Render length overflows usize
+    fn call_exact<B: Buffer>(&self) -> B::Freeze {
+        self.call::<B>(self.render_len())
+    }
+}
+
+#[cfg(any(feature = "bytes-buf", feature = "bytes-buf-tokio2"))]
+/// Template trait of bytes derives with `error` attribute, `?` expressions return `Self::Error`
+pub trait TryTemplateBytesTrait {
+    /// Error of `?` expressions, converted with `From`
+    type Error;
+
+    /// MIME type of the output, like [`TemplateTrait::MIME`]
+    const MIME: &'static str = "text/html";
+
+    /// Writes to buffer and return it freeze
+    ///
+    /// # Panics
+    /// Render length overflows usize
+    fn try_call<B: Buffer>(
+        &self,
+        capacity: usize,
+    ) -> std::result::Result<B::Freeze, TemplateError<Self::Error>>;
+    /// Writes to buffer and return it freeze and drop
+    ///
+    /// # Panics
+    /// Render length overflows usize
+    fn try_ccall<B: Buffer>(
+        self,
+        capacity: usize,
+    ) -> std::result::Result<B::Freeze, TemplateError<Self::Error>>;
+    /// Writes to buffer, it keeps the output until the failed expression
+    ///
+    /// # Panics
+    /// Render length overflows usize
+    fn try_write_call<B: Buffer>(
+        &self,
+        buf: &mut B,
+    ) -> std::result::Result<(), TemplateError<Self::Error>>;
+    /// Writes to buffer and drop, it keeps the output until the failed expression
+    ///
+    /// # Panics
+    /// Render length overflows usize
+    fn try_write_ccall<B: Buffer>(
+        self,
+        buf: &mut B,
+    ) -> std::result::Result<(), TemplateError<Self::Error>>;
 }
 
+#[cfg(any(feature = "bytes-buf", feature = "bytes-buf-tokio2"))]
+pub use TryTemplateBytesTrait as TryTemplateBytes;
+
 #[cfg(all(
     any(feature = "bytes-buf", feature = "bytes-buf-tokio2"),
     feature = "html-min"
@@ -156,7 +335,9 @@ pub use TemplateBytesTrait as TemplateBytesText;
 pub use TemplateBytesTrait as TemplateBytesMin;
 
 #[cfg(any(feature = "bytes-buf", feature = "bytes-buf-tokio2"))]
-pub use yarte_helpers::helpers::{RenderBytes, RenderBytesA, RenderBytesSafe, RenderBytesSafeA};
+pub use yarte_helpers::helpers::{
+    render_len::LenBuffer, RenderBytes, RenderBytesA, RenderBytesSafe, RenderBytesSafeA,
+};
 
 #[cfg(any(feature = "bytes-buf"))]
 pub use buf_min::Buffer;
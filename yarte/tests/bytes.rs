@@ -11,6 +11,30 @@ struct VariablesTemplate<'a> {
     i18n: String,
 }
 
+#[test]
+fn test_render_len() {
+    let s = VariablesTemplate {
+        strvar: "<foo>",
+        num: -42,
+        i18n: "Iñtërnâtiônàlizætiøn".to_string(),
+    };
+    let out = s.call_exact::<String>();
+    assert_eq!(out, s.call::<String>(0));
+    assert_eq!(s.render_len(), out.len());
+}
+
+#[test]
+fn test_call_auto() {
+    let s = VariablesTemplate {
+        strvar: "foo",
+        num: 42,
+        i18n: "i18n".to_string(),
+    };
+    let out = s.call_auto::<String>();
+    assert_eq!(out, s.call::<String>(0));
+    assert_eq!(VariablesTemplate::__capacity().unwrap().get(), out.len());
+}
+
 #[test]
 fn test_variables() {
     let s = VariablesTemplate {
@@ -0,0 +1,47 @@
+use yarte::Template;
+
+#[derive(Template)]
+#[template(
+    src = "<script src=\"{{ src }}\"></script>{{#if inline }}<STYLE>p { color: red }</style>{{/if}}\n<script>let s = '<style>';</script><!-- <script> -->",
+    csp_nonce = "nonce"
+)]
+struct Page<'a> {
+    nonce: &'a str,
+    src: &'a str,
+    inline: bool,
+}
+
+#[test]
+fn test_csp_nonce() {
+    let page = Page {
+        nonce: "n0\"",
+        src: "app.js",
+        inline: true,
+    };
+    assert_eq!(
+        page.call().unwrap(),
+        "<script nonce=\"n0&quot;\" src=\"app.js\"></script><STYLE nonce=\"n0&quot;\">p { color: red }</style>\n\
+         <script nonce=\"n0&quot;\">let s = '<style>';</script><!-- <script> -->"
+    );
+}
+
+#[derive(Template)]
+#[template(csp_nonce = "_0")]
+enum Widget {
+    #[template(src = "<script>{{ _1 }}</script>")]
+    Script(String, usize),
+    #[template(src = "<style></style>", csp_nonce = "nonce")]
+    Style { nonce: String },
+}
+
+#[test]
+fn test_csp_nonce_enum() {
+    assert_eq!(
+        Widget::Script("a".into(), 1).call().unwrap(),
+        "<script nonce=\"a\">1</script>"
+    );
+    assert_eq!(
+        Widget::Style { nonce: "b".into() }.call().unwrap(),
+        "<style nonce=\"b\"></style>"
+    );
+}
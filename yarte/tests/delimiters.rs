@@ -0,0 +1,38 @@
+use yarte::Template;
+use yarte_helpers::config::{Config, Syntax};
+
+#[derive(Template)]
+#[template(
+    src = "<p v-if=\"seen\">{{ message }}</p>[[ name ]][[[ html ]]][[#each items]]<b>[[ this ]]</b>[[/each]]",
+    delimiters = ("[[", "]]")
+)]
+struct Vue<'a> {
+    name: &'a str,
+    html: &'a str,
+    items: Vec<usize>,
+}
+
+#[test]
+fn test_delimiters() {
+    let t = Vue {
+        name: "<foo>",
+        html: "<i>bar</i>",
+        items: vec![1, 2],
+    };
+    assert_eq!(
+        "<p v-if=\"seen\">{{ message }}</p>&lt;foo&gt;<i>bar</i><b>1</b><b>2</b>",
+        t.call().unwrap()
+    );
+}
+
+#[test]
+fn test_config_syntax() {
+    let config = Config::new("[syntax]\nopen = \"<%\"\nclose = \"%>\"");
+    assert_eq!(config.syntax, Syntax::new("<%", "%>").unwrap());
+    assert_eq!(config.syntax.open(), "<%");
+    assert_eq!(config.syntax.close(), "%>");
+    assert_eq!(Config::new("").syntax, Syntax::default());
+    assert!(Syntax::new("{", "}}").is_err());
+    assert!(Syntax::new("{#", "}}").is_err());
+    assert!(Syntax::new("{{", "{{").is_err());
+}
@@ -0,0 +1,48 @@
+use std::path::Path;
+
+use yarte::Template;
+use yarte_helpers::config::Config;
+
+#[derive(Template)]
+#[template(path = "theme/layout")]
+struct Layout<'a> {
+    title: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "theme/page")]
+struct Page<'a> {
+    title: &'a str,
+}
+
+#[test]
+fn test_override() {
+    let t = Layout { title: "foo" };
+    assert_eq!("<main><b>foo</b></main>", t.call().unwrap());
+}
+
+#[test]
+fn test_fall_through() {
+    let t = Page { title: "foo" };
+    assert_eq!("<p>foo</p><footer>foo</footer>", t.call().unwrap());
+}
+
+#[test]
+fn test_dependency_dirs() {
+    let config = Config::new(
+        r#"
+        [[main.dirs]]
+        crate = "yarte_helpers"
+        path = "src"
+
+        [[main.dirs]]
+        crate = "yarte_helpers"
+        "#,
+    );
+    let dirs = config.get_dirs();
+    assert_eq!(dirs.len(), 3);
+    assert!(dirs[1].ends_with(Path::new("yarte_helpers/src")));
+    assert!(dirs[2].ends_with(Path::new("yarte_helpers/templates")));
+    assert!(config.find_template(Path::new("config.rs")).is_some());
+    assert!(config.find_template(Path::new("no-exist.hbs")).is_none());
+}
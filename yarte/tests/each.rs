@@ -54,3 +54,53 @@ fn test_for_range() {
     let s = ForRangeTemplate { init: -1, end: 1 };
     assert_eq!(s.call().unwrap(), "foo\nfoo\nbar\nbar\nfoo\nbar\nbar\n");
 }
+
+#[derive(Template)]
+#[template(src = "{{#each rows}}<tr>{{#each this}}<td>{{ this }}</td>{{/each}}</tr>{{/each}}")]
+struct RowsTemplate {
+    rows: Vec<Vec<&'static str>>,
+}
+
+#[test]
+fn test_render_len() {
+    let s = RowsTemplate {
+        rows: vec![vec!["<a>", "b"], vec!["Iñtërnâtiônàl"]],
+    };
+    let out = s.call_exact().unwrap();
+    assert_eq!(out, s.call().unwrap());
+    assert_eq!(s.render_len(), out.len());
+}
+
+#[derive(Template)]
+#[template(
+    src = "<table>{{#each rows}}{{#if this.is_empty() }}<tr/>{{else}}{{ let n = this.len() }}\
+           <tr>{{ n }}{{ this[0] }}</tr>{{/if}}{{/each}}</table>"
+)]
+struct BranchesTemplate {
+    rows: Vec<Vec<&'static str>>,
+}
+
+#[test]
+fn test_render_len_branches() {
+    let s = BranchesTemplate {
+        rows: vec![vec![], vec!["<a>", "b"]],
+    };
+    let out = s.call().unwrap();
+    assert_eq!(out, "<table><tr/><tr>2&lt;a&gt;</tr></table>");
+    assert_eq!(s.render_len(), out.len());
+}
+
+#[test]
+fn test_call_auto() {
+    let s = RowsTemplate {
+        rows: vec![vec!["a"; 100]],
+    };
+    let out = s.call_auto().unwrap();
+    assert_eq!(out, s.call().unwrap());
+    let capacity = RowsTemplate::__capacity().unwrap();
+    assert_eq!(capacity.get(), out.len());
+
+    let s = RowsTemplate { rows: vec![] };
+    assert_eq!(s.call_auto().unwrap(), "");
+    assert!(0 < capacity.get() && capacity.get() < out.len());
+}
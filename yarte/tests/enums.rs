@@ -0,0 +1,99 @@
+use yarte::Template;
+
+#[derive(Template)]
+enum Page<'a> {
+    #[template(src = "Loading...")]
+    Loading,
+    #[template(
+        src = "<ul>{{#each items}}<li>{{ this }}</li>{{/each}}</ul>{{#if more }}more{{/if}}"
+    )]
+    Ready { items: Vec<&'a str>, more: bool },
+    #[template(src = "<p>{{ _0 }}: {{ _1 }}</p>")]
+    Error(u16, &'a str),
+    #[template(path = "hello")]
+    Hello { name: &'a str },
+}
+
+#[test]
+fn test_enum() {
+    assert_eq!(Page::Loading.call().unwrap(), "Loading...");
+    assert_eq!(
+        Page::Ready {
+            items: vec!["<a>", "b"],
+            more: true
+        }
+        .call()
+        .unwrap(),
+        "<ul><li>&lt;a&gt;</li><li>b</li></ul>more"
+    );
+    assert_eq!(
+        Page::Error(404, "Not <found>").call().unwrap(),
+        "<p>404: Not &lt;found&gt;</p>"
+    );
+    assert_eq!(
+        Page::Hello { name: "world" }.call().unwrap(),
+        "Hello, world!"
+    );
+    assert!(Page::size_hint() > "Loading...".len());
+}
+
+#[test]
+fn test_enum_render_len() {
+    let pages = [
+        Page::Loading,
+        Page::Ready {
+            items: vec!["<a>", "b"],
+            more: false,
+        },
+        Page::Error(404, "Not <found>"),
+    ];
+    for page in &pages {
+        assert_eq!(page.render_len(), page.call().unwrap().len());
+        assert_eq!(page.call_exact().unwrap(), page.call().unwrap());
+    }
+}
+
+#[derive(Template)]
+#[template(delimiters = ("[[", "]]"))]
+enum Message {
+    #[template(src = "{{ [[ _0 ]] }}")]
+    Text(String),
+    #[template(src = "[[#if true]]empty[[/if]]", delimiters = ("{{", "}}"))]
+    Empty,
+}
+
+#[test]
+fn test_enum_options() {
+    assert_eq!(Message::Text("hi".into()).call().unwrap(), "{{ hi }}");
+    assert_eq!(Message::Empty.call().unwrap(), "[[#if true]]empty[[/if]]");
+}
+
+#[cfg(feature = "bytes-buf")]
+mod bytes {
+    use yarte::TemplateBytes;
+
+    #[derive(TemplateBytes)]
+    enum Status {
+        #[template(src = "ok")]
+        Ok,
+        #[template(src = "{{ code }}: {{ reason }}")]
+        Failed { code: u16, reason: String },
+    }
+
+    #[test]
+    fn test_enum_bytes() {
+        let failed = Status::Failed {
+            code: 500,
+            reason: "<oops>".into(),
+        };
+        assert_eq!(Status::Ok.call::<String>(16), "ok");
+        assert_eq!(failed.call::<String>(16), "500: &lt;oops&gt;");
+
+        let mut buf = String::new();
+        failed.write_call(&mut buf);
+        assert_eq!(buf, "500: &lt;oops&gt;");
+        assert_eq!(Status::Ok.render_len(), 2);
+        assert_eq!(failed.render_len(), "500: &lt;oops&gt;".len());
+        assert_eq!(failed.ccall::<String>(16), "500: &lt;oops&gt;");
+    }
+}
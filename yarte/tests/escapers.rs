@@ -0,0 +1,109 @@
+use yarte::{Escaper, Template};
+
+/// Markdown inline text escaper
+pub struct Markdown;
+
+impl Escaper for Markdown {
+    fn escape_byte(byte: u8) -> Option<&'static str> {
+        match byte {
+            b'*' => Some("\\*"),
+            b'_' => Some("\\_"),
+            b'`' => Some("\\`"),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Template)]
+#[template(src = "*{{ title }}* {{ \"a_b\" }}", escape = "crate::Markdown")]
+struct Note<'a> {
+    title: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "formats/report.tex")]
+struct Report<'a> {
+    title: &'a str,
+    items: Vec<&'a str>,
+}
+
+struct Row<'a> {
+    name: &'a str,
+    qty: usize,
+}
+
+#[derive(Template)]
+#[template(path = "formats/table.csv")]
+struct Table<'a> {
+    rows: Vec<Row<'a>>,
+}
+
+#[derive(Template)]
+#[template(path = "formats/run.sh")]
+struct Run<'a> {
+    arg: &'a str,
+}
+
+#[derive(Template)]
+#[template(src = "{{ a }} {{{ a }}}", format = "latex")]
+struct Safe<'a> {
+    a: &'a str,
+}
+
+#[test]
+fn test_custom_escaper() {
+    assert_eq!(Note { title: "2*3" }.call().unwrap(), "*2\\*3* a\\_b");
+    assert_eq!(Note { title: "2*3" }.render_len(), "*2\\*3* a\\_b".len());
+}
+
+#[test]
+fn test_format_escapers() {
+    let report = Report {
+        title: "50% off",
+        items: vec!["$5 & #1", "a_b"],
+    };
+    assert_eq!(
+        report.call().unwrap(),
+        "\\section*{Report: 50\\% off}\n\\item \\$5 \\& \\#1\n\\item a\\_b\n100\\% \\& more"
+    );
+    let table = Table {
+        rows: vec![Row {
+            name: "12\" disk, \"b\"",
+            qty: 2,
+        }],
+    };
+    assert_eq!(table.call().unwrap(), "\"12\"\" disk, \"\"b\"\"\",2\n");
+    assert_eq!(
+        Run { arg: "it's; rm" }.call().unwrap(),
+        "echo 'it'\\''s; rm'"
+    );
+    assert_eq!(Safe { a: "\\" }.call().unwrap(), "\\textbackslash{} \\");
+    assert_eq!(Report::MIME, "application/x-tex");
+    assert_eq!(Table::MIME, "text/csv");
+}
+
+#[cfg(feature = "bytes-buf")]
+mod bytes {
+    use yarte::TemplateBytes;
+
+    #[derive(TemplateBytes)]
+    #[template(src = "[{{ a }}]", escape = "crate::Markdown")]
+    struct Note<'a> {
+        a: &'a str,
+    }
+
+    #[derive(TemplateBytes)]
+    #[template(path = "formats/table.csv")]
+    struct Table<'a> {
+        rows: Vec<super::Row<'a>>,
+    }
+
+    #[test]
+    fn test_escapers_bytes() {
+        assert_eq!(Note { a: "_x_" }.call::<String>(16), "[\\_x\\_]");
+        let table = Table {
+            rows: vec![super::Row { name: "\"", qty: 1 }],
+        };
+        assert_eq!(table.call::<String>(16), "\"\"\"\",1\n");
+    }
+}
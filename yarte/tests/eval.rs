@@ -238,3 +238,61 @@ fn test_const_partial2() {
     let t = ConstPartial2Template { s: "bar" };
     assert_eq!(t.call().unwrap(), "foo\nIn partial\n1bar\nIn partial\n2");
 }
+
+#[derive(Template)]
+#[template(src = "<h1>Not found</h1>{{# each 1..4 }} {{ this * 2 }}{{/each}}{{ \"<\" }}")]
+struct ConstantTemplate;
+
+#[test]
+fn test_constant() {
+    const RENDERED: &str = ConstantTemplate::RENDERED;
+    assert_eq!(RENDERED, "<h1>Not found</h1> 2 4 6&lt;");
+    assert_eq!(ConstantTemplate.call().unwrap(), RENDERED);
+    assert_eq!(ConstantTemplate.to_string(), RENDERED);
+    assert_eq!(ConstantTemplate::size_hint(), RENDERED.len());
+    assert_eq!(ConstantTemplate.render_len(), RENDERED.len());
+    assert_eq!(ConstantTemplate.call_auto().unwrap(), RENDERED);
+}
+
+#[derive(Template)]
+#[template(src = "")]
+struct EmptyTemplate;
+
+#[test]
+fn test_constant_empty() {
+    assert_eq!(EmptyTemplate::RENDERED, "");
+    assert_eq!(EmptyTemplate.call().unwrap(), "");
+    assert_eq!(EmptyTemplate::size_hint(), 0);
+}
+
+#[cfg(feature = "bytes-buf")]
+mod bytes {
+    use yarte::{Template, TemplateBytes};
+
+    #[derive(Template, TemplateBytes)]
+    #[template(src = "<p>{{ 1 + 2 }}</p>")]
+    struct Constant;
+
+    #[derive(TemplateBytes)]
+    #[template(src = "")]
+    struct Empty;
+
+    #[test]
+    fn test_constant_bytes() {
+        assert_eq!(Constant::RENDERED_BYTES, Constant::RENDERED.as_bytes());
+        assert_eq!(Constant::RENDERED_BYTES, b"<p>3</p>");
+        assert_eq!(TemplateBytes::call::<String>(&Constant, 0), "<p>3</p>");
+        assert_eq!(TemplateBytes::call_auto::<String>(&Constant), "<p>3</p>");
+        assert_eq!(TemplateBytes::render_len(&Constant), 8);
+        let mut buf = String::from("a");
+        TemplateBytes::write_ccall(Constant, &mut buf);
+        assert_eq!(buf, "a<p>3</p>");
+    }
+
+    #[test]
+    fn test_constant_bytes_empty() {
+        assert_eq!(Empty::RENDERED_BYTES, b"");
+        assert_eq!(Empty.ccall::<String>(8), "");
+        assert_eq!(Empty.render_len(), 0);
+    }
+}
@@ -0,0 +1,104 @@
+use yarte::{Template, TemplateText};
+
+#[derive(Template)]
+#[template(path = "formats/feed.xml")]
+struct Feed<'a> {
+    title: &'a str,
+    tags: Vec<&'a str>,
+}
+
+#[derive(Template)]
+#[template(path = "formats/mail.txt.hbs")]
+struct Mail<'a> {
+    name: &'a str,
+    email: &'a str,
+}
+
+#[derive(TemplateText)]
+#[template(path = "formats/data.json.hbs")]
+struct Data<'a> {
+    name: &'a str,
+}
+
+#[derive(TemplateText)]
+#[template(path = "formats/page.html.hbs")]
+struct Page<'a> {
+    name: &'a str,
+}
+
+#[derive(Template)]
+#[template(src = "{{ name }}", format = "xml")]
+struct Attr<'a> {
+    name: &'a str,
+}
+
+#[test]
+fn test_extensions() {
+    let feed = Feed {
+        title: "Tom & 'Jerry'",
+        tags: vec!["a\"b"],
+    };
+    assert_eq!(
+        feed.call().unwrap(),
+        "<feed><title>Tom &amp; &apos;Jerry&apos;</title><tag name=\"a&quot;b\"/>&lt;&amp;&gt;</feed>"
+    );
+    assert_eq!(
+        Mail {
+            name: "<Jo>",
+            email: "jo@example.com"
+        }
+        .call()
+        .unwrap(),
+        "Hi <Jo> <jo@example.com>"
+    );
+    assert_eq!(
+        Data { name: "\"q\"\n" }.call().unwrap(),
+        r#"{"name": "\"q\"\n", "note": "a\"b"}"#
+    );
+    assert_eq!(Page { name: "<b>" }.call().unwrap(), "<p>&lt;b&gt;</p>");
+    assert_eq!(Attr { name: "<'>" }.call().unwrap(), "&lt;&apos;&gt;");
+}
+
+#[test]
+fn test_mime() {
+    assert_eq!(Feed::MIME, "text/xml");
+    assert_eq!(Mail::MIME, "text/plain");
+    assert_eq!(Data::MIME, "application/json");
+    assert_eq!(Page::MIME, "text/html");
+    assert_eq!(Attr::MIME, "text/xml");
+}
+
+#[cfg(feature = "bytes-buf")]
+mod bytes {
+    use yarte::TemplateBytes;
+
+    #[derive(TemplateBytes)]
+    #[template(path = "formats/feed.xml.hbs")]
+    struct Feed<'a> {
+        title: &'a str,
+        tags: Vec<&'a str>,
+    }
+
+    #[derive(TemplateBytes)]
+    #[template(path = "formats/data.json.hbs")]
+    struct Data<'a> {
+        name: &'a str,
+    }
+
+    #[test]
+    fn test_extensions_bytes() {
+        let feed = Feed {
+            title: "<t>",
+            tags: vec![],
+        };
+        assert_eq!(
+            feed.call::<String>(64),
+            "<feed><title>&lt;t&gt;</title>&lt;&amp;&gt;</feed>"
+        );
+        assert_eq!(
+            Data { name: "\\" }.call::<String>(64),
+            r#"{"name": "\\", "note": "a\"b"}"#
+        );
+        assert_eq!(Feed::MIME, "text/xml");
+    }
+}
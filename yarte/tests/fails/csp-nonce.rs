@@ -0,0 +1,16 @@
+use yarte::Template;
+
+#[derive(Template)]
+#[template(src = "<a href=\"#\" onclick=\"go()\">{{ text }}</a>", csp_nonce = "nonce")]
+struct Handler {
+    nonce: String,
+    text: String,
+}
+
+#[derive(Template)]
+#[template(src = "<script></script>", csp_nonce = "token")]
+struct Field {
+    nonce: String,
+}
+
+fn main() {}
@@ -0,0 +1,7 @@
+use yarte::Template;
+
+#[derive(Template)]
+#[template(src = "", delimiters = ("{#", "#}"))]
+struct Test;
+
+fn main() {}
@@ -0,0 +1,11 @@
+use yarte::Template;
+
+#[derive(Template)]
+#[template(src = "")]
+enum Test {
+    #[template(src = "{{ a }}")]
+    A { a: usize },
+    B,
+}
+
+fn main() {}
@@ -0,0 +1,12 @@
+use yarte::Template;
+
+#[derive(Template)]
+#[template(name = "html", src = "")]
+#[template(name = "html", format = "yaml", src = "")]
+struct Mail;
+
+#[derive(yarte::TemplateBytes)]
+#[template(name = "html", src = "")]
+struct Bytes;
+
+fn main() {}
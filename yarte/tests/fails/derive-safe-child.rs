@@ -0,0 +1,15 @@
+use yarte::Template;
+
+#[derive(Template)]
+#[template(src = "<p>{{{ html }}}</p>")]
+struct Child {
+    html: String,
+}
+
+#[derive(Template)]
+#[template(src = "{{{ child }}}", strict_safe)]
+struct Parent {
+    child: Child,
+}
+
+fn main() {}
@@ -0,0 +1,16 @@
+use yarte::Template;
+
+#[derive(Template)]
+#[template(src = "{{{ html }}}", strict_safe)]
+struct Strict {
+    html: String,
+}
+
+#[derive(Template)]
+#[template(src = "{{ html }}", raw_html)]
+struct Field {
+    #[template(raw)]
+    html: String,
+}
+
+fn main() {}
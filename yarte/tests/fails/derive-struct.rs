@@ -2,8 +2,8 @@ use yarte::Template;
 
 #[derive(Template)]
 #[template(src = "")]
-enum Test {
-    F
+union Test {
+    f: u8,
 }
 
 fn main() {}
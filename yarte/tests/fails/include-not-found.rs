@@ -0,0 +1,5 @@
+use yarte::include_template;
+
+include_template!("hello");
+
+fn main() {}
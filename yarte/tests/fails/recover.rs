@@ -0,0 +1,9 @@
+use yarte::Template;
+
+#[derive(Template)]
+#[template(src = "{{ foo( }}\n{{# each foo, bar }}{{/each }}\n{{ yield foo }}")]
+struct Test {
+    foo: usize,
+}
+
+fn main() {}
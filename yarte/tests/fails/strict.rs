@@ -0,0 +1,14 @@
+use yarte::Template;
+
+#[derive(Template)]
+#[template(
+    src = "{{ unsafe { 1 } }}{{ loop { break 1 } }}{{ items.push(1) }}{{ (&mut items).len() }}{{ println!(\"{}\", 1) }}{{ format!(\"{}\", items.pop()) }}\
+           {{ std::process::exit(1) }}{{ std::fs::remove_file(\"x\").is_ok() }}{{ drop(items) }}\
+           {{ format!(\"{}\", drop(1)) }}",
+    strict
+)]
+struct Strict {
+    items: Vec<usize>,
+}
+
+fn main() {}
@@ -1,9 +1,9 @@
 use yarte::Template;
 
 #[derive(Template)]
-#[template(src = "{{# each yield foo }}{{/each }}")]
+#[template(src = "{{# each while foo {} }}{{/each }}")]
 struct Test {
-    foo: Vec<usize>
+    foo: bool
 }
 
 fn main() {}
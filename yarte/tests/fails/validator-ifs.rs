@@ -1,7 +1,7 @@
 use yarte::Template;
 
 #[derive(Template)]
-#[template(src = "{{# if yield foo }}{{/if }}")]
+#[template(src = "{{# if while foo {} }}{{/if }}")]
 struct Test {
     foo: bool
 }
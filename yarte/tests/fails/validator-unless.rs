@@ -1,7 +1,7 @@
 use yarte::Template;
 
 #[derive(Template)]
-#[template(src = "{{# unless yield foo }}{{/unless }}")]
+#[template(src = "{{# unless while foo {} }}{{/unless }}")]
 struct Test {
     foo: bool
 }
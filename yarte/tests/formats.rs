@@ -0,0 +1,73 @@
+use yarte::Template;
+
+#[derive(Template)]
+#[template(
+    name = "html",
+    src = "<p>Hi {{ name }},</p>{{#each items}}<li>{{ this }}</li>{{/each}}"
+)]
+#[template(
+    name = "text",
+    format = "text",
+    src = "Hi {{ name }},\n{{#each items}}- {{ this }}\n{{/each}}"
+)]
+struct Mail<'a> {
+    name: &'a str,
+    items: Vec<&'a str>,
+}
+
+#[test]
+fn test_formats() {
+    let mail = Mail {
+        name: "<Jo>",
+        items: vec!["a&b", "c"],
+    };
+    assert_eq!(
+        mail.render_html().unwrap(),
+        "<p>Hi &lt;Jo&gt;,</p><li>a&amp;b</li><li>c</li>"
+    );
+    assert_eq!(mail.render_text().unwrap(), "Hi <Jo>,\n- a&b\n- c\n");
+}
+
+#[derive(Template)]
+#[template(src = "{{ title }}")]
+#[template(name = "heading", src = "<h1>{{ title }}</h1>")]
+struct Page {
+    title: String,
+}
+
+impl std::fmt::Debug for Page {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.write_heading(f)
+    }
+}
+
+#[test]
+fn test_named_and_display() {
+    let page = Page {
+        title: "a < b".into(),
+    };
+    assert_eq!(page.call().unwrap(), "a &lt; b");
+    assert_eq!(page.render_heading().unwrap(), "<h1>a &lt; b</h1>");
+    assert_eq!(format!("{page:?}"), "<h1>a &lt; b</h1>");
+}
+
+#[cfg(feature = "json")]
+mod json {
+    use serde::Serialize;
+    use yarte::TemplateText;
+
+    #[derive(Serialize, TemplateText)]
+    #[template(name = "text", src = "{{ id }}: {{ tag }}")]
+    #[template(name = "json", src = "{{ @json self }}")]
+    struct Item {
+        id: usize,
+        tag: &'static str,
+    }
+
+    #[test]
+    fn test_json_format() {
+        let item = Item { id: 1, tag: "<a>" };
+        assert_eq!(item.render_text().unwrap(), "1: <a>");
+        assert_eq!(item.render_json().unwrap(), r#"{"id":1,"tag":"<a>"}"#);
+    }
+}
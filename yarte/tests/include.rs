@@ -0,0 +1,16 @@
+use yarte::{include_template, Template};
+
+include_template!("front-matter");
+
+#[test]
+fn test_include_template() {
+    let t = Page {
+        title: "foo",
+        items: vec!["bar", "<baz>"],
+    };
+    assert_eq!(
+        "<h1>foo</h1><p>bar</p><p>&lt;baz&gt;</p>",
+        t.call().unwrap().trim()
+    );
+    assert_eq!(Page::default().items.len(), 0);
+}
@@ -0,0 +1,149 @@
+use yarte::{Template, TemplateText};
+
+#[derive(Template)]
+#[template(
+    src = "<body>\n  {{~> outline/navbar user = &name, count = items.len() ~}}\n  {{#each items}}{{> outline/navbar user = \"item\", count = *this }}{{/each}}</body>",
+    inline_partials = false
+)]
+struct Page {
+    name: String,
+    items: Vec<usize>,
+}
+
+#[derive(Template)]
+#[template(
+    src = "<body>\n  {{~> outline/navbar user = &name, count = items.len() ~}}\n  {{#each items}}{{> outline/navbar user = \"item\", count = *this }}{{/each}}</body>"
+)]
+struct Inlined {
+    name: String,
+    items: Vec<usize>,
+}
+
+#[test]
+fn test_outline() {
+    let page = Page {
+        name: "<b>".into(),
+        items: vec![1, 2],
+    };
+    assert_eq!(
+        page.call().unwrap(),
+        "<body><nav>&lt;b&gt;: 2</nav><nav>item: 1</nav><nav>item: 2</nav></body>"
+    );
+
+    let inlined = Inlined {
+        name: "<b>".into(),
+        items: vec![1, 2],
+    };
+    assert_eq!(inlined.call().unwrap(), page.call().unwrap());
+}
+
+#[derive(Template)]
+#[template(
+    src = "{{> outline/card title = title, count = 3 }}{{> outline/card title = &title, count = 4 }}",
+    inline_partials = false
+)]
+struct Card {
+    title: String,
+}
+
+#[derive(TemplateText)]
+#[template(
+    src = "{{> outline/card title = title, count = 3 }}",
+    inline_partials = false
+)]
+struct CardText {
+    title: String,
+}
+
+#[test]
+fn test_outline_inferred() {
+    let card = Card {
+        title: "<b>".into(),
+    };
+    assert_eq!(
+        card.call().unwrap(),
+        "<p>&lt;b&gt; (3)</p><p>&lt;b&gt; (4)</p>"
+    );
+
+    let card = CardText {
+        title: "<b>".into(),
+    };
+    assert_eq!(card.call().unwrap(), "<p><b> (3)</p>");
+}
+
+pub struct Node {
+    name: String,
+    children: Vec<Node>,
+}
+
+fn node(name: &str, children: Vec<Node>) -> Node {
+    Node {
+        name: name.into(),
+        children,
+    }
+}
+
+#[derive(Template)]
+#[cfg_attr(feature = "bytes-buf", derive(yarte::TemplateBytes))]
+#[template(path = "outline/tree")]
+pub struct Tree<'a> {
+    pub node: &'a Node,
+}
+
+#[derive(Template)]
+#[template(src = "<ul>{{> outline/tree node = &root }}</ul>")]
+struct Page2 {
+    root: Node,
+}
+
+#[test]
+fn test_recursive() {
+    let root = node(
+        "a",
+        vec![node("b", vec![node("c", vec![])]), node("d", vec![])],
+    );
+    let expected = "<li>a<ul>1<li>b<ul>1<li>c</li></ul></li>2<li>d</li></ul></li>";
+    assert_eq!(Tree { node: &root }.call().unwrap(), expected);
+    assert_eq!(Tree { node: &root }.render_len(), expected.len());
+    assert_eq!(
+        Page2 { root }.call().unwrap(),
+        format!("<ul>{expected}</ul>")
+    );
+
+    let deep = (0..500).fold(node("leaf", vec![]), |n, _| node("n", vec![n]));
+    let html = Tree { node: &deep }.call().unwrap();
+    assert_eq!(html.matches("<li>").count(), 501);
+}
+
+#[cfg(feature = "bytes-buf")]
+mod bytes {
+    use yarte::TemplateBytes;
+
+    #[derive(TemplateBytes)]
+    #[template(
+        src = "<body>{{> outline/navbar user = &name, count = 1 }}</body>",
+        inline_partials = false
+    )]
+    struct Page {
+        name: String,
+    }
+
+    #[test]
+    fn test_outline_bytes() {
+        let page = Page { name: "<b>".into() };
+        let expected = "<body><nav>&lt;b&gt;: 1</nav></body>";
+        assert_eq!(page.call::<String>(0), expected);
+        assert_eq!(page.render_len(), expected.len());
+    }
+
+    #[test]
+    fn test_recursive_bytes() {
+        use super::{node, Tree};
+
+        let root = node("a", vec![node("b", vec![])]);
+        assert_eq!(
+            Tree { node: &root }.call::<String>(0),
+            "<li>a<ul>1<li>b</li></ul></li>"
+        );
+    }
+}
@@ -0,0 +1,99 @@
+use yarte::{SafeHtml, Template};
+
+#[derive(Template)]
+#[template(src = "{{ html }}|{{{ html }}}|{{ text }}|{{ raw }}", strict_safe)]
+struct Post {
+    html: SafeHtml,
+    text: String,
+    #[template(safe)]
+    raw: String,
+}
+
+#[test]
+fn test_safe_html() {
+    let post = Post {
+        html: SafeHtml::from_static("<b>a</b>"),
+        text: "<i>".into(),
+        raw: "<hr>".into(),
+    };
+    assert_eq!(post.call().unwrap(), "<b>a</b>|<b>a</b>|&lt;i&gt;|<hr>");
+    assert_eq!(SafeHtml::render(&post).as_str(), post.call().unwrap());
+}
+
+#[derive(Template)]
+#[template(src = "<p>{{ title }}</p>", strict_safe)]
+struct Child<'a> {
+    title: &'a str,
+}
+
+#[derive(Template)]
+#[template(
+    src = "{{{ body }}}{{{ child }}}{{{ n }}}{{{ raw }}}{{{ \"<br>\" }}}{{#each items}}{{{ this }}}{{/each}}",
+    strict_safe
+)]
+struct Strict<'a> {
+    body: SafeHtml,
+    child: Child<'a>,
+    n: usize,
+    #[template(safe)]
+    raw: &'a str,
+    items: Vec<SafeHtml>,
+}
+
+#[test]
+fn test_strict_safe() {
+    let strict = Strict {
+        body: SafeHtml::escape("<a>"),
+        child: Child { title: "<t>" },
+        n: 1,
+        raw: "<hr>",
+        items: vec![SafeHtml::from_static("<li>")],
+    };
+    assert_eq!(
+        strict.call().unwrap(),
+        "&lt;a&gt;<p>&lt;t&gt;</p>1<hr><br><li>"
+    );
+}
+
+#[derive(Template)]
+#[template(strict_safe)]
+enum Card {
+    #[template(src = "{{{ _0 }}}{{ _1 }}")]
+    Html(#[template(safe)] String, String),
+    #[template(src = "{{{ body }}}")]
+    Body { body: SafeHtml },
+}
+
+#[test]
+fn test_safe_variants() {
+    assert_eq!(
+        Card::Html("<b>".into(), "<i>".into()).call().unwrap(),
+        "<b>&lt;i&gt;"
+    );
+    let body = Card::Body {
+        body: SafeHtml::from_static("<p>"),
+    };
+    assert_eq!(body.call().unwrap(), "<p>");
+}
+
+#[cfg(feature = "bytes-buf")]
+mod bytes {
+    use yarte::{SafeHtml, TemplateBytes};
+
+    #[derive(TemplateBytes)]
+    #[template(src = "{{ html }}{{{ html }}}{{ raw }}", strict_safe)]
+    struct Post<'a> {
+        html: SafeHtml,
+        #[template(safe)]
+        raw: &'a str,
+    }
+
+    #[test]
+    fn test_safe_bytes() {
+        let post = Post {
+            html: SafeHtml::from_static("<b>"),
+            raw: "<i>",
+        };
+        assert_eq!(post.call::<String>(16), "<b><b><i>");
+    }
+}
@@ -0,0 +1,66 @@
+use yarte::{SanitizePolicy, Template, TemplateText};
+
+#[derive(Template)]
+#[template(src = "<div>{{ @sanitize body }}</div>")]
+struct Comment<'a> {
+    body: &'a str,
+}
+
+#[derive(TemplateText)]
+#[template(src = "{{ @sanitize body }}")]
+struct CommentText {
+    body: String,
+}
+
+#[test]
+fn test_sanitize() {
+    let body = r#"<p onclick="x()">Hi <b>you</b><script>alert(1)</script><img src="javascript:x()" alt="a"><img src=/a.png><em>e</em></p>"#;
+    let expected = r#"<p>Hi <b>you</b><img alt="a"><img src="/a.png">e</p>"#;
+    assert_eq!(
+        Comment { body }.call().unwrap(),
+        format!("<div>{expected}</div>")
+    );
+    assert_eq!(
+        CommentText {
+            body: format!("<b>{body}")
+        }
+        .call()
+        .unwrap(),
+        format!("<b>{expected}</b>")
+    );
+}
+
+#[derive(Template)]
+#[template(src = "{{ html }}{{{ html }}}", strict_safe)]
+struct Post {
+    html: yarte::SafeHtml,
+}
+
+#[test]
+fn test_sanitized_safe_html() {
+    let html = SanitizePolicy::DEFAULT.sanitize("<h1 title=t>a<img></h1>");
+    assert_eq!(
+        Post { html }.call().unwrap(),
+        "<h1 title=\"t\">a</h1><h1 title=\"t\">a</h1>"
+    );
+}
+
+#[cfg(feature = "bytes-buf")]
+mod bytes {
+    use yarte::TemplateBytes;
+
+    #[derive(TemplateBytes)]
+    #[template(src = "{{ @sanitize body }}")]
+    struct Comment<'a> {
+        body: &'a str,
+    }
+
+    #[test]
+    fn test_sanitize_bytes() {
+        let body = "<a href='https://a.b' title=t>a</a><style>*{}</style>";
+        assert_eq!(
+            Comment { body }.call::<String>(64),
+            "<a href=\"https://a.b\">a</a>"
+        );
+    }
+}
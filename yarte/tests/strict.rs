@@ -0,0 +1,22 @@
+use yarte::Template;
+
+#[derive(Template)]
+#[template(
+    src = "{{ let title = name.trim().to_uppercase() }}{{ title }} {{ format!(\"{}\", 1) }}\
+           {{#each items}}{{ this.len() }}{{/each}}{{#if items.is_empty() }}-{{/if}}\
+           {{ Some(String::from(name)).is_some() }}",
+    strict
+)]
+struct Page<'a> {
+    name: &'a str,
+    items: Vec<&'a str>,
+}
+
+#[test]
+fn test_strict() {
+    let page = Page {
+        name: " yarte ",
+        items: vec!["a", "bc"],
+    };
+    assert_eq!(page.call().unwrap(), "YARTE 112true");
+}
@@ -0,0 +1,121 @@
+use std::{error::Error, fmt, num::ParseIntError};
+
+use yarte::{TemplateError, TryTemplate};
+
+#[derive(Debug, PartialEq)]
+enum PageError {
+    Id(ParseIntError),
+    Missing(&'static str),
+}
+
+impl From<ParseIntError> for PageError {
+    fn from(e: ParseIntError) -> Self {
+        PageError::Id(e)
+    }
+}
+
+impl fmt::Display for PageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PageError::Id(e) => write!(f, "invalid id: {e}"),
+            PageError::Missing(name) => write!(f, "missing {name}"),
+        }
+    }
+}
+
+impl Error for PageError {}
+
+fn title(id: u32) -> Result<&'static str, PageError> {
+    match id {
+        1 => Ok("Home"),
+        _ => Err(PageError::Missing("title")),
+    }
+}
+
+#[derive(yarte::Template)]
+#[template(
+    src = "{{ let n = id.parse::<u32>()? }}<h1>{{ title(n)? }}</h1>\n{{#each tags}}{{ this.parse::<u8>()? }}{{/each}}",
+    error = "PageError"
+)]
+struct Page<'a> {
+    id: &'a str,
+    tags: Vec<&'a str>,
+}
+
+#[test]
+fn test_try_template() {
+    let page = Page {
+        id: "1",
+        tags: vec!["1", "2"],
+    };
+    assert_eq!(page.try_call().unwrap(), "<h1>Home</h1>\n12");
+
+    let err = Page {
+        id: "2",
+        tags: vec![],
+    }
+    .try_call()
+    .unwrap_err();
+    let at = err.location().unwrap();
+    assert_eq!((at.file, at.line, at.column), ("Page.hbs", 1, 40));
+    assert_eq!(at.expr, "title(n)?");
+    assert_eq!(
+        err.to_string(),
+        "missing title, at `title(n)?` in Page.hbs:1:40"
+    );
+    assert!(err.source().is_some());
+    assert_eq!(err.into_inner(), Some(PageError::Missing("title")));
+
+    let err = Page {
+        id: "1",
+        tags: vec!["1", "x"],
+    }
+    .try_call()
+    .unwrap_err();
+    let at = err.location().unwrap();
+    assert_eq!((at.line, at.column), (2, 18));
+    assert!(matches!(err, TemplateError::Expr(PageError::Id(_), _)));
+}
+
+#[derive(yarte::Template)]
+#[template(error = "PageError")]
+enum Item {
+    #[template(
+        src = "{{ _0.parse::<u8>()? }}{{ [Some(1), None].iter().filter_map(|x| Some((*x)? + 1)).count() }}"
+    )]
+    Number(String),
+    #[template(src = "none")]
+    None,
+}
+
+#[test]
+fn test_try_template_enum() {
+    let mut buf = String::new();
+    Item::Number("7".into()).try_write(&mut buf).unwrap();
+    Item::None.try_write(&mut buf).unwrap();
+    assert_eq!(buf, "71none");
+    assert!(Item::Number("-".into()).try_call().is_err());
+}
+
+#[cfg(feature = "bytes-buf")]
+mod bytes {
+    use super::*;
+    use yarte::TryTemplateBytes;
+
+    #[derive(yarte::TemplateBytes)]
+    #[template(src = "<p>{{ id.parse::<u32>()? }}</p>", error = "PageError")]
+    struct Id<'a> {
+        id: &'a str,
+    }
+
+    #[test]
+    fn test_try_template_bytes() {
+        let buf = Id { id: "12" }.try_call::<String>(0).unwrap();
+        assert_eq!(buf, "<p>12</p>");
+
+        let mut buf = String::new();
+        let err = Id { id: "a" }.try_write_call(&mut buf).unwrap_err();
+        assert_eq!(buf, "<p>");
+        assert_eq!(err.location().unwrap().expr, "id.parse::<u32>()?");
+    }
+}
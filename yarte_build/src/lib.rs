@@ -0,0 +1,701 @@
+//! Generate yarte templates from the build script
+//!
+//! Writes the same implementations that yarte derives expand to `OUT_DIR/templates.rs`,
+//! so they aren't expanded again in every build nor by the IDE.
+//!
+//! Templates come from the `[[templates]]` manifest in `yarte.toml`, from [`Builder::template`]
+//! or are discovered in the template directory when they declare its struct in the head
+//! comment:
+//!
+//! ```handlebars
+//! {{!-- yarte: #[derive(Template, Debug)] pub struct Card<'a> { title: &'a str } --!}}
+//! <h1>{{ title }}</h1>
+//! ```
+//!
+//! ```rust,ignore
+//! // build.rs
+//! fn main() {
+//!     yarte_build::build();
+//! }
+//!
+//! // src/main.rs
+//! include!(concat!(env!("OUT_DIR"), "/templates.rs"));
+//! ```
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    env,
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use proc_macro2::TokenStream;
+#[allow(unused_imports)]
+use quote::{format_ident, quote};
+use syn::punctuated::Punctuated;
+
+use yarte_codegen::{CodeGen, EscapeCodeGen, FmtCodeGen, HTMLCodeGen, TextCodeGen};
+use yarte_helpers::{
+    config::{get_source, read_config, Config, PrintConfig, Syntax},
+    logger::log,
+    recompile::when_changed,
+};
+use yarte_hir::{front_matter, generate, visit_derive, Format, HIROptions, Print, Struct, HIR};
+use yarte_parser::{cache, cache::Source, emitter, source_map::SourceMap, ErrorMessage};
+
+/// Sources of a template and its partials, by [`read`]
+pub struct Sources {
+    pub sources: BTreeMap<PathBuf, Source>,
+    /// Partials in a cycle of the partial graph, rendered by recursive functions
+    pub cyclic: BTreeSet<PathBuf>,
+}
+
+const PARENT: &str = "yarte";
+const OUT_FILE: &str = "templates.rs";
+static DEFAULT_EXTENSION: &str = "hbs";
+
+/// Yarte template derives
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Derive {
+    /// Implements TemplateTrait with html escape functionality
+    Template,
+    /// Implements TemplateTrait without html escape functionality
+    TemplateText,
+    /// Implements TemplateBytesTrait with html escape functionality
+    #[cfg(feature = "bytes-buf")]
+    TemplateBytes,
+    /// Implements TemplateBytesTrait without html escape functionality
+    #[cfg(feature = "bytes-buf")]
+    TemplateBytesText,
+}
+
+impl FromStr for Derive {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Template" => Ok(Derive::Template),
+            "TemplateText" => Ok(Derive::TemplateText),
+            #[cfg(feature = "bytes-buf")]
+            "TemplateBytes" => Ok(Derive::TemplateBytes),
+            #[cfg(feature = "bytes-buf")]
+            "TemplateBytesText" => Ok(Derive::TemplateBytesText),
+            #[cfg(not(feature = "bytes-buf"))]
+            "TemplateBytes" | "TemplateBytesText" => {
+                Err(format!("derive `{s}` needs `bytes-buf` feature"))
+            }
+            _ => Err(format!("invalid yarte derive `{s}`")),
+        }
+    }
+}
+
+impl Derive {
+    /// Expands the derive over `i`
+    pub fn expand(self, i: &syn::DeriveInput, config: &Config) -> TokenStream {
+        let s = &mut match visit_derive(i, config) {
+            Ok(s) => s,
+            Err(ts) => return ts,
+        };
+        s.default_format(self.format());
+        let s = &*s;
+        #[cfg(feature = "bytes-buf")]
+        if matches!(self, Derive::TemplateBytes | Derive::TemplateBytesText)
+            && !s.has_template()
+            && s.variants.is_empty()
+        {
+            return syn::Error::new_spanned(&i.ident, "need a template without 'name'")
+                .to_compile_error();
+        }
+        proc_macro2::fallback::force();
+        #[cfg(feature = "bytes-buf")]
+        let buf: &syn::Expr = &syn::parse2(quote!(bytes_mut)).unwrap();
+
+        let codegen = match self {
+            Derive::Template | Derive::TemplateText => fmt_codegen(s),
+            #[cfg(feature = "bytes-buf")]
+            Derive::TemplateBytes | Derive::TemplateBytesText => bytes_codegen(s, buf),
+        };
+        let opt = options(s);
+
+        let mut tokens = if !s.variants.is_empty() {
+            variants_to_tokens(config, s, codegen, opt)
+        } else if s.has_template() {
+            let sources = &read(
+                s.path.clone(),
+                cache::intern(&s.path, s.src.clone()),
+                s.syntax,
+                config,
+            );
+            sources_to_tokens(sources, config, s, codegen, opt)
+        } else {
+            TokenStream::new()
+        };
+
+        #[cfg(feature = "bytes-buf")]
+        if matches!(self, Derive::TemplateBytes | Derive::TemplateBytesText) {
+            return tokens;
+        }
+        for f in &s.formats {
+            let sources = &read(
+                f.path.clone(),
+                cache::intern(&f.path, f.src.clone()),
+                f.syntax,
+                config,
+            );
+            tokens.extend(sources_to_tokens(
+                sources,
+                config,
+                f,
+                fmt_codegen(f),
+                options(f),
+            ));
+        }
+
+        tokens
+    }
+
+    /// Format of templates without inner extension nor `format` attribute
+    fn format(self) -> Format {
+        match self {
+            Derive::Template => Format::Html,
+            Derive::TemplateText => Format::Text,
+            #[cfg(feature = "bytes-buf")]
+            Derive::TemplateBytes => Format::Html,
+            #[cfg(feature = "bytes-buf")]
+            Derive::TemplateBytesText => Format::Text,
+        }
+    }
+}
+
+fn options(s: &Struct) -> HIROptions {
+    HIROptions {
+        is_text: s.options.escaper.is_none() && s.options.format == Some(Format::Text),
+        ..Default::default()
+    }
+}
+
+/// Escaper type of the template, `None` with html and text formats
+fn escaper(s: &Struct) -> Option<TokenStream> {
+    if let Some(escaper) = &s.options.escaper {
+        return Some(quote!(#escaper));
+    }
+    match s.options.format.unwrap_or(Format::Html) {
+        Format::Html | Format::Text => None,
+        Format::Xml => Some(quote!(yarte::XmlEscaper)),
+        Format::Json => Some(quote!(yarte::JsonEscaper)),
+        Format::Latex => Some(quote!(yarte::LatexEscaper)),
+        Format::Csv => Some(quote!(yarte::CsvEscaper)),
+        Format::Shell => Some(quote!(yarte::ShellEscaper)),
+    }
+}
+
+/// Display code generator by the template escaper
+fn fmt_codegen<'a>(s: &'a Struct<'a>) -> Box<dyn CodeGen + 'a> {
+    match (escaper(s), s.options.format) {
+        (Some(escaper), _) => Box::new(FmtCodeGen::new(EscapeCodeGen::new(escaper), s, PARENT)),
+        (None, Some(Format::Text)) => Box::new(FmtCodeGen::new(TextCodeGen, s, PARENT)),
+        (None, _) => Box::new(FmtCodeGen::new(HTMLCodeGen, s, PARENT)),
+    }
+}
+
+/// Buffer code generator by the template escaper
+#[cfg(feature = "bytes-buf")]
+fn bytes_codegen<'a>(s: &'a Struct<'a>, buf: &'a syn::Expr) -> Box<dyn CodeGen + 'a> {
+    use yarte_codegen::{BytesCodeGen, EscapeBytesCodeGen, HTMLBytesCodeGen, TextBytesCodeGen};
+
+    let buf_i = format_ident!("bytes_mut");
+    match (escaper(s), s.options.format) {
+        (Some(escaper), _) => Box::new(BytesCodeGen::new(
+            EscapeBytesCodeGen::new(buf, escaper),
+            s,
+            buf_i,
+            PARENT,
+        )),
+        (None, Some(Format::Text)) => Box::new(BytesCodeGen::new(
+            TextBytesCodeGen::new(buf),
+            s,
+            buf_i,
+            PARENT,
+        )),
+        (None, _) => Box::new(BytesCodeGen::new(
+            HTMLBytesCodeGen::new(buf),
+            s,
+            buf_i,
+            PARENT,
+        )),
+    }
+}
+
+/// Removes yarte derives from the `derive` attributes of `i` and returns them
+///
+/// Empty `derive` attributes are removed
+pub fn take_derives(i: &mut syn::DeriveInput) -> Result<Vec<Derive>, syn::Error> {
+    let mut derives = vec![];
+    for attr in &mut i.attrs {
+        if !attr.path.is_ident("derive") {
+            continue;
+        }
+        let paths = attr
+            .parse_args_with(Punctuated::<syn::Path, syn::Token![,]>::parse_terminated)?
+            .into_iter()
+            .filter(|path| {
+                match path
+                    .get_ident()
+                    .and_then(|ident| ident.to_string().parse().ok())
+                {
+                    Some(derive) => {
+                        derives.push(derive);
+                        false
+                    }
+                    None => true,
+                }
+            })
+            .collect::<Vec<_>>();
+        attr.tokens = quote!((#(#paths),*));
+    }
+    i.attrs
+        .retain(|attr| !(attr.path.is_ident("derive") && attr.tokens.to_string() == "()"));
+
+    Ok(derives)
+}
+
+/// Reads template and its partials sources, partials use the template delimiters
+///
+/// Partials of sources with parse errors are read too, errors are reported
+/// by [`sources_to_tokens`]
+pub fn read(path: PathBuf, src: Source, syntax: Syntax, config: &Config) -> Sources {
+    let mut stack = vec![(path, src)];
+    let mut visited = BTreeMap::new();
+    let mut graph = BTreeMap::new();
+
+    while let Some((path, src)) = stack.pop() {
+        let partials = cache::partials(&path, &src, syntax)
+            .iter()
+            .map(|partial| config.resolve_partial(&path, partial))
+            .collect::<BTreeSet<_>>();
+        visited.insert(path.clone(), src);
+
+        for partial in &partials {
+            if !visited.contains_key(partial) {
+                let src = cache::read(partial.as_path());
+                stack.push((partial.clone(), src));
+            }
+        }
+        graph.insert(path, partials);
+    }
+
+    Sources {
+        sources: visited,
+        cyclic: cyclic(&graph),
+    }
+}
+
+/// Templates of `graph` reachable from themselves by their partials
+fn cyclic(graph: &BTreeMap<PathBuf, BTreeSet<PathBuf>>) -> BTreeSet<PathBuf> {
+    graph
+        .keys()
+        .filter(|&path| {
+            let mut stack: Vec<_> = graph[path].iter().collect();
+            let mut visited = BTreeSet::new();
+            while let Some(p) = stack.pop() {
+                if p == path {
+                    return true;
+                }
+                if visited.insert(p) {
+                    stack.extend(graph.get(p).into_iter().flatten());
+                }
+            }
+            false
+        })
+        .cloned()
+        .collect()
+}
+
+/// Parse, lower and generate code of template sources
+///
+/// Parse errors of every source and lowering errors are reported together
+pub fn sources_to_tokens<'a>(
+    sources: &Sources,
+    config: &Config,
+    s: &'a Struct<'a>,
+    mut codegen: Box<dyn CodeGen + 'a>,
+    opt: HIROptions,
+) -> TokenStream {
+    let mut sm = SourceMap::default();
+    let mut errors = vec![];
+    let hir = lower(sources, config, s, opt, &mut sm, &mut errors);
+    if !errors.is_empty() {
+        emitter(&sources.sources, config, &sm, errors.into_iter());
+    }
+
+    let tokens = codegen.gen(hir);
+    print_code(config, s, &tokens);
+    print_size(config, s, &tokens);
+
+    tokens
+}
+
+/// Read, parse, lower and generate code of the templates of each enum variant
+///
+/// Errors of every variant are reported together
+pub fn variants_to_tokens<'a>(
+    config: &Config,
+    s: &'a Struct<'a>,
+    mut codegen: Box<dyn CodeGen + 'a>,
+    opt: HIROptions,
+) -> TokenStream {
+    let mut all = BTreeMap::new();
+    let mut sm = SourceMap::default();
+    let mut errors = vec![];
+    let mut hirs = vec![];
+    for v in &s.variants {
+        let sources = &read(
+            v.path.clone(),
+            cache::intern(&v.path, v.src.clone()),
+            v.syntax,
+            config,
+        );
+        hirs.push(lower(sources, config, v, opt, &mut sm, &mut errors));
+        all.extend(
+            sources
+                .sources
+                .iter()
+                .map(|(p, src)| (p.clone(), src.clone())),
+        );
+    }
+    if !errors.is_empty() {
+        emitter(&all, config, &sm, errors.into_iter());
+    }
+
+    let tokens = codegen.gen_enum(hirs);
+    print_code(config, s, &tokens);
+    print_size(config, s, &tokens);
+
+    tokens
+}
+
+/// Parse and lower template sources, adding them to `sm` and pushing its errors
+fn lower(
+    sources: &Sources,
+    config: &Config,
+    s: &Struct,
+    opt: HIROptions,
+    sm: &mut SourceMap,
+    errors: &mut Vec<ErrorMessage<String>>,
+) -> Vec<HIR> {
+    let mut parsed = BTreeMap::new();
+    for (p, src) in &sources.sources {
+        let (nodes, e) = cache::parse(sm, p, src, s.syntax);
+        errors.extend(e.into_iter().map(message));
+        parsed.insert(p, nodes);
+    }
+
+    if cfg!(debug_assertions) && config.print_override == PrintConfig::Ast
+        || config.print_override == PrintConfig::All
+        || s.print == Print::Ast
+        || s.print == Print::All
+    {
+        eprintln!("{parsed:?}\n");
+    }
+
+    generate(config, s, &parsed, &sources.cyclic, sm, opt).unwrap_or_else(|e| {
+        errors.extend(e.into_iter().map(message));
+        vec![]
+    })
+}
+
+fn print_code(config: &Config, s: &Struct, tokens: &TokenStream) {
+    if cfg!(debug_assertions) && config.print_override == PrintConfig::Code
+        || config.print_override == PrintConfig::All
+        || s.print == Print::Code
+        || s.print == Print::All
+    {
+        log(&tokens.to_string());
+    }
+}
+
+/// Reports the size of the generated code of each template
+fn print_size(config: &Config, s: &Struct, tokens: &TokenStream) {
+    if cfg!(debug_assertions) && config.print_override == PrintConfig::Size
+        || config.print_override == PrintConfig::All
+        || s.print == Print::Size
+        || s.print == Print::All
+    {
+        let mut name = match &s.name {
+            Some(name) => format!("{}::{name}", s.ident),
+            None => s.ident.to_string(),
+        };
+        if s.has_template() {
+            let path = config
+                .get_dirs()
+                .iter()
+                .find_map(|dir| s.path.strip_prefix(dir).ok())
+                .unwrap_or(&s.path);
+            name.push_str(&format!(" ({})", path.display()));
+        }
+        eprintln!(
+            "{name}: {} tokens, {} bytes of code",
+            count_tokens(tokens.clone()),
+            tokens.to_string().len()
+        );
+    }
+}
+
+fn count_tokens(tokens: TokenStream) -> usize {
+    tokens
+        .into_iter()
+        .map(|tt| match tt {
+            proc_macro2::TokenTree::Group(g) => 1 + count_tokens(g.stream()),
+            _ => 1,
+        })
+        .sum()
+}
+
+/// Error message with its message as string
+fn message<T: Display>(e: ErrorMessage<T>) -> ErrorMessage<String> {
+    ErrorMessage {
+        message: e.message.to_string(),
+        span: e.span,
+    }
+}
+
+/// Build script template generator
+pub struct Builder {
+    templates: Vec<(syn::DeriveInput, Vec<Derive>)>,
+    discover: bool,
+    out_file: String,
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Builder {
+    pub fn new() -> Builder {
+        Builder {
+            templates: vec![],
+            discover: true,
+            out_file: OUT_FILE.to_owned(),
+        }
+    }
+
+    /// Implements `derives` for the struct `head`, like `Card<'a>`, with the template at `path`
+    ///
+    /// # Panics
+    /// When `head` isn't a valid struct head
+    pub fn template(mut self, head: &str, path: &str, derives: &[Derive]) -> Builder {
+        self.templates
+            .push((template_input(head, path), derives.to_vec()));
+        self
+    }
+
+    /// Discover templates with struct declaration in the template directory, `true` by default
+    pub fn discover(mut self, discover: bool) -> Builder {
+        self.discover = discover;
+        self
+    }
+
+    /// Name of the generated file in `OUT_DIR`, `templates.rs` by default
+    pub fn out_file(mut self, name: &str) -> Builder {
+        self.out_file = name.to_owned();
+        self
+    }
+
+    /// Writes generated code and rerun the build script when templates change
+    ///
+    /// # Panics
+    /// With the template error messages
+    pub fn build(self) {
+        let config = &read_config();
+        let mut tokens = TokenStream::new();
+
+        let mut templates = self.templates;
+        for entry in &config.templates {
+            let derives = entry
+                .derive
+                .iter()
+                .map(|d| {
+                    d.parse()
+                        .unwrap_or_else(|e| panic!("{e} in {}", entry.head))
+                })
+                .collect();
+            templates.push((template_input(&entry.head, &entry.path), derives));
+        }
+
+        if self.discover {
+            let mut seen = BTreeSet::new();
+            for dir in config.get_dirs().iter().filter(|dir| dir.exists()) {
+                for path in template_files(dir) {
+                    let rel = path.strip_prefix(dir).unwrap().to_str().unwrap().to_owned();
+                    // Overridden by a previous directory
+                    if !seen.insert(rel.clone()) {
+                        continue;
+                    }
+                    match declared(&get_source(&path), &rel, config.syntax) {
+                        Some(Ok(d)) => tokens.extend(d.expand(config)),
+                        Some(Err(e)) => panic!("invalid struct declaration in {path:?}: {e}"),
+                        None => (),
+                    }
+                }
+            }
+        }
+
+        let mut impls = TokenStream::new();
+        for (i, derives) in &templates {
+            if derives.is_empty() {
+                impls.extend(Derive::Template.expand(i, config));
+            }
+            for derive in derives {
+                impls.extend(derive.expand(i, config));
+            }
+        }
+        tokens.extend(allow_generated(impls));
+
+        let out = PathBuf::from(env::var("OUT_DIR").expect("run in build script"));
+        fs::write(out.join(&self.out_file), tokens.to_string())
+            .unwrap_or_else(|e| panic!("unable to write {}: {e}", self.out_file));
+
+        when_changed();
+    }
+}
+
+/// Template with its context struct declared in the head comment
+pub struct Declared {
+    /// Struct declaration without yarte derives
+    pub decl: syn::DeriveInput,
+    /// Derive input pointing to the template
+    pub input: syn::DeriveInput,
+    /// Yarte derives of the declaration, `Template` when there are none
+    pub derives: Vec<Derive>,
+}
+
+impl Declared {
+    /// Struct declaration and its template implementations
+    pub fn expand(&self, config: &Config) -> TokenStream {
+        let Declared {
+            decl,
+            input,
+            derives,
+        } = self;
+        let impls = derives
+            .iter()
+            .map(|derive| derive.expand(input, config))
+            .collect();
+        let impls = allow_generated(impls);
+
+        quote!(#decl #impls)
+    }
+}
+
+/// Struct declared in the head comment of `src`, the template at `path`
+pub fn declared(src: &str, path: &str, syntax: Syntax) -> Option<syn::Result<Declared>> {
+    Some(front_matter(src, syntax)?.and_then(|mut decl| {
+        let mut derives = take_derives(&mut decl)?;
+        if derives.is_empty() {
+            derives.push(Derive::Template);
+        }
+        let mut input = decl.clone();
+        input.attrs = vec![syn::parse_quote!(#[template(path = #path)])];
+
+        Ok(Declared {
+            decl,
+            input,
+            derives,
+        })
+    }))
+}
+
+// Generated code isn't linted when it comes from a derive
+fn allow_generated(impls: TokenStream) -> TokenStream {
+    quote! {
+        #[allow(unused, nonstandard_style, clippy::all)]
+        const _: () = { #impls };
+    }
+}
+
+/// Generates templates with the default [`Builder`]. Put me on your `build.rs`
+pub fn build() {
+    Builder::new().build()
+}
+
+fn template_input(head: &str, path: &str) -> syn::DeriveInput {
+    let head: TokenStream = head
+        .parse()
+        .unwrap_or_else(|_| panic!("invalid struct head `{head}`"));
+    syn::parse2(quote!(#[template(path = #path)] struct #head;))
+        .unwrap_or_else(|e| panic!("invalid struct head `{head}`: {e}"))
+}
+
+fn template_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files = vec![];
+    let mut stack = vec![dir.to_owned()];
+    while let Some(dir) = stack.pop() {
+        for entry in fs::read_dir(dir).expect("valid directory") {
+            let path = entry.expect("valid directory entry").path();
+            if path.is_dir() {
+                stack.push(path);
+            } else if path.extension().is_some_and(|e| e == DEFAULT_EXTENSION) {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+
+    files
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_take_derives() {
+        let mut i: syn::DeriveInput = syn::parse_str(
+            "#[derive(Debug, Template)] #[derive(TemplateText)] #[doc = \"foo\"] struct Foo;",
+        )
+        .unwrap();
+        let derives = take_derives(&mut i).unwrap();
+        assert_eq!(derives, vec![Derive::Template, Derive::TemplateText]);
+        assert_eq!(i.attrs.len(), 2);
+        assert!(i.attrs[0].path.is_ident("derive"));
+        assert_eq!(i.attrs[0].tokens.to_string(), "(Debug)");
+    }
+
+    #[test]
+    fn test_template_input() {
+        let i = template_input("Card<'a> where 'a: 'static", "card");
+        assert_eq!(i.ident, "Card");
+        assert_eq!(i.generics.params.len(), 1);
+        assert!(template_input("Card", "card").attrs[0]
+            .path
+            .is_ident("template"));
+    }
+
+    #[test]
+    fn test_cyclic() {
+        let graph: BTreeMap<PathBuf, BTreeSet<PathBuf>> = [
+            ("page", &["navbar", "tree"][..]),
+            ("navbar", &[]),
+            ("tree", &["node"]),
+            ("node", &["tree", "leaf"]),
+            ("leaf", &["leaf"]),
+        ]
+        .into_iter()
+        .map(|(p, partials)| (p.into(), partials.iter().map(PathBuf::from).collect()))
+        .collect();
+        let cycles: Vec<_> = cyclic(&graph).into_iter().collect();
+        assert_eq!(
+            cycles,
+            [PathBuf::from("leaf"), "node".into(), "tree".into()]
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_template_input_panic() {
+        template_input("Card<", "card");
+    }
+}
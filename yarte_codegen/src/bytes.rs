@@ -1,10 +1,11 @@
 use proc_macro2::{Ident, TokenStream};
 use quote::{format_ident, quote};
 
-use yarte_hir::{Struct, HIR};
+use syn::parse_quote;
 
-use crate::EachCodeGen;
-use crate::{CodeGen, IfElseCodeGen};
+use yarte_hir::{PartialFn, Struct, HIR};
+
+use crate::{CodeGen, EachCodeGen, IfElseCodeGen};
 
 pub struct BytesCodeGen<'a, T: CodeGen> {
     codegen: T,
@@ -28,14 +29,26 @@ impl<'a, T: CodeGen> BytesCodeGen<'a, T> {
         }
     }
 
+    /// Implements the trait with `nodes` in borrowing methods and `cnodes` in consuming ones
     #[inline]
-    fn template(&mut self, nodes: Vec<HIR>, tokens: &mut TokenStream) {
-        let nodes = self.codegen.gen(nodes);
+    fn template(
+        &self,
+        nodes: &TokenStream,
+        cnodes: &TokenStream,
+        render_len: TokenStream,
+        tokens: &mut TokenStream,
+    ) {
+        if let Some(error) = &self.s.options.error {
+            return self.try_template(error, nodes, cnodes, tokens);
+        }
         let parent = &self.parent;
+        let mime = crate::mime(self.s);
         let buf = &self.buf;
         tokens.extend(self.s.implement_head(
             quote!(#parent::TemplateBytesTrait),
             &quote!(
+                const MIME: &'static str = #mime;
+
                 fn call<B: #parent::Buffer>(&self, capacity: usize) -> B::Freeze {
                     use #parent::*;
                     let mut #buf = B::with_capacity(capacity);
@@ -52,7 +65,7 @@ impl<'a, T: CodeGen> BytesCodeGen<'a, T> {
                     macro_rules! buf_ref {
                         ($b:expr) => { &mut $b };
                     }
-                    #nodes
+                    #cnodes
                     #buf.freeze()
                 }
 
@@ -69,7 +82,186 @@ impl<'a, T: CodeGen> BytesCodeGen<'a, T> {
                     macro_rules! buf_ref {
                         ($b:expr) => { $b };
                     }
+                    #cnodes
+                }
+
+                #render_len
+
+                fn __capacity() -> Option<&'static #parent::Capacity> {
+                    static CAPACITY: #parent::Capacity = #parent::Capacity::new();
+                    Some(&CAPACITY)
+                }
+            ),
+        ));
+    }
+
+    /// Methods of the out of line partials, generic over the buffer
+    fn partials(&mut self, fns: Vec<PartialFn>, tokens: &mut TokenStream) {
+        if fns.is_empty() {
+            return;
+        }
+        let parent = &self.parent;
+        let buf = &self.buf;
+        let fns: TokenStream = fns
+            .into_iter()
+            .map(|PartialFn { mut sig, body }| {
+                let body = self.codegen.gen(body);
+                sig.ident = method(&sig.ident);
+                sig.generics.params.push(parse_quote!(__B: #parent::Buffer));
+                sig.inputs.insert(0, parse_quote!(&self));
+                sig.inputs.insert(1, parse_quote!(#buf: &mut __B));
+                quote!(
+                    #[doc(hidden)]
+                    #sig {
+                        use #parent::*;
+                        macro_rules! buf_ref {
+                            ($b:expr) => { $b };
+                        }
+                        #body
+                    }
+                )
+            })
+            .collect();
+        tokens.extend(self.s.implement_inherent(&fns));
+    }
+
+    /// Template without expressions, rendered at compile time to `RENDERED_BYTES`
+    ///
+    /// `RENDERED` is left to the display derives of the same struct
+    fn constant(&self, rendered: &str, tokens: &mut TokenStream) {
+        let parent = &self.parent;
+        let mime = crate::mime(self.s);
+        let len = rendered.len();
+        tokens.extend(self.s.implement_inherent(&quote!(
+            /// Output of the template, it hasn't expressions
+            pub const RENDERED_BYTES: &'static [u8] = #rendered.as_bytes();
+        )));
+        tokens.extend(self.s.implement_head(
+            quote!(#parent::TemplateBytesTrait),
+            &quote!(
+                const MIME: &'static str = #mime;
+
+                fn call<B: #parent::Buffer>(&self, _capacity: usize) -> B::Freeze {
+                    let mut buf = B::with_capacity(#len);
+                    <Self as #parent::TemplateBytesTrait>::write_call(self, &mut buf);
+                    buf.freeze()
+                }
+
+                fn ccall<B: #parent::Buffer>(self, capacity: usize) -> B::Freeze {
+                    <Self as #parent::TemplateBytesTrait>::call::<B>(&self, capacity)
+                }
+
+                fn write_call<B: #parent::Buffer>(&self, buf: &mut B) {
+                    #parent::Buffer::extend(buf, #rendered);
+                }
+
+                fn write_ccall<B: #parent::Buffer>(self, buf: &mut B) {
+                    <Self as #parent::TemplateBytesTrait>::write_call(&self, buf)
+                }
+
+                fn render_len(&self) -> usize {
+                    #len
+                }
+
+                fn call_auto<B: #parent::Buffer>(&self) -> B::Freeze {
+                    <Self as #parent::TemplateBytesTrait>::call::<B>(self, #len)
+                }
+
+                fn call_exact<B: #parent::Buffer>(&self) -> B::Freeze {
+                    <Self as #parent::TemplateBytesTrait>::call::<B>(self, #len)
+                }
+            ),
+        ));
+    }
+
+    /// `render_len` with the constant length `len` of the literals, `body` writes the
+    /// expressions to a counting buffer
+    fn render_len(&self, len: usize, body: TokenStream) -> TokenStream {
+        if body.is_empty() {
+            return quote!(
+                fn render_len(&self) -> usize {
+                    #len
+                }
+            );
+        }
+        let parent = &self.parent;
+        let buf = &self.buf;
+        quote!(
+            fn render_len(&self) -> usize {
+                use #parent::*;
+                #[allow(unused_mut)]
+                let mut __len: usize = #len;
+                let mut #buf = LenBuffer::default();
+                macro_rules! buf_ref {
+                    ($b:expr) => { &mut $b };
+                }
+                #body
+                __len + Buffer::len(&#buf)
+            }
+        )
+    }
+
+    /// Implements the `Try` trait, `?` expressions return `error`
+    fn try_template(
+        &self,
+        error: &syn::Path,
+        nodes: &TokenStream,
+        cnodes: &TokenStream,
+        tokens: &mut TokenStream,
+    ) {
+        let parent = &self.parent;
+        let mime = crate::mime(self.s);
+        let buf = &self.buf;
+        let result = |ok| quote!(std::result::Result<#ok, #parent::TemplateError<#error>>);
+        let freeze = result(quote!(B::Freeze));
+        let unit = result(quote!(()));
+        tokens.extend(self.s.implement_head(
+            quote!(#parent::TryTemplateBytesTrait),
+            &quote!(
+                type Error = #error;
+
+                const MIME: &'static str = #mime;
+
+                fn try_call<B: #parent::Buffer>(&self, capacity: usize) -> #freeze {
+                    use #parent::*;
+                    let __yarte_try = TryBuf;
+                    let mut #buf = B::with_capacity(capacity);
+                    macro_rules! buf_ref {
+                        ($b:expr) => { &mut $b };
+                    }
+                    #nodes
+                    Ok(#buf.freeze())
+                }
+
+                fn try_ccall<B: #parent::Buffer>(self, capacity: usize) -> #freeze {
+                    use #parent::*;
+                    let __yarte_try = TryBuf;
+                    let mut #buf = B::with_capacity(capacity);
+                    macro_rules! buf_ref {
+                        ($b:expr) => { &mut $b };
+                    }
+                    #cnodes
+                    Ok(#buf.freeze())
+                }
+
+                fn try_write_call<B: #parent::Buffer>(&self, #buf: &mut B) -> #unit {
+                    use #parent::*;
+                    let __yarte_try = TryBuf;
+                    macro_rules! buf_ref {
+                        ($b:expr) => { $b };
+                    }
                     #nodes
+                    Ok(())
+                }
+
+                fn try_write_ccall<B: #parent::Buffer>(self, #buf: &mut B) -> #unit {
+                    use #parent::*;
+                    let __yarte_try = TryBuf;
+                    macro_rules! buf_ref {
+                        ($b:expr) => { $b };
+                    }
+                    #cnodes
+                    Ok(())
                 }
             ),
         ));
@@ -80,7 +272,45 @@ impl<'a, T: CodeGen> CodeGen for BytesCodeGen<'a, T> {
     fn gen(&mut self, v: Vec<HIR>) -> TokenStream {
         let mut tokens = TokenStream::new();
 
-        self.template(v, &mut tokens);
+        let (fns, v) = crate::partials(v);
+        self.partials(fns, &mut tokens);
+        if let ([HIR::Lit(rendered)], None) = (v.as_slice(), &self.s.options.error) {
+            self.constant(rendered, &mut tokens);
+            return tokens;
+        }
+        let render_len = match self.s.options.error {
+            Some(_) => TokenStream::new(),
+            None => {
+                let (len, body) = crate::measure(&mut self.codegen, v.clone());
+                self.render_len(len, body)
+            }
+        };
+        let nodes = self.codegen.gen(v);
+        self.template(&nodes, &nodes, render_len, &mut tokens);
+
+        tokens
+    }
+
+    fn gen_enum(&mut self, v: Vec<Vec<HIR>>) -> TokenStream {
+        let mut tokens = TokenStream::new();
+
+        let render_len = match self.s.options.error {
+            Some(_) => TokenStream::new(),
+            None => {
+                let lens: Vec<_> = v
+                    .iter()
+                    .map(|v| {
+                        let (len, body) = crate::measure(&mut self.codegen, v.clone());
+                        quote!(__len += #len; #body)
+                    })
+                    .collect();
+                self.render_len(0, self.s.implement_match(quote!(self), &lens))
+            }
+        };
+        let bodies: Vec<_> = v.into_iter().map(|v| self.codegen.gen(v)).collect();
+        let nodes = self.s.implement_match(quote!(self), &bodies);
+        let cnodes = self.s.implement_match(quote!(&self), &bodies);
+        self.template(&nodes, &cnodes, render_len, &mut tokens);
 
         tokens
     }
@@ -118,6 +348,11 @@ impl<'a> CodeGen for TextBytesCodeGen<'a> {
                 }
                 Each(a) => self.gen_each(*a),
                 IfElse(a) => self.gen_if_else(*a),
+                Call(a) => {
+                    let buf = &self.buf;
+                    call(*a, &quote!(#buf))
+                }
+                Partial(_) => unreachable!("out of line partials are generated by the template"),
             });
         }
         tokens
@@ -138,19 +373,34 @@ where
             Expr(a) => quote!((&(#a)).__render_itb(buf_ref!(#buf));),
             Each(a) => codegen.gen_each(*a),
             IfElse(a) => codegen.gen_if_else(*a),
+            Call(a) => call(*a, &buf),
+            Partial(_) => unreachable!("out of line partials are generated by the template"),
         })
     }
 
     quote! {{ #tokens }}
 }
 
+/// Call of the buffer method of an out of line partial
+fn call(mut a: syn::ExprMethodCall, buf: &TokenStream) -> TokenStream {
+    a.method = method(&a.method);
+    let a = crate::write_to(a, quote!(buf_ref!(#buf)));
+    quote!(#a;)
+}
+
+/// Buffer method of the out of line partial `ident`
+fn method(ident: &Ident) -> Ident {
+    format_ident!("{}_b", ident)
+}
+
 fn literal(a: String, buf: &TokenStream) -> TokenStream {
     let len = a.len();
     let b = a.as_bytes();
     // https://github.com/torvalds/linux/blob/master/arch/x86/lib/memcpy_64.S
     // https://software.intel.com/content/www/us/en/develop/download/intel-64-and-ia-32-architectures-optimization-reference-manual.html
     match len {
-        0 => unreachable!(),
+        // Empty templates and enum variants
+        0 => TokenStream::new(),
         // For 1 to 3 bytes, is mostly faster write byte-by-byte
         1..=3 => {
             let range: TokenStream = write_bb(b, buf);
@@ -182,6 +432,51 @@ fn write_bb(b: &[u8], buf: &TokenStream) -> TokenStream {
         .collect()
 }
 
+/// Escapes expressions with the `yarte::Escaper` type `escaper`
+pub struct EscapeBytesCodeGen<'a> {
+    buf: &'a syn::Expr,
+    escaper: TokenStream,
+}
+
+impl<'a> EscapeBytesCodeGen<'a> {
+    pub fn new(buf: &syn::Expr, escaper: TokenStream) -> EscapeBytesCodeGen {
+        EscapeBytesCodeGen { buf, escaper }
+    }
+}
+
+impl<'a> EachCodeGen for EscapeBytesCodeGen<'a> {}
+impl<'a> IfElseCodeGen for EscapeBytesCodeGen<'a> {}
+
+impl<'a> CodeGen for EscapeBytesCodeGen<'a> {
+    fn gen(&mut self, v: Vec<HIR>) -> TokenStream {
+        let mut tokens = TokenStream::new();
+        let buf = self.buf;
+        let buf = &quote!(#buf);
+        for i in v {
+            use HIR::*;
+            tokens.extend(match i {
+                Local(a) => quote!(#a),
+                Lit(a) => literal(a, buf),
+                Safe(a) => quote!((&(#a)).__render_itb_safe(buf_ref!(#buf));),
+                Expr(a) => {
+                    let escaper = &self.escaper;
+                    quote! {
+                        let _ = std::fmt::Write::write_fmt(
+                            &mut yarte::EscapeBuf::<#escaper, _>::new(buf_ref!(#buf)),
+                            format_args!("{}", &(#a)),
+                        );
+                    }
+                }
+                Each(a) => self.gen_each(*a),
+                IfElse(a) => self.gen_if_else(*a),
+                Call(a) => call(*a, buf),
+                Partial(_) => unreachable!("out of line partials are generated by the template"),
+            });
+        }
+        tokens
+    }
+}
+
 pub struct HTMLBytesCodeGen<'a> {
     buf: &'a syn::Expr,
 }
@@ -226,5 +521,10 @@ pub mod html_min {
             let buf = self.buf;
             gen(self, dom.0, quote!(#buf))
         }
+
+        fn gen_len(&mut self, v: Vec<HIR>) -> TokenStream {
+            let dom: DOMFmt = v.into();
+            crate::gen_len(self, dom.0)
+        }
     }
 }
@@ -0,0 +1,49 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+use super::{CodeGen, EachCodeGen, IfElseCodeGen, HIR};
+
+/// Escapes expressions with the `yarte::Escaper` type `escaper`
+pub struct EscapeCodeGen {
+    escaper: TokenStream,
+}
+
+impl EscapeCodeGen {
+    pub fn new(escaper: TokenStream) -> EscapeCodeGen {
+        EscapeCodeGen { escaper }
+    }
+}
+
+impl EachCodeGen for EscapeCodeGen {}
+impl IfElseCodeGen for EscapeCodeGen {}
+
+impl CodeGen for EscapeCodeGen {
+    fn gen(&mut self, v: Vec<HIR>) -> TokenStream {
+        let mut tokens = TokenStream::new();
+        for i in v {
+            use HIR::*;
+            tokens.extend(match i {
+                Local(a) => quote!(#a),
+                Lit(a) => quote!(_fmt.write_str(#a)?;),
+                Safe(a) => quote!((&(#a)).fmt(_fmt)?;),
+                Expr(a) => {
+                    let escaper = &self.escaper;
+                    quote! {
+                        std::fmt::Write::write_fmt(
+                            &mut yarte::EscapeFmt::<#escaper, _>::new(_fmt),
+                            format_args!("{}", &(#a)),
+                        )?;
+                    }
+                }
+                Each(a) => self.gen_each(*a),
+                IfElse(a) => self.gen_if_else(*a),
+                Call(a) => {
+                    let call = crate::write_to(*a, quote!(_fmt));
+                    quote!(#call?;)
+                }
+                Partial(_) => unreachable!("out of line partials are generated by the template"),
+            });
+        }
+        tokens
+    }
+}
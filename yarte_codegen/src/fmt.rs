@@ -1,7 +1,8 @@
 use proc_macro2::{Ident, TokenStream};
 use quote::{format_ident, quote};
+use syn::parse_quote;
 
-use yarte_hir::{Struct, HIR};
+use yarte_hir::{Format, PartialFn, Struct, HIR};
 
 use crate::CodeGen;
 
@@ -20,21 +21,282 @@ impl<'a, T: CodeGen> FmtCodeGen<'a, T> {
         }
     }
 
+    /// Implements the template trait with `methods` overriding the provided ones
     #[inline]
-    fn template(&self, size_hint: usize, tokens: &mut TokenStream) {
+    fn template(&self, size_hint: usize, methods: TokenStream, tokens: &mut TokenStream) {
+        let mime = crate::mime(self.s);
         tokens.extend(self.s.implement_head(
             quote!(yarte::TemplateTrait),
             &quote!(
+            const MIME: &'static str = #mime;
+
+            #methods
+
             fn size_hint() -> usize {
                 #size_hint
             }),
         ));
+        // Only `strict_safe` html templates escape the expressions of their `{{{ }}}`
+        if self.s.options.strict_safe
+            && self.s.options.format == Some(Format::Html)
+            && self.s.options.escaper.is_none()
+        {
+            let parent = &self.parent;
+            tokens.extend(
+                self.s
+                    .implement_head(quote!(#parent::TrustedHtml), &TokenStream::new()),
+            );
+        }
+    }
+
+    fn display(&mut self, nodes: Vec<HIR>, tokens: &mut TokenStream) {
+        if let ([HIR::Lit(rendered)], None) = (nodes.as_slice(), &self.s.options.error) {
+            return self.constant(rendered, tokens);
+        }
+        let render_len = match self.s.options.error {
+            Some(_) => TokenStream::new(),
+            None => {
+                let (len, body) = crate::measure(&mut self.codegen, nodes.clone());
+                self.render_len(len, body)
+            }
+        };
+        let nodes = self.codegen.gen(nodes);
+        // heuristic based on https://github.com/lfairy/maud
+        let size_hint = nodes.to_string().len();
+        self.implement(&nodes, size_hint, render_len, tokens);
+    }
+
+    fn display_enum(&mut self, variants: Vec<Vec<HIR>>, tokens: &mut TokenStream) {
+        let render_len = match self.s.options.error {
+            Some(_) => TokenStream::new(),
+            None => {
+                let lens: Vec<_> = variants
+                    .iter()
+                    .map(|v| {
+                        let (len, body) = crate::measure(&mut self.codegen, v.clone());
+                        quote!(__len += #len; #body)
+                    })
+                    .collect();
+                self.render_len(0, self.s.implement_match(quote!(self), &lens))
+            }
+        };
+        let bodies: Vec<_> = variants.into_iter().map(|v| self.codegen.gen(v)).collect();
+        let size_hint = bodies
+            .iter()
+            .map(|body| body.to_string().len())
+            .max()
+            .unwrap_or(0);
+        let nodes = self.s.implement_match(quote!(self), &bodies);
+        self.implement(&nodes, size_hint, render_len, tokens);
+    }
+
+    /// `render_len` with the constant length `len` of the literals, `body` writes the
+    /// expressions to a counting writer
+    fn render_len(&self, len: usize, body: TokenStream) -> TokenStream {
+        if body.is_empty() {
+            return quote!(
+                fn render_len(&self) -> usize {
+                    #len
+                }
+            );
+        }
+        let parent = &self.parent;
+        quote!(
+            fn render_len(&self) -> usize {
+                #[allow(unused_imports)]
+                use std::fmt::Display;
+                #[allow(unused_imports)]
+                use #parent::*;
+                #[allow(unused_mut)]
+                let mut __len: usize = #len;
+                let mut _w = LenWriter::default();
+                let _ = std::fmt::Write::write_fmt(
+                    &mut _w,
+                    format_args!(
+                        "{}",
+                        DisplayFn::new(|_fmt: &mut std::fmt::Formatter| {
+                            #body
+                            Ok(())
+                        })
+                    ),
+                );
+                __len + _w.0
+            }
+        )
+    }
+
+    /// Methods of the out of line partials, generic over the writer
+    fn partials(&mut self, fns: Vec<PartialFn>, tokens: &mut TokenStream) {
+        if fns.is_empty() {
+            return;
+        }
+        let parent = &self.parent;
+        let fns: TokenStream = fns
+            .into_iter()
+            .map(|PartialFn { mut sig, body }| {
+                let body = self.codegen.gen(body);
+                sig.generics
+                    .params
+                    .push(parse_quote!(__W: std::fmt::Write + ?Sized));
+                sig.inputs.insert(0, parse_quote!(&self));
+                sig.inputs.insert(1, parse_quote!(_w: &mut __W));
+                sig.output = parse_quote!(-> std::fmt::Result);
+                quote!(
+                    #[doc(hidden)]
+                    #sig {
+                        #[allow(unused_imports)]
+                        use std::fmt::Display;
+                        #[allow(unused_imports)]
+                        use #parent::*;
+                        std::fmt::Write::write_fmt(
+                            _w,
+                            format_args!(
+                                "{}",
+                                DisplayFn::new(|_fmt: &mut std::fmt::Formatter| {
+                                    #body
+                                    Ok(())
+                                })
+                            ),
+                        )
+                    }
+                )
+            })
+            .collect();
+        tokens.extend(self.s.implement_inherent(&fns));
     }
 
-    fn display(&mut self, nodes: Vec<HIR>, tokens: &mut TokenStream) -> usize {
+    /// Render methods of a named template
+    fn named(&mut self, name: &str, nodes: Vec<HIR>, tokens: &mut TokenStream) {
         let nodes = self.codegen.gen(nodes);
         // heuristic based on https://github.com/lfairy/maud
         let size_hint = nodes.to_string().len();
+        let parent = &self.parent;
+        let write = format_ident!("write_{}", name);
+        let render = format_ident!("render_{}", name);
+        let write_doc = format!("Writes `{name}` template");
+        let render_doc = format!("Renders `{name}` template");
+        tokens.extend(self.s.implement_inherent(&quote!(
+            #[doc = #write_doc]
+            pub fn #write(&self, _fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+                #[allow(unused_imports)]
+                use std::fmt::Display;
+                #[allow(unused_imports)]
+                use #parent::*;
+                #nodes
+                Ok(())
+            }
+
+            #[doc = #render_doc]
+            pub fn #render(&self) -> #parent::Result<String> {
+                use std::fmt::Write;
+                let mut buf = String::with_capacity(#size_hint);
+                write!(
+                    buf,
+                    "{}",
+                    #parent::DisplayFn::new(|f: &mut std::fmt::Formatter| self.#write(f))
+                )
+                .map(|_| buf)
+            }
+        )));
+    }
+
+    /// Implements `Display` and the template trait, or the `Try` template trait
+    fn implement(
+        &self,
+        nodes: &TokenStream,
+        size_hint: usize,
+        render_len: TokenStream,
+        tokens: &mut TokenStream,
+    ) {
+        match &self.s.options.error {
+            Some(error) => self.implement_try(error, nodes, size_hint, tokens),
+            None => {
+                let parent = &self.parent;
+                self.implement_display(nodes, tokens);
+                self.template(
+                    size_hint,
+                    quote!(
+                        #render_len
+
+                        fn __capacity() -> Option<&'static #parent::Capacity> {
+                            static CAPACITY: #parent::Capacity = #parent::Capacity::new();
+                            Some(&CAPACITY)
+                        }
+                    ),
+                    tokens,
+                );
+            }
+        }
+    }
+
+    /// Template without expressions, rendered at compile time to `RENDERED`
+    fn constant(&self, rendered: &str, tokens: &mut TokenStream) {
+        let parent = &self.parent;
+        tokens.extend(self.s.implement_inherent(&quote!(
+            /// Output of the template, it hasn't expressions
+            pub const RENDERED: &'static str = #rendered;
+        )));
+        self.implement_display(&quote!(_fmt.write_str(Self::RENDERED)?;), tokens);
+        self.template(
+            rendered.len(),
+            quote!(
+                fn call(&self) -> #parent::Result<String> {
+                    Ok(String::from(Self::RENDERED))
+                }
+
+                fn render_len(&self) -> usize {
+                    Self::RENDERED.len()
+                }
+
+                fn call_auto(&self) -> #parent::Result<String> {
+                    <Self as #parent::TemplateTrait>::call(self)
+                }
+
+                fn call_exact(&self) -> #parent::Result<String> {
+                    <Self as #parent::TemplateTrait>::call(self)
+                }
+            ),
+            tokens,
+        );
+    }
+
+    fn implement_try(
+        &self,
+        error: &syn::Path,
+        nodes: &TokenStream,
+        size_hint: usize,
+        tokens: &mut TokenStream,
+    ) {
+        let parent = &self.parent;
+        let mime = crate::mime(self.s);
+        tokens.extend(self.s.implement_head(
+            quote!(#parent::TryTemplateTrait),
+            &quote!(
+                type Error = #error;
+
+                const MIME: &'static str = #mime;
+
+                fn __try_fmt(
+                    &self,
+                    _fmt: &mut std::fmt::Formatter,
+                    __yarte_try: &#parent::TrySlot<Self::Error>,
+                ) -> std::fmt::Result {
+                    #[allow(unused_imports)]
+                    use std::fmt::Display;
+                    #[allow(unused_imports)]
+                    use #parent::*;
+                    #nodes
+                    Ok(())
+                }
+
+                fn size_hint() -> usize {
+                    #size_hint
+                }
+            ),
+        ));
+    }
+
+    fn implement_display(&self, nodes: &TokenStream, tokens: &mut TokenStream) {
         let parent = &self.parent;
         let func = quote!(
             fn fmt(&self, _fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
@@ -48,8 +310,6 @@ impl<'a, T: CodeGen> FmtCodeGen<'a, T> {
         );
 
         tokens.extend(self.s.implement_head(quote!(std::fmt::Display), &func));
-
-        size_hint
     }
 }
 
@@ -58,8 +318,22 @@ impl<'a, T: CodeGen> CodeGen for FmtCodeGen<'a, T> {
     fn gen(&mut self, v: Vec<HIR>) -> TokenStream {
         let mut tokens = TokenStream::new();
 
-        let size_hint = self.display(v, &mut tokens);
-        self.template(size_hint, &mut tokens);
+        let (fns, v) = crate::partials(v);
+        self.partials(fns, &mut tokens);
+        if let Some(name) = &self.s.name {
+            self.named(name, v, &mut tokens);
+            return tokens;
+        }
+
+        self.display(v, &mut tokens);
+
+        tokens
+    }
+
+    fn gen_enum(&mut self, v: Vec<Vec<HIR>>) -> TokenStream {
+        let mut tokens = TokenStream::new();
+
+        self.display_enum(v, &mut tokens);
 
         tokens
     }
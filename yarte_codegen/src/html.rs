@@ -17,6 +17,11 @@ where
             Expr(a) => quote!((&(#a)).__renders_it(_fmt)?;),
             Each(a) => codegen.gen_each(*a),
             IfElse(a) => codegen.gen_if_else(*a),
+            Call(a) => {
+                let call = crate::write_to(*a, quote!(_fmt));
+                quote!(#call?;)
+            }
+            Partial(_) => unreachable!("out of line partials are generated by the template"),
         })
     }
     tokens
@@ -47,5 +52,10 @@ pub mod html_min {
             let dom: DOMFmt = v.into();
             gen(self, dom.0)
         }
+
+        fn gen_len(&mut self, v: Vec<HIR>) -> TokenStream {
+            let dom: DOMFmt = v.into();
+            crate::gen_len(self, dom.0)
+        }
     }
 }
@@ -1,13 +1,17 @@
 #![allow(unknown_lints, clippy::match_on_vec_items)]
+use std::path::Path;
+
 use proc_macro2::TokenStream;
 use quote::quote;
+use syn::parse_quote;
 
-use yarte_hir::{Each, IfElse, HIR};
+use yarte_hir::{Each, Format, IfElse, PartialFn, Struct, HIR};
 
 #[cfg(feature = "bytes-buf")]
 mod attr_b;
 #[cfg(feature = "bytes-buf")]
 mod bytes;
+mod escape;
 #[cfg(feature = "fixed")]
 mod fixed;
 mod fmt;
@@ -18,7 +22,10 @@ pub mod wasm;
 #[cfg(feature = "bytes-buf")]
 mod write_b;
 
-pub use self::{fmt::FmtCodeGen, fn_fmt::FnFmtCodeGen, html::HTMLCodeGen, text::TextCodeGen};
+pub use self::{
+    escape::EscapeCodeGen, fmt::FmtCodeGen, fn_fmt::FnFmtCodeGen, html::HTMLCodeGen,
+    text::TextCodeGen,
+};
 
 #[cfg(any(feature = "wasm-app", feature = "wasm-server"))]
 pub use wasm::*;
@@ -28,7 +35,7 @@ pub use self::attr_b::AttrBCodeGen;
 #[cfg(all(feature = "bytes-buf", feature = "html-min"))]
 pub use self::bytes::html_min::HTMLMinBytesCodeGen;
 #[cfg(feature = "bytes-buf")]
-pub use self::bytes::{BytesCodeGen, HTMLBytesCodeGen, TextBytesCodeGen};
+pub use self::bytes::{BytesCodeGen, EscapeBytesCodeGen, HTMLBytesCodeGen, TextBytesCodeGen};
 #[cfg(all(feature = "fixed", feature = "html-min"))]
 pub use self::fixed::html_min::HTMLMinFixedCodeGen;
 #[cfg(feature = "fixed")]
@@ -38,8 +45,35 @@ pub use self::html::html_min::HTMLMinCodeGen;
 #[cfg(feature = "bytes-buf")]
 pub use self::write_b::WriteBCodeGen;
 
+/// MIME type of the template inner extension, or of its format
+pub fn mime(s: &Struct) -> String {
+    s.path
+        .file_stem()
+        .and_then(|stem| mime_guess::from_path(Path::new(stem)).first())
+        .unwrap_or_else(|| {
+            mime_guess::from_ext(s.options.format.unwrap_or(Format::Html).extension())
+                .first_or_text_plain()
+        })
+        .to_string()
+}
+
 pub trait CodeGen {
     fn gen(&mut self, v: Vec<HIR>) -> TokenStream;
+
+    /// Generates the measuring pass of `render_len`
+    ///
+    /// Literals add their length to `__len`, only expressions are written
+    fn gen_len(&mut self, v: Vec<HIR>) -> TokenStream
+    where
+        Self: Sized,
+    {
+        gen_len(self, v)
+    }
+
+    /// Generates an enum implementation with the templates of each variant
+    fn gen_enum(&mut self, _v: Vec<Vec<HIR>>) -> TokenStream {
+        unimplemented!("enum templates")
+    }
 }
 
 pub trait EachCodeGen: CodeGen {
@@ -70,3 +104,79 @@ pub trait IfElseCodeGen: CodeGen {
         tokens
     }
 }
+
+fn gen_len<C: CodeGen>(codegen: &mut C, v: Vec<HIR>) -> TokenStream {
+    let mut tokens = TokenStream::new();
+    for i in v {
+        tokens.extend(match i {
+            HIR::Lit(a) if a.is_empty() => TokenStream::new(),
+            HIR::Lit(a) => {
+                let len = a.len();
+                quote!(__len += #len;)
+            }
+            HIR::Each(a) => {
+                let Each { args, body, expr } = *a;
+                let body = codegen.gen_len(body);
+                quote!(for #expr in #args { #body })
+            }
+            HIR::IfElse(a) => {
+                let IfElse { ifs, if_else, els } = *a;
+                let (args, body) = ifs;
+                let body = codegen.gen_len(body);
+                let mut tokens = quote!(if #args { #body });
+                for (args, body) in if_else {
+                    let body = codegen.gen_len(body);
+                    tokens.extend(quote!(else if #args { #body }));
+                }
+                if let Some(body) = els {
+                    let body = codegen.gen_len(body);
+                    tokens.extend(quote!(else { #body }));
+                }
+                tokens
+            }
+            a => codegen.gen(vec![a]),
+        })
+    }
+    tokens
+}
+
+/// Out of line partials of the top level nodes `v` and the other nodes
+fn partials(v: Vec<HIR>) -> (Vec<PartialFn>, Vec<HIR>) {
+    let mut fns = vec![];
+    let nodes = v
+        .into_iter()
+        .filter_map(|x| match x {
+            HIR::Partial(a) => {
+                fns.push(*a);
+                None
+            }
+            x => Some(x),
+        })
+        .collect();
+
+    (fns, nodes)
+}
+
+/// Out of line partial `call` writing to `writer`
+fn write_to(mut call: syn::ExprMethodCall, writer: TokenStream) -> syn::ExprMethodCall {
+    call.args.insert(0, parse_quote!(#writer));
+    call
+}
+
+/// Length of the top level literals, summed at compile time, and the measuring pass
+/// of the other nodes, empty without them
+fn measure<C: CodeGen>(codegen: &mut C, v: Vec<HIR>) -> (usize, TokenStream) {
+    let mut len = 0;
+    let rest: Vec<_> = v
+        .into_iter()
+        .filter(|x| match x {
+            HIR::Lit(a) => {
+                len += a.len();
+                false
+            }
+            _ => true,
+        })
+        .collect();
+
+    (len, codegen.gen_len(rest))
+}
@@ -19,6 +19,11 @@ impl CodeGen for TextCodeGen {
                 Safe(a) | Expr(a) => quote!((&(#a)).fmt(_fmt)?;),
                 Each(a) => self.gen_each(*a),
                 IfElse(a) => self.gen_if_else(*a),
+                Call(a) => {
+                    let call = crate::write_to(*a, quote!(_fmt));
+                    quote!(#call?;)
+                }
+                Partial(_) => unreachable!("out of line partials are generated by the template"),
             });
         }
         tokens
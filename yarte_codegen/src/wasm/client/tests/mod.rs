@@ -6,10 +6,7 @@ use syn::parse2;
 
 use yarte_helpers::{config::Config, logger::log};
 use yarte_hir::{generate, visit_derive};
-use yarte_parser::{
-    emitter, parse,
-    source_map::{clean, get_cursor},
-};
+use yarte_parser::{emitter, parse, source_map::SourceMap};
 
 use crate::CodeGen;
 
@@ -25,13 +22,20 @@ fn tokens(i: TokenStream, wlog: bool) -> String {
     let s = visit_derive(&der, config).unwrap();
     let mut src = BTreeMap::new();
     src.insert(s.path.clone(), s.src.clone());
-    let sources = parse(get_cursor(&s.path, &s.src)).unwrap();
+    let mut sm = SourceMap::default();
+    let sources = parse(sm.get_cursor(&s.path, &s.src)).unwrap();
     let mut ctx = BTreeMap::new();
     ctx.insert(&s.path, sources);
 
-    let ir = generate(config, &s, &ctx, Default::default())
-        .unwrap_or_else(|e| emitter(&src, config, e.into_iter()));
-    clean();
+    let ir = generate(
+        config,
+        &s,
+        &ctx,
+        &Default::default(),
+        &sm,
+        Default::default(),
+    )
+    .unwrap_or_else(|e| emitter(&src, config, &sm, e.into_iter()));
 
     let res = WASMCodeGen::new(&s).gen(ir).to_string();
     if wlog {
@@ -1,133 +1,148 @@
 #![allow(unused_imports, dead_code)]
-use std::{
-    collections::{BTreeMap, BTreeSet},
-    iter,
-    path::PathBuf,
-};
+use std::path::PathBuf;
 
 use proc_macro::TokenStream;
-use quote::{format_ident, quote};
+use quote::quote;
 
 use syn::parse::{ParseBuffer, ParseStream};
 use syn::spanned::Spanned;
 
-use yarte_codegen::{CodeGen, FmtCodeGen, HTMLCodeGen, TextCodeGen};
-use yarte_helpers::{
-    config::{get_source, read_config_file, Config, PrintConfig},
-    logger::log,
-};
-use yarte_hir::{generate, visit_derive, HIROptions, Print, Struct};
-use yarte_parser::{emitter, parse, parse_partials, source_map, Partial};
+use yarte_build::{declared, read, sources_to_tokens, Derive};
+use yarte_codegen::{CodeGen, HTMLCodeGen, TextCodeGen};
+use yarte_helpers::config::read_config;
+use yarte_hir::{visit_derive, HIROptions, Struct};
+use yarte_parser::cache;
 
 #[cfg(feature = "json")]
 mod ser_json;
 
-type Sources<'a> = &'a BTreeMap<PathBuf, String>;
-
 macro_rules! build {
     ($i:ident, $codegen:ident, $opt:expr) => {{
-        let config_toml: &str = &read_config_file();
-        let config = &Config::new(config_toml);
+        let config = &read_config();
         let s = &match visit_derive($i, config) {
             Ok(s) => s,
             Err(ts) => return ts.into(),
         };
         proc_macro2::fallback::force();
-        let sources = &read(s.path.clone(), s.src.clone(), config);
+        let sources = &read(
+            s.path.clone(),
+            cache::intern(&s.path, s.src.clone()),
+            s.syntax,
+            config,
+        );
 
         sources_to_tokens(sources, config, s, $codegen(s), $opt)
     }};
 }
 
+/// Input is parsed to compiler tokens, previous expansions force the fallback
+fn unforce() {
+    proc_macro2::fallback::unforce();
+}
+
 #[proc_macro_derive(TemplateText, attributes(template))]
 /// Implements TemplateTrait without html escape functionality
 pub fn template(input: TokenStream) -> TokenStream {
-    fn get_codegen<'a>(s: &'a Struct) -> Box<dyn CodeGen + 'a> {
-        Box::new(FmtCodeGen::new(TextCodeGen, s, "yarte"))
-    }
-
+    unforce();
     let i = &syn::parse(input).unwrap();
-    build!(
-        i,
-        get_codegen,
-        HIROptions {
-            is_text: true,
-            ..Default::default()
-        }
-    )
-    .into()
+    Derive::TemplateText.expand(i, &read_config()).into()
 }
 
 #[proc_macro_derive(Template, attributes(template))]
 /// Implements TemplateTrait with html escape functionality
 pub fn template_html(input: TokenStream) -> TokenStream {
-    fn get_codegen<'a>(s: &'a Struct) -> Box<dyn CodeGen + 'a> {
-        Box::new(FmtCodeGen::new(HTMLCodeGen, s, "yarte"))
-    }
+    unforce();
     let i = &syn::parse(input).unwrap();
-    build!(i, get_codegen, Default::default()).into()
+    Derive::Template.expand(i, &read_config()).into()
 }
 
 #[proc_macro_derive(TemplateBytesText, attributes(template))]
 #[cfg(feature = "bytes-buf")]
 /// Implements TemplateBytesTrait without html escape functionality
 pub fn template_bytes(input: TokenStream) -> TokenStream {
-    const PARENT: &str = "yarte";
-
-    let buf_i = format_ident!("bytes_mut");
-    let buf: syn::Expr = syn::parse2(quote!(#buf_i)).unwrap();
-    let get_codegen = |s| {
-        Box::new(yarte_codegen::BytesCodeGen::new(
-            yarte_codegen::TextBytesCodeGen::new(&buf),
-            s,
-            buf_i,
-            PARENT,
-        ))
-    };
-
+    unforce();
     let i = &syn::parse(input).unwrap();
-    build!(
-        i,
-        get_codegen,
-        HIROptions {
-            is_text: true,
-            ..Default::default()
-        }
-    )
-    .into()
+    Derive::TemplateBytesText.expand(i, &read_config()).into()
 }
 
 #[proc_macro_derive(TemplateBytes, attributes(template))]
 #[cfg(feature = "bytes-buf")]
 /// Implements TemplateBytesTrait with html escape functionality
 pub fn template_html_bytes(input: TokenStream) -> TokenStream {
-    const PARENT: &str = "yarte";
-
-    let buf_i = format_ident!("bytes_mut");
-    let buf: syn::Expr = syn::parse2(quote!(#buf_i)).unwrap();
-    let get_codegen = |s| {
-        Box::new(yarte_codegen::BytesCodeGen::new(
-            yarte_codegen::HTMLBytesCodeGen::new(&buf),
-            s,
-            buf_i,
-            PARENT,
-        ))
-    };
+    unforce();
     let i = &syn::parse(input).unwrap();
-    build!(i, get_codegen, Default::default()).into()
+    Derive::TemplateBytes.expand(i, &read_config()).into()
 }
 
 #[proc_macro_derive(Serialize)]
 #[cfg(feature = "json")]
 pub fn serialize_json(i: TokenStream) -> TokenStream {
+    unforce();
     let i = syn::parse(i).unwrap();
     let tokens = ser_json::serialize_json(i);
     tokens.into()
 }
 
+#[proc_macro]
+/// Declares the struct of template head comment and implements its derives
+///
+/// ```handlebars
+/// {{!-- yarte: #[derive(Template, Debug)] pub struct Page { title: String } --!}}
+/// ```
+pub fn include_template(i: TokenStream) -> TokenStream {
+    unforce();
+    let lit: syn::LitStr = match syn::parse(i) {
+        Ok(lit) => lit,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    let config = &read_config();
+    let mut path = PathBuf::from(lit.value());
+    if path.extension().is_none() {
+        path.set_extension("hbs");
+    }
+    let file = match config.find_template(&path) {
+        Some(file) => file,
+        None => {
+            return syn::Error::new(lit.span(), format!("template {path:?} not found"))
+                .to_compile_error()
+                .into()
+        }
+    };
+
+    let src = cache::read(&file);
+    let path = path.to_str().unwrap();
+    // Errors are reported at the argument before switch to fallback tokens
+    match declared(&src, path, config.syntax) {
+        Some(Ok(_)) => (),
+        Some(Err(e)) => {
+            return syn::Error::new(
+                lit.span(),
+                format!("invalid struct declaration in template: {e}"),
+            )
+            .to_compile_error()
+            .into()
+        }
+        None => {
+            return syn::Error::new(
+                lit.span(),
+                "template head comment doesn't declare a struct, like `{{!-- yarte: struct Page; --!}}`",
+            )
+            .to_compile_error()
+            .into()
+        }
+    }
+
+    proc_macro2::fallback::force();
+    match declared(&src, path, config.syntax) {
+        Some(Ok(d)) => d.expand(config).into(),
+        _ => unreachable!(),
+    }
+}
+
 #[proc_macro]
 /// Format handlebars string in this scope with html escape functionality
 pub fn yformat_html(i: TokenStream) -> TokenStream {
+    unforce();
     const PARENT: &str = "yarte";
     fn get_codegen<'a>(_s: &'a Struct<'a>) -> Box<dyn CodeGen + 'a> {
         Box::new(yarte_codegen::FnFmtCodeGen::new(HTMLCodeGen, PARENT))
@@ -155,6 +170,7 @@ pub fn yformat_html(i: TokenStream) -> TokenStream {
 #[proc_macro]
 /// Format handlebars string in this scope without html escape functionality
 pub fn yformat(i: TokenStream) -> TokenStream {
+    unforce();
     const PARENT: &str = "yarte";
     fn get_codegen<'a>(_s: &'a Struct<'a>) -> Box<dyn CodeGen + 'a> {
         Box::new(yarte_codegen::FnFmtCodeGen::new(TextCodeGen, PARENT))
@@ -205,6 +221,7 @@ impl syn::parse::Parse for AutoArg {
 
 #[proc_macro]
 pub fn auto(i: TokenStream) -> TokenStream {
+    unforce();
     let AutoArg { path, ty, lit, .. } = match syn::parse(i) {
         Ok(arg) => arg,
         Err(e) => return e.to_compile_error().into(),
@@ -232,6 +249,7 @@ pub fn auto(i: TokenStream) -> TokenStream {
 #[cfg(feature = "bytes-buf")]
 /// Write handlebars template to `buf-min::Buffer` in this scope without html escape functionality
 pub fn ywrite(i: TokenStream) -> TokenStream {
+    unforce();
     const PARENT: &str = "yarte";
 
     let WriteArg { buf, src, .. } = match syn::parse(i) {
@@ -280,6 +298,7 @@ pub fn ywrite(i: TokenStream) -> TokenStream {
 #[cfg(feature = "bytes-buf")]
 /// Write handlebars template to `buf-min::Buffer` in this scope with html escape functionality
 pub fn ywrite_html(i: TokenStream) -> TokenStream {
+    unforce();
     const PARENT: &str = "yarte";
 
     let WriteArg { buf, src, .. } = match syn::parse(i) {
@@ -328,6 +347,7 @@ pub fn ywrite_html(i: TokenStream) -> TokenStream {
 #[cfg(all(feature = "html-min", feature = "bytes-buf"))]
 /// Write handlebars template to `buf-min::Buffer` in this scope without html escape functionality
 pub fn ywrite_min(i: TokenStream) -> TokenStream {
+    unforce();
     const PARENT: &str = "yarte";
 
     let WriteArg { buf, src, .. } = match syn::parse(i) {
@@ -391,6 +411,7 @@ impl syn::parse::Parse for TemplateArg {
 #[proc_macro_attribute]
 #[cfg(feature = "bytes-buf")]
 pub fn yarte(args: TokenStream, input: TokenStream) -> TokenStream {
+    unforce();
     const PARENT: &str = "yarte";
 
     let args_is_empty = args.is_empty();
@@ -461,75 +482,3 @@ impl syn::parse::Parse for WriteArg {
         })
     }
 }
-
-fn sources_to_tokens<'a>(
-    sources: Sources,
-    config: &Config,
-    s: &'a Struct<'a>,
-    mut codegen: Box<dyn CodeGen + 'a>,
-    opt: HIROptions,
-) -> proc_macro2::TokenStream {
-    let mut parsed = BTreeMap::new();
-    for (p, src) in sources {
-        let nodes = match parse(source_map::get_cursor(p, src)) {
-            Ok(n) => n,
-            Err(e) => emitter(sources, config, iter::once(e)),
-        };
-        parsed.insert(p, nodes);
-    }
-
-    if cfg!(debug_assertions) && config.print_override == PrintConfig::Ast
-        || config.print_override == PrintConfig::All
-        || s.print == Print::Ast
-        || s.print == Print::All
-    {
-        eprintln!("{parsed:?}\n");
-    }
-
-    let hir = generate(config, s, &parsed, opt)
-        .unwrap_or_else(|e| emitter(sources, config, e.into_iter()));
-    // when multiple templates
-    source_map::clean();
-
-    let tokens = codegen.gen(hir);
-
-    if cfg!(debug_assertions) && config.print_override == PrintConfig::Code
-        || config.print_override == PrintConfig::All
-        || s.print == Print::Code
-        || s.print == Print::All
-    {
-        log(&tokens.to_string());
-    }
-
-    tokens
-}
-
-fn read(path: PathBuf, src: String, config: &Config) -> BTreeMap<PathBuf, String> {
-    let mut stack = vec![(path, src)];
-    let mut visited = BTreeMap::new();
-
-    while let Some((path, src)) = stack.pop() {
-        let partials = parse_partials(&src);
-
-        let partials = match partials {
-            Ok(n) => n
-                .iter()
-                .map(|Partial(_, partial, _)| config.resolve_partial(&path, partial.t()))
-                .collect::<BTreeSet<_>>(),
-            Err(e) => {
-                visited.insert(path, src);
-                emitter(&visited, config, iter::once(e))
-            }
-        };
-        visited.insert(path, src);
-
-        for partial in partials {
-            if !visited.contains_key(&partial) {
-                let src = get_source(partial.as_path());
-                stack.push((partial, src));
-            }
-        }
-    }
-
-    visited
-}
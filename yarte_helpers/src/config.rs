@@ -4,55 +4,138 @@
 //!   - **`dir`**: name of template directory. If no value is given, a default directory
 //! **`templates`** will be used. If the defined directory is not found, an error
 //! will prompt.
+//!   - **`dirs`**: array of tables with more template directories, where templates and
+//! partials not found in `dir` are looked up in order. Each one has a `path` relative to
+//! the crate manifest or, with `crate`, relative to the manifest of that path dependency.
+//! A `crate` without `path` uses the template directories of the dependency configuration.
 //!   - **`debug`**: type of output of debug mode. The code and/or  ast generated by  Yarte
-//! can be visualize, to do so, at most one of three possible values has to be given:
-//! `code`, `ast`, or `all`.
+//! can be visualize, to do so, at most one of four possible values has to be given:
+//! `code`, `ast`, `size` or `all`.
+//!   - **`strict`**: `true` restricts the Rust of all templates, like `#[template(strict)]`.
+//!   - **`inline_partials`**: `false` renders the partials of all templates by functions,
+//! like `#[template(inline_partials = false)]`.
 //!
 //! - **`partials`** (partials aliasing - optional): each entry must be of the type
 //! `name_alias = "./alias/path/"`, where `./` makes reference to `dir` value. Path
 //! must exist, or error will be prompt. If the tag `partials` doesn't exist no aliasing
 //! will be possible.
 //!
+//! - **`templates`** (build script manifest - optional): array of tables with the
+//! templates generated by `yarte_build`, each one with the `struct` head, the template
+//! `path` and optionally the list of yarte `derive`s, `Template` by default.
+//!
+//! - **`syntax`** (template delimiters - optional): `open` and `close` delimiters of all
+//! templates, two ASCII punctuation characters each. By default `{{` and `}}`.
+//!
+//! - **`sanitize`** (`@sanitize` allowlist - optional): written `tags`, their written
+//! `attributes` and the `url_schemes` of absolute urls. Lists not given are the default ones.
+//! Raw text tags, like `script`, `on*` attributes and the `javascript`, `vbscript` and `data`
+//! schemes are compile errors.
+//!
+//! - **`strict`** (strict templates allowlist - optional): `macros`, `methods` and `functions`,
+//! by their path as written in templates, available in strict templates besides the default ones.
+//!
 //! ### Example of a config file
 //! ```toml
 //! [main]
 //! dir = "templates"
 //! debug = "all"
 //!
+//! [[main.dirs]]
+//! path = "theme"
+//!
+//! [[main.dirs]]
+//! crate = "components"
+//!
 //! [partials]
 //! alias = "./deep/more/deep"
+//!
+//! [[templates]]
+//! struct = "Card<'a>"
+//! path = "card"
+//! derive = ["TemplateText"]
+//!
+//! [sanitize]
+//! tags = ["p", "a", "img"]
+//! attributes = ["href", "src", "alt"]
+//!
+//! [strict]
+//! methods = ["display_name"]
+//! functions = ["crate::fmt_date"]
 //! ```
 //!
 //! With this configuration, the user can call `alias` in a partial instance with
 //! `{{> alias context}}` or `{{> alias}}` if the current context is well defined.
 //!
 use std::{
+    cell::RefCell,
     collections::BTreeMap,
     env, fs,
     path::{Path, PathBuf},
+    rc::Rc,
+    str,
+    time::SystemTime,
 };
 
 use serde::Deserialize;
 
+/// Ordered template directories, lookups fall through from the first to the last
 #[derive(Debug)]
-pub struct Dir(PathBuf);
+pub struct Dir(Vec<PathBuf>);
 
 impl Dir {
-    pub fn get_template(&self, path: &Path) -> PathBuf {
-        let template = self.0.join(path);
+    /// Directories of the crate at `root`, the manifests and configs read are added to `files`
+    fn new(root: &Path, main: Option<Main>, files: &mut Vec<PathBuf>) -> Dir {
+        let mut visited = vec![manifest_id(root)];
+        Dir(Dir::collect(root, main, &mut visited, files)
+            .into_iter()
+            .map(|p| if p.exists() { normalize(p) } else { p })
+            .collect())
+    }
 
-        if template.exists() {
-            template
-        } else {
-            panic!("template not found in directory {template:?}")
+    /// Directories of the crate at `root`, `visited` are the crates already collected
+    ///
+    /// Dependencies exporting directories to each other stop at the first repeated crate
+    fn collect(
+        root: &Path,
+        main: Option<Main>,
+        visited: &mut Vec<PathBuf>,
+        files: &mut Vec<PathBuf>,
+    ) -> Vec<PathBuf> {
+        let (dir, dirs) = main.map(|x| (x.dir, x.dirs)).unwrap_or_default();
+        let mut all = vec![root.join(dir.as_deref().unwrap_or(DEFAULT_DIR))];
+        for entry in dirs.unwrap_or_default() {
+            match (entry.krate, entry.path) {
+                (Some(krate), path) => {
+                    let dep = dependency_dir(root, &krate, files);
+                    match path {
+                        Some(path) => all.push(dep.join(path)),
+                        // Directories exported by the dependency config
+                        None => {
+                            let id = manifest_id(&dep);
+                            if !visited.contains(&id) {
+                                visited.push(id);
+                                let main = dep_config(&dep, files).main;
+                                all.extend(Dir::collect(&dep, main, visited, files));
+                            }
+                        }
+                    }
+                }
+                (None, Some(path)) => all.push(root.join(path)),
+                (None, None) => panic!("`main.dirs` entries need a `path` or a `crate`"),
+            }
         }
+
+        all
     }
-}
 
-impl From<Option<String>> for Dir {
-    fn from(p: Option<String>) -> Self {
-        let root = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
-        Dir(p.map_or_else(|| root.join(DEFAULT_DIR), |v| root.join(v)))
+    pub fn find_template(&self, path: &Path) -> Option<PathBuf> {
+        self.0.iter().map(|dir| dir.join(path)).find(|t| t.exists())
+    }
+
+    pub fn get_template(&self, path: &Path) -> PathBuf {
+        self.find_template(path)
+            .unwrap_or_else(|| panic!("template {path:?} not found in directories {:?}", self.0))
     }
 }
 
@@ -61,6 +144,7 @@ pub enum PrintConfig {
     All,
     Ast,
     Code,
+    Size,
     None,
 }
 
@@ -70,6 +154,7 @@ impl From<Option<String>> for PrintConfig {
             Some("all") => PrintConfig::All,
             Some("ast") => PrintConfig::Ast,
             Some("code") => PrintConfig::Code,
+            Some("size") => PrintConfig::Size,
             _ => PrintConfig::None,
         }
     }
@@ -81,26 +166,77 @@ pub struct Config {
     alias: BTreeMap<String, String>,
     pub print_override: PrintConfig,
     pub debug: PrintOption,
+    pub templates: Vec<TemplateEntry>,
+    pub syntax: Syntax,
+    pub sanitize: Option<SanitizeConfig>,
+    /// Manifests and dependency configs read besides the config file
+    files: Vec<PathBuf>,
+    /// Strict templates by default
+    pub strict: bool,
+    pub strict_allow: StrictConfig,
+    /// Partials are inlined by default, they're rendered by functions otherwise
+    pub inline_partials: bool,
 }
 
 impl Config {
     pub fn new(s: &str) -> Config {
         let raw: RawConfig =
             toml::from_str(s).unwrap_or_else(|_| panic!("invalid TOML in {CONFIG_FILE_NAME}"));
-        let (dir, print) = raw.main.map(|x| (x.dir, x.debug)).unwrap_or((None, None));
+        let root = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+        let print = raw.main.as_ref().and_then(|x| x.debug.clone());
+        let strict = raw.main.as_ref().and_then(|x| x.strict).unwrap_or(false);
+        let inline_partials = raw
+            .main
+            .as_ref()
+            .and_then(|x| x.inline_partials)
+            .unwrap_or(true);
+        let mut files = vec![];
 
         Config {
-            dir: Dir::from(dir),
+            dir: Dir::new(&root, raw.main, &mut files),
             print_override: PrintConfig::from(print),
             debug: raw.debug.unwrap_or_default(),
             alias: raw.partials.unwrap_or_default(),
+            templates: raw.templates.unwrap_or_default(),
+            syntax: raw
+                .syntax
+                .map(|x| {
+                    Syntax::new(&x.open, &x.close)
+                        .unwrap_or_else(|e| panic!("{e} in {CONFIG_FILE_NAME}"))
+                })
+                .unwrap_or_default(),
+            sanitize: raw.sanitize,
+            strict,
+            strict_allow: raw.strict.unwrap_or_default(),
+            inline_partials,
+            files,
         }
     }
 
+    /// Modification times of the config file at `path` and of the files read with it
+    fn modified(&self, path: &Path) -> Vec<Option<SystemTime>> {
+        [path]
+            .into_iter()
+            .chain(self.files.iter().map(PathBuf::as_path))
+            .map(|file| fs::metadata(file).and_then(|m| m.modified()).ok())
+            .collect()
+    }
+
+    /// Main template directory
     pub fn get_dir(&self) -> &PathBuf {
+        &self.dir.0[0]
+    }
+
+    /// All template directories in lookup order
+    pub fn get_dirs(&self) -> &[PathBuf] {
         &self.dir.0
     }
 
+    /// Path of the template in the first directory where it exists
+    pub fn find_template(&self, path: &Path) -> Option<PathBuf> {
+        self.dir.find_template(path)
+    }
+
     pub fn get_template(&self, path: &Path) -> (PathBuf, String) {
         let path = self.dir.get_template(path);
         let src = get_source(path.as_path());
@@ -133,8 +269,20 @@ impl Config {
         } else {
             let mut parent = parent.to_owned();
             parent.pop();
-            parent.push(buf);
-            normalize(parent)
+            // Relative to the template directory so it falls through the other directories
+            let found = self
+                .dir
+                .0
+                .iter()
+                .find_map(|dir| parent.strip_prefix(dir).ok())
+                .and_then(|rel| self.dir.find_template(&rel.join(&buf)));
+            match found {
+                Some(path) => normalize(path),
+                None => {
+                    parent.push(buf);
+                    normalize(parent)
+                }
+            }
         }
     }
 }
@@ -154,12 +302,208 @@ struct RawConfig {
     main: Option<Main>,
     debug: Option<PrintOption>,
     partials: Option<BTreeMap<String, String>>,
+    templates: Option<Vec<TemplateEntry>>,
+    syntax: Option<RawSyntax>,
+    sanitize: Option<SanitizeConfig>,
+    strict: Option<StrictConfig>,
+}
+
+#[derive(Deserialize)]
+struct RawSyntax {
+    open: String,
+    close: String,
 }
 
 #[derive(Deserialize)]
 struct Main {
     dir: Option<String>,
+    dirs: Option<Vec<DirEntry>>,
     debug: Option<String>,
+    strict: Option<bool>,
+    inline_partials: Option<bool>,
+}
+
+/// Entry of `[[main.dirs]]`
+#[derive(Deserialize)]
+struct DirEntry {
+    /// Directory relative to the manifest of `crate` or of the current crate
+    path: Option<String>,
+    /// Path dependency which exports the templates
+    #[serde(rename = "crate")]
+    krate: Option<String>,
+}
+
+fn dep_config(root: &Path, files: &mut Vec<PathBuf>) -> RawConfig {
+    let path = root.join(CONFIG_FILE_NAME);
+    let file = fs::read_to_string(&path).unwrap_or_default();
+    let config = toml::from_str(&file).unwrap_or_else(|_| panic!("invalid TOML in {path:?}"));
+    files.push(path);
+    config
+}
+
+/// Identity of the crate at `root`, equal for every path to the same manifest directory
+fn manifest_id(root: &Path) -> PathBuf {
+    root.canonicalize().unwrap_or_else(|_| root.to_owned())
+}
+
+fn read_manifest(root: &Path, files: &mut Vec<PathBuf>) -> toml::Table {
+    let path = root.join("Cargo.toml");
+    let file = fs::read_to_string(&path).unwrap_or_else(|_| panic!("unable to read {path:?}"));
+    let manifest = toml::from_str(&file).unwrap_or_else(|_| panic!("invalid TOML in {path:?}"));
+    files.push(path);
+    manifest
+}
+
+/// Manifest directory of the path dependency `name` of the crate at `root`
+fn dependency_dir(root: &Path, name: &str, files: &mut Vec<PathBuf>) -> PathBuf {
+    let manifest = read_manifest(root, files);
+    let (key, dep) = ["dependencies", "dev-dependencies", "build-dependencies"]
+        .iter()
+        .filter_map(|table| manifest.get(*table)?.as_table())
+        .flat_map(|deps| deps.iter())
+        .find(|(key, dep)| {
+            *key == name || dep.get("package").and_then(|p| p.as_str()) == Some(name)
+        })
+        .unwrap_or_else(|| panic!("`{name}` isn't a dependency of {root:?}"));
+
+    if let Some(path) = dep.get("path").and_then(|p| p.as_str()) {
+        return root.join(path);
+    }
+    if dep.get("workspace").and_then(|w| w.as_bool()) == Some(true) {
+        // Inherited from the first ancestor manifest with a workspace
+        let path = root
+            .ancestors()
+            .filter(|ws| ws.join("Cargo.toml").exists())
+            .find_map(|ws| {
+                let deps = read_manifest(ws, files)
+                    .get("workspace")?
+                    .get("dependencies")?
+                    .clone();
+                Some(ws.join(deps.get(key)?.get("path")?.as_str()?))
+            });
+        if let Some(path) = path {
+            return path;
+        }
+    }
+
+    panic!("`{name}` must be a path dependency to use its templates")
+}
+
+/// Template generated by build script
+#[derive(Debug, Deserialize)]
+pub struct TemplateEntry {
+    /// Struct head, identifier and generics, like `Card<'a>`
+    #[serde(rename = "struct")]
+    pub head: String,
+    /// Template path relative to template directory
+    pub path: String,
+    /// Yarte derives to implement
+    #[serde(default)]
+    pub derive: Vec<String>,
+}
+
+/// Allowlist of `@sanitize` helper, `None` lists are the default ones
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SanitizeConfig {
+    pub tags: Option<Vec<String>>,
+    pub attributes: Option<Vec<String>>,
+    pub url_schemes: Option<Vec<String>>,
+}
+
+/// Allowlist of strict templates, added to the default one
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StrictConfig {
+    #[serde(default)]
+    pub macros: Vec<String>,
+    #[serde(default)]
+    pub methods: Vec<String>,
+    /// Paths of functions, as written in templates
+    #[serde(default)]
+    pub functions: Vec<String>,
+}
+
+/// Template delimiters, `{{` and `}}` by default
+///
+/// Both are two ASCII punctuation characters. Safe expressions repeat the inner
+/// characters, `{{{ .. }}}` with the default ones and `[[[ .. ]]]` with `[[` and `]]`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Syntax {
+    open: [u8; 2],
+    // with leading whitespace control character
+    close: [u8; 3],
+}
+
+impl Default for Syntax {
+    fn default() -> Self {
+        Syntax {
+            open: *b"{{",
+            close: *b"~}}",
+        }
+    }
+}
+
+impl Syntax {
+    pub fn new(open: &str, close: &str) -> Result<Syntax, String> {
+        fn delimiter(s: &str, reserved: &[u8]) -> Result<[u8; 2], String> {
+            match s.as_bytes() {
+                &[a, b]
+                    if [a, b]
+                        .iter()
+                        .all(|x| x.is_ascii_punctuation() && !reserved.contains(x)) =>
+                {
+                    Ok([a, b])
+                }
+                _ => Err(format!(
+                    "invalid delimiter `{s}`, expected two ASCII punctuation characters \
+                     other than `{}`",
+                    str::from_utf8(reserved).unwrap()
+                )),
+            }
+        }
+
+        let open = delimiter(open, b"~!?#>$/@\"'")?;
+        let [a, b] = delimiter(close, b"~!\"'")?;
+        if open == [a, b] {
+            return Err(format!("open and close delimiters are equal `{close}`"));
+        }
+
+        Ok(Syntax {
+            open,
+            close: [b'~', a, b],
+        })
+    }
+
+    /// Open delimiter
+    pub fn open(&self) -> &str {
+        str::from_utf8(&self.open).unwrap()
+    }
+
+    /// Close delimiter
+    pub fn close(&self) -> &str {
+        str::from_utf8(&self.close[1..]).unwrap()
+    }
+
+    /// Close delimiter with whitespace control, `~}}`
+    pub fn ws_close(&self) -> &str {
+        str::from_utf8(&self.close).unwrap()
+    }
+
+    /// First character of open delimiter
+    pub fn open_start(&self) -> char {
+        self.open[0] as char
+    }
+
+    /// Second character of open delimiter, repeated in safe expressions
+    pub fn open_expr(&self) -> u8 {
+        self.open[1]
+    }
+
+    /// First character of close delimiter, repeated in safe expressions
+    pub fn close_expr(&self) -> char {
+        self.close[1] as char
+    }
 }
 
 #[derive(Debug, Deserialize, Default)]
@@ -185,6 +529,35 @@ pub fn read_config_file() -> String {
     }
 }
 
+/// Read and parse the config file of the current crate
+///
+/// The result is reused by following calls in the same thread until the modification time
+/// of the file, or of a manifest or dependency config read with it, changes. So it's parsed
+/// once per compilation instead of once per derive
+pub fn read_config() -> Rc<Config> {
+    type Configs = BTreeMap<PathBuf, (Vec<Option<SystemTime>>, Rc<Config>)>;
+    thread_local! {
+        static CONFIGS: RefCell<Configs> = RefCell::new(Default::default());
+    }
+
+    let path = config_file_path();
+    CONFIGS.with(|c| {
+        let mut c = c.borrow_mut();
+        match c.get(&path) {
+            Some((m, config)) if *m == config.modified(&path) => config.clone(),
+            _ => {
+                let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+                let config = Rc::new(Config::new(&read_config_file()));
+                let mut all = config.modified(&path);
+                // A change while reading it is seen by the next call
+                all[0] = modified;
+                c.insert(path, (all, config.clone()));
+                config
+            }
+        }
+    })
+}
+
 #[inline]
 pub fn config_file_path() -> PathBuf {
     PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap()).join(CONFIG_FILE_NAME)
@@ -209,3 +582,39 @@ pub fn get_source(path: &Path) -> String {
 
 static CONFIG_FILE_NAME: &str = "yarte.toml";
 static DEFAULT_DIR: &str = "templates";
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_dependency_cycle() {
+        let root = env::temp_dir().join(format!("yarte-dirs-{}", std::process::id()));
+        for (name, dep) in [("a", "b"), ("b", "a")] {
+            let dir = root.join(name);
+            fs::create_dir_all(dir.join("templates")).unwrap();
+            fs::write(
+                dir.join("Cargo.toml"),
+                format!("[dependencies]\n{dep} = {{ path = \"../{dep}\" }}\n"),
+            )
+            .unwrap();
+            fs::write(
+                dir.join(CONFIG_FILE_NAME),
+                format!("[[main.dirs]]\ncrate = \"{dep}\"\n"),
+            )
+            .unwrap();
+        }
+
+        let a = root.join("a");
+        let mut files = vec![];
+        let dirs = Dir::new(&a, dep_config(&a, &mut vec![]).main, &mut files).0;
+        fs::remove_dir_all(&root).unwrap();
+        assert_eq!(dirs.len(), 2);
+        assert!(dirs[0].ends_with("a/templates"));
+        assert!(dirs[1].ends_with("b/templates"));
+        assert_eq!(files.len(), 3);
+        assert!(files[0].ends_with("a/Cargo.toml"));
+        assert!(files[1].ends_with("b/yarte.toml"));
+        assert!(files[2].ends_with("b/Cargo.toml"));
+    }
+}
@@ -0,0 +1,76 @@
+//! Adaptive buffer capacity of the `call_auto` template methods
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+
+/// Smaller outputs move the mark `1 / DECAY` of the distance to them
+const DECAY: usize = 16;
+
+/// High-water mark of the output length of a template
+///
+/// Grows to any longer output and decays to shorter ones, so an outlier
+/// doesn't keep the capacity of every later render
+///
+/// Derives keep one in a `static` of the template impl, so all instantiations
+/// of a generic template share its mark
+///
+/// ```
+/// use yarte_helpers::helpers::capacity::Capacity;
+///
+/// static CAPACITY: Capacity = Capacity::new();
+///
+/// CAPACITY.update(1600);
+/// assert_eq!(CAPACITY.get(), 1600);
+/// CAPACITY.update(0);
+/// assert_eq!(CAPACITY.get(), 1500);
+/// ```
+#[derive(Debug, Default)]
+pub struct Capacity(AtomicUsize);
+
+impl Capacity {
+    pub const fn new() -> Capacity {
+        Capacity(AtomicUsize::new(0))
+    }
+
+    /// Capacity for the next render, `0` before the first one
+    #[inline]
+    pub fn get(&self) -> usize {
+        self.0.load(Relaxed)
+    }
+
+    /// Learns the output length of a render
+    ///
+    /// Concurrent renders may lose updates, the next ones make up for them
+    #[inline]
+    pub fn update(&self, len: usize) {
+        let mark = self.get();
+        if len > mark {
+            self.0.store(len, Relaxed);
+        } else if len < mark {
+            self.0.store(mark - (mark - len) / DECAY, Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_capacity() {
+        let c = Capacity::new();
+        assert_eq!(c.get(), 0);
+        for _ in 0..10 {
+            c.update(100);
+        }
+        assert_eq!(c.get(), 100);
+
+        c.update(10_000);
+        assert_eq!(c.get(), 10_000);
+        let renders = (0..200).take_while(|_| {
+            c.update(100);
+            c.get() > 200
+        });
+        assert!(renders.count() < 100);
+        c.update(120);
+        assert!(c.get() >= 120);
+    }
+}
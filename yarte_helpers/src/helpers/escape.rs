@@ -0,0 +1,259 @@
+//! Escapers of template outputs
+//!
+//! Escapers replace characters, templates write the surrounding quotes,
+//! like `"{{ field }}"` in csv or `'{{ arg }}'` in shell scripts
+use std::{borrow::Cow, fmt, marker::PhantomData};
+
+#[cfg(feature = "bytes-buf")]
+use buf_min::Buffer;
+
+/// Escapes expressions of templates selected with `#[template(escape = "path::To::Escaper")]`
+///
+/// ```
+/// use yarte_helpers::helpers::escape::Escaper;
+///
+/// struct Quotes;
+///
+/// impl Escaper for Quotes {
+///     fn escape_byte(byte: u8) -> Option<&'static str> {
+///         match byte {
+///             b'"' => Some("\\\""),
+///             _ => None,
+///         }
+///     }
+/// }
+///
+/// assert_eq!(Quotes::escape("a\"b"), "a\\\"b");
+/// ```
+pub trait Escaper {
+    /// Replacement of ascii `byte`, `None` when it's written as is
+    fn escape_byte(byte: u8) -> Option<&'static str>;
+
+    /// Writes `s` escaped to a formatter
+    fn escape_fmt<W: fmt::Write + ?Sized>(s: &str, w: &mut W) -> fmt::Result {
+        let mut last = 0;
+        for (i, b) in s.bytes().enumerate() {
+            if let Some(esc) = Self::escape_byte(b) {
+                // Escaped bytes are ascii so `i` is a char boundary
+                w.write_str(&s[last..i])?;
+                w.write_str(esc)?;
+                last = i + 1;
+            }
+        }
+        w.write_str(&s[last..])
+    }
+
+    /// Writes `s` escaped to a buffer
+    #[cfg(feature = "bytes-buf")]
+    fn escape_buf<B: Buffer>(s: &str, buf: &mut B) {
+        let _ = Self::escape_fmt(s, &mut BufWriter(buf));
+    }
+
+    /// Escaped `s`
+    fn escape(s: &str) -> Cow<'_, str> {
+        if s.bytes().any(|b| Self::escape_byte(b).is_some()) {
+            let mut out = String::with_capacity(s.len() + 8);
+            let _ = Self::escape_fmt(s, &mut out);
+            Cow::Owned(out)
+        } else {
+            Cow::Borrowed(s)
+        }
+    }
+}
+
+/// Xml escaper
+pub struct XmlEscaper;
+
+impl Escaper for XmlEscaper {
+    #[inline]
+    fn escape_byte(byte: u8) -> Option<&'static str> {
+        match byte {
+            b'&' => Some("&amp;"),
+            b'<' => Some("&lt;"),
+            b'>' => Some("&gt;"),
+            b'"' => Some("&quot;"),
+            b'\'' => Some("&apos;"),
+            _ => None,
+        }
+    }
+}
+
+/// Json string content escaper
+pub struct JsonEscaper;
+
+impl Escaper for JsonEscaper {
+    #[inline]
+    fn escape_byte(byte: u8) -> Option<&'static str> {
+        const CONTROL: [&str; 32] = [
+            "\\u0000", "\\u0001", "\\u0002", "\\u0003", "\\u0004", "\\u0005", "\\u0006", "\\u0007",
+            "\\b", "\\t", "\\n", "\\u000b", "\\f", "\\r", "\\u000e", "\\u000f", "\\u0010",
+            "\\u0011", "\\u0012", "\\u0013", "\\u0014", "\\u0015", "\\u0016", "\\u0017", "\\u0018",
+            "\\u0019", "\\u001a", "\\u001b", "\\u001c", "\\u001d", "\\u001e", "\\u001f",
+        ];
+        match byte {
+            b'"' => Some("\\\""),
+            b'\\' => Some("\\\\"),
+            b if b < 0x20 => Some(CONTROL[b as usize]),
+            _ => None,
+        }
+    }
+}
+
+/// LaTeX text escaper
+pub struct LatexEscaper;
+
+impl Escaper for LatexEscaper {
+    #[inline]
+    fn escape_byte(byte: u8) -> Option<&'static str> {
+        match byte {
+            b'\\' => Some("\\textbackslash{}"),
+            b'{' => Some("\\{"),
+            b'}' => Some("\\}"),
+            b'$' => Some("\\$"),
+            b'&' => Some("\\&"),
+            b'#' => Some("\\#"),
+            b'%' => Some("\\%"),
+            b'_' => Some("\\_"),
+            b'^' => Some("\\textasciicircum{}"),
+            b'~' => Some("\\textasciitilde{}"),
+            _ => None,
+        }
+    }
+}
+
+/// Csv quoted field escaper
+pub struct CsvEscaper;
+
+impl Escaper for CsvEscaper {
+    #[inline]
+    fn escape_byte(byte: u8) -> Option<&'static str> {
+        match byte {
+            b'"' => Some("\"\""),
+            _ => None,
+        }
+    }
+}
+
+/// Shell single quoted argument escaper
+pub struct ShellEscaper;
+
+impl Escaper for ShellEscaper {
+    #[inline]
+    fn escape_byte(byte: u8) -> Option<&'static str> {
+        match byte {
+            b'\'' => Some("'\\''"),
+            _ => None,
+        }
+    }
+}
+
+/// `fmt::Write` adapter escaping with `E`
+pub struct EscapeFmt<'a, E, W: ?Sized>(&'a mut W, PhantomData<E>);
+
+impl<'a, E: Escaper, W: fmt::Write + ?Sized> EscapeFmt<'a, E, W> {
+    #[inline]
+    pub fn new(w: &'a mut W) -> Self {
+        EscapeFmt(w, PhantomData)
+    }
+}
+
+impl<'a, E: Escaper, W: fmt::Write + ?Sized> fmt::Write for EscapeFmt<'a, E, W> {
+    #[inline]
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        E::escape_fmt(s, self.0)
+    }
+}
+
+/// `fmt::Write` adapter escaping with `E` to a buffer
+#[cfg(feature = "bytes-buf")]
+pub struct EscapeBuf<'a, E, B>(&'a mut B, PhantomData<E>);
+
+#[cfg(feature = "bytes-buf")]
+impl<'a, E: Escaper, B: Buffer> EscapeBuf<'a, E, B> {
+    #[inline]
+    pub fn new(buf: &'a mut B) -> Self {
+        EscapeBuf(buf, PhantomData)
+    }
+}
+
+#[cfg(feature = "bytes-buf")]
+impl<'a, E: Escaper, B: Buffer> fmt::Write for EscapeBuf<'a, E, B> {
+    #[inline]
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        E::escape_buf(s, self.0);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "bytes-buf")]
+struct BufWriter<'a, B>(&'a mut B);
+
+#[cfg(feature = "bytes-buf")]
+impl<'a, B: Buffer> fmt::Write for BufWriter<'a, B> {
+    #[inline]
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.extend(s);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fmt::Write;
+
+    #[test]
+    fn test_xml() {
+        assert!(matches!(XmlEscaper::escape("foo"), Cow::Borrowed("foo")));
+        assert_eq!(
+            XmlEscaper::escape("<a b='c'>&\"</a>"),
+            "&lt;a b=&apos;c&apos;&gt;&amp;&quot;&lt;/a&gt;"
+        );
+        assert_eq!(XmlEscaper::escape("ñ<ñ"), "ñ&lt;ñ");
+    }
+
+    #[test]
+    fn test_json() {
+        assert!(matches!(JsonEscaper::escape("foo"), Cow::Borrowed("foo")));
+        assert_eq!(
+            JsonEscaper::escape("a\"b\\c\nd\u{1}"),
+            "a\\\"b\\\\c\\nd\\u0001"
+        );
+    }
+
+    #[test]
+    fn test_latex() {
+        assert_eq!(
+            LatexEscaper::escape("50% of $x_1 & {y}"),
+            "50\\% of \\$x\\_1 \\& \\{y\\}"
+        );
+        assert_eq!(
+            LatexEscaper::escape("\\^~"),
+            "\\textbackslash{}\\textasciicircum{}\\textasciitilde{}"
+        );
+    }
+
+    #[test]
+    fn test_csv_shell() {
+        assert_eq!(CsvEscaper::escape("a \"b\", c"), "a \"\"b\"\", c");
+        assert_eq!(ShellEscaper::escape("it's"), "it'\\''s");
+    }
+
+    #[test]
+    fn test_adapters() {
+        let mut out = String::new();
+        let (lt, quote) = ('<', "\"");
+        write!(EscapeFmt::<XmlEscaper, _>::new(&mut out), "{lt}{}", 1).unwrap();
+        write!(EscapeFmt::<JsonEscaper, _>::new(&mut out), "{quote}").unwrap();
+        assert_eq!(out, "&lt;1\\\"");
+    }
+
+    #[cfg(feature = "bytes-buf")]
+    #[test]
+    fn test_buf() {
+        let mut buf = String::new();
+        let q = "\"q\"";
+        write!(EscapeBuf::<CsvEscaper, _>::new(&mut buf), "{q}").unwrap();
+        assert_eq!(buf, "\"\"q\"\"");
+    }
+}
@@ -11,7 +11,13 @@ pub struct Aligned256<T>(pub T);
 
 #[cfg(feature = "big-num-32")]
 pub mod big_num_32;
+pub mod capacity;
 pub mod display_fn;
+pub mod escape;
+pub mod render_len;
+pub mod safe;
+pub mod sanitize;
+pub mod template_error;
 
 #[cfg(any(feature = "bytes-buf", feature = "json"))]
 mod bytes;
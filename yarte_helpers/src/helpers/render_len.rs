@@ -0,0 +1,102 @@
+//! Counting writers of the measuring pass of `render_len`
+use std::fmt;
+
+/// `fmt::Write` which only counts the written bytes
+///
+/// ```
+/// use std::fmt::Write;
+/// use yarte_helpers::helpers::render_len::LenWriter;
+///
+/// let mut w = LenWriter::default();
+/// write!(w, "<td>{}</td>", 100).unwrap();
+/// assert_eq!(w.0, 12);
+/// ```
+#[derive(Debug, Default)]
+pub struct LenWriter(pub usize);
+
+impl fmt::Write for LenWriter {
+    #[inline]
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0 += s.len();
+        Ok(())
+    }
+}
+
+#[cfg(feature = "bytes-buf")]
+pub use self::bytes::LenBuffer;
+
+#[cfg(feature = "bytes-buf")]
+mod bytes {
+    use buf_min::Buffer;
+
+    /// `Buffer` which only counts the written bytes, it freezes to the length
+    ///
+    /// Renders writing through `buf_ptr`, like the integer ones, write to a scratch
+    /// of the greatest reserved size
+    #[derive(Debug, Default)]
+    pub struct LenBuffer {
+        len: usize,
+        scratch: Vec<u8>,
+    }
+
+    impl Buffer for LenBuffer {
+        type Freeze = usize;
+
+        #[inline]
+        fn with_capacity(_: usize) -> Self {
+            LenBuffer::default()
+        }
+
+        #[inline]
+        fn is_empty(&self) -> bool {
+            self.len == 0
+        }
+
+        #[inline]
+        fn len(&self) -> usize {
+            self.len
+        }
+
+        #[inline]
+        unsafe fn extend_from_slice(&mut self, src: &[u8]) {
+            self.len += src.len();
+        }
+
+        #[inline]
+        fn reserve(&mut self, additional: usize) {
+            // Scratch is always empty
+            self.scratch.reserve(additional);
+        }
+
+        #[inline]
+        fn freeze(self) -> usize {
+            self.len
+        }
+
+        #[inline]
+        unsafe fn advance(&mut self, cnt: usize) {
+            self.len += cnt;
+        }
+
+        #[inline]
+        unsafe fn buf_ptr(&mut self) -> *mut u8 {
+            self.scratch.as_mut_ptr()
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+        use crate::helpers::{RenderBytes, RenderBytesSafe};
+
+        #[test]
+        fn test_len_buffer() {
+            let mut buf = LenBuffer::with_capacity(0);
+            RenderBytes::render("<a>", &mut buf);
+            RenderBytes::render(u64::MAX, &mut buf);
+            RenderBytesSafe::render("<a>", &mut buf);
+            buf.extend("ab");
+            assert_eq!(buf.freeze(), "&lt;a&gt;".len() + 20 + 3 + 2);
+        }
+    }
+}
@@ -0,0 +1,150 @@
+//! Html trusted to be written without escape
+use std::{borrow::Cow, fmt};
+
+/// Html from trusted sources, written as is by `{{ }}` and `{{{ }}}`
+///
+/// Only built from static strings, escaped text, sanitized html or the output of trusted
+/// html types, like `strict_safe` html templates
+///
+/// ```
+/// use yarte_helpers::helpers::safe::SafeHtml;
+///
+/// let html = SafeHtml::from_static("<b>bold</b>");
+/// assert_eq!(html.as_str(), "<b>bold</b>");
+/// assert_eq!(SafeHtml::escape("<b>").as_str(), "&lt;b&gt;");
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SafeHtml(Cow<'static, str>);
+
+impl SafeHtml {
+    /// Html written in the program sources
+    pub const fn from_static(html: &'static str) -> SafeHtml {
+        SafeHtml(Cow::Borrowed(html))
+    }
+
+    /// Html escaped text
+    #[cfg(feature = "markup")]
+    pub fn escape(text: &str) -> SafeHtml {
+        SafeHtml(Cow::Owned(v_htmlescape::escape(text).to_string()))
+    }
+
+    /// Renders a trusted html type, like a `strict_safe` html template
+    pub fn render<T: TrustedHtml + fmt::Display + ?Sized>(html: &T) -> SafeHtml {
+        SafeHtml(Cow::Owned(html.to_string()))
+    }
+
+    /// Output of trusted producers of this crate, like the sanitizer
+    pub(crate) fn trusted(html: String) -> SafeHtml {
+        SafeHtml(Cow::Owned(html))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn into_string(self) -> String {
+        self.0.into_owned()
+    }
+}
+
+impl AsRef<str> for SafeHtml {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for SafeHtml {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Types written unescaped by `{{{ }}}` in `strict_safe` templates
+///
+/// `strict_safe` html `Template` derives implement it
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` is not trusted html",
+    label = "unescaped expression of a `strict_safe` template",
+    note = "use a `yarte::SafeHtml`, a `strict_safe` html template or a `#[template(safe)]` field"
+)]
+pub trait TrustedHtml {
+    #[doc(hidden)]
+    #[inline(always)]
+    fn __trusted_html(&self) -> &Self {
+        self
+    }
+}
+
+impl TrustedHtml for SafeHtml {}
+
+impl<T: TrustedHtml + ?Sized> TrustedHtml for &T {}
+
+macro_rules! trusted {
+    ($($ty:ty)*) => {
+        $(impl TrustedHtml for $ty {})*
+    };
+}
+
+#[rustfmt::skip]
+trusted! {
+    u8 u16 u32 u64 u128 usize
+    i8 i16 i32 i64 i128 isize
+    f32 f64 bool
+}
+
+#[cfg(feature = "markup")]
+impl super::Render for SafeHtml {
+    #[inline(always)]
+    fn render(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[cfg(feature = "bytes-buf")]
+mod bytes {
+    use buf_min::Buffer;
+
+    use super::SafeHtml;
+    use crate::helpers::{RenderBytes, RenderBytesSafe};
+
+    impl RenderBytes for &SafeHtml {
+        #[inline(always)]
+        fn render<B: Buffer>(self, buf: &mut B) {
+            buf.extend(self.as_str());
+        }
+    }
+
+    impl RenderBytesSafe for &SafeHtml {
+        #[inline(always)]
+        fn render<B: Buffer>(self, buf: &mut B) {
+            buf.extend(self.as_str());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct Bold;
+
+    impl TrustedHtml for Bold {}
+
+    impl fmt::Display for Bold {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("<b></b>")
+        }
+    }
+
+    #[test]
+    fn test_safe_html() {
+        assert_eq!(SafeHtml::render(&Bold).as_str(), "<b></b>");
+        assert_eq!(SafeHtml::render(&&Bold).into_string(), "<b></b>");
+        assert_eq!(SafeHtml::escape("a&<").to_string(), "a&amp;&lt;");
+        assert_eq!(
+            SafeHtml::from_static("<br>").__trusted_html().as_str(),
+            "<br>"
+        );
+    }
+}
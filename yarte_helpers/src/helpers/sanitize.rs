@@ -0,0 +1,673 @@
+//! Allowlist html sanitizer of `@sanitize` helper
+//!
+//! Writes the allowed tags with the allowed attributes, drops comments, the other tags and
+//! the content of raw text elements, like `<script>`, and escapes the remaining markup.
+//! Output tags are balanced
+use std::fmt;
+
+use super::safe::{SafeHtml, TrustedHtml};
+
+/// Raw text elements, its content is dropped with them
+const RAW_TEXT: &[&str] = &[
+    "script",
+    "style",
+    "textarea",
+    "title",
+    "xmp",
+    "iframe",
+    "noembed",
+    "noframes",
+    "noscript",
+    "plaintext",
+    "template",
+    "object",
+];
+
+/// Elements without end tag
+const VOID: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
+/// Attributes with urls, only written with an allowed scheme or relative
+const URL_ATTRIBUTES: &[&str] = &[
+    "href",
+    "src",
+    "cite",
+    "action",
+    "formaction",
+    "poster",
+    "background",
+    "longdesc",
+    "usemap",
+    "xlink:href",
+];
+
+/// Url schemes of scripts and inline documents, never allowed
+const SCRIPT_SCHEMES: &[&str] = &["javascript", "vbscript", "data"];
+
+/// Allowlist of `@sanitize`, in lowercase
+///
+/// Built with [`SanitizePolicy::new`], it can't allow raw text tags, event handler attributes
+/// nor script url schemes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SanitizePolicy {
+    /// Written tags
+    tags: &'static [&'static str],
+    /// Written attributes of the written tags
+    attributes: &'static [&'static str],
+    /// Schemes of absolute urls
+    url_schemes: &'static [&'static str],
+}
+
+/// Item of a policy that can't be allowed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolicyError<'a> {
+    /// Raw text element, like `script`
+    RawTextTag(&'a str),
+    /// Inline event handler, like `onclick`
+    EventHandler(&'a str),
+    /// Script url scheme, like `javascript`
+    ScriptScheme(&'a str),
+}
+
+impl<'a> fmt::Display for PolicyError<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PolicyError::RawTextTag(tag) => write!(f, "raw text tag `{tag}`"),
+            PolicyError::EventHandler(attr) => write!(f, "event handler attribute `{attr}`"),
+            PolicyError::ScriptScheme(scheme) => write!(f, "script url scheme `{scheme}`"),
+        }
+    }
+}
+
+impl Default for SanitizePolicy {
+    fn default() -> Self {
+        SanitizePolicy::DEFAULT
+    }
+}
+
+impl SanitizePolicy {
+    /// Policy without `[sanitize]` in the configuration file
+    pub const DEFAULT: SanitizePolicy = SanitizePolicy::new(
+        &[
+            "a",
+            "abbr",
+            "b",
+            "blockquote",
+            "br",
+            "code",
+            "em",
+            "h1",
+            "h2",
+            "h3",
+            "h4",
+            "h5",
+            "h6",
+            "hr",
+            "i",
+            "li",
+            "ol",
+            "p",
+            "pre",
+            "s",
+            "strong",
+            "sub",
+            "sup",
+            "u",
+            "ul",
+        ],
+        &["href", "title"],
+        &["http", "https", "mailto"],
+    );
+
+    /// Policy writing `tags`, their `attributes` and absolute urls with `url_schemes`
+    ///
+    /// # Panics
+    /// When [`SanitizePolicy::check`] fails, in a `const` it's a compile error
+    ///
+    /// ```compile_fail
+    /// use yarte_helpers::helpers::sanitize::SanitizePolicy;
+    ///
+    /// const POLICY: SanitizePolicy = SanitizePolicy::new(&["p"], &["onclick"], &[]);
+    /// ```
+    pub const fn new(
+        tags: &'static [&'static str],
+        attributes: &'static [&'static str],
+        url_schemes: &'static [&'static str],
+    ) -> SanitizePolicy {
+        match SanitizePolicy::check(tags, attributes, url_schemes) {
+            Ok(()) => SanitizePolicy {
+                tags,
+                attributes,
+                url_schemes,
+            },
+            Err(PolicyError::RawTextTag(_)) => panic!("sanitize policy allows a raw text tag"),
+            Err(PolicyError::EventHandler(_)) => {
+                panic!("sanitize policy allows an event handler attribute")
+            }
+            Err(PolicyError::ScriptScheme(_)) => panic!("sanitize policy allows a script scheme"),
+        }
+    }
+
+    /// First item of the allowlist that can't be sanitized
+    ///
+    /// Raw text elements, like `<script>`, `on*` event handler attributes and the `javascript`,
+    /// `vbscript` and `data` url schemes run scripts
+    pub const fn check<'a>(
+        tags: &[&'a str],
+        attributes: &[&'a str],
+        url_schemes: &[&'a str],
+    ) -> Result<(), PolicyError<'a>> {
+        let mut i = 0;
+        while i < tags.len() {
+            if contains(RAW_TEXT, tags[i]) {
+                return Err(PolicyError::RawTextTag(tags[i]));
+            }
+            i += 1;
+        }
+        let mut i = 0;
+        while i < attributes.len() {
+            let attr = attributes[i].as_bytes();
+            if attr.len() > 2 && attr.split_at(2).0.eq_ignore_ascii_case(b"on") {
+                return Err(PolicyError::EventHandler(attributes[i]));
+            }
+            i += 1;
+        }
+        let mut i = 0;
+        while i < url_schemes.len() {
+            if contains(SCRIPT_SCHEMES, url_schemes[i]) {
+                return Err(PolicyError::ScriptScheme(url_schemes[i]));
+            }
+            i += 1;
+        }
+        Ok(())
+    }
+
+    /// Written tags
+    pub const fn tags(&self) -> &'static [&'static str] {
+        self.tags
+    }
+
+    /// Written attributes of the written tags
+    pub const fn attributes(&self) -> &'static [&'static str] {
+        self.attributes
+    }
+
+    /// Schemes of absolute urls
+    pub const fn url_schemes(&self) -> &'static [&'static str] {
+        self.url_schemes
+    }
+
+    /// Sanitized `html`
+    ///
+    /// ```
+    /// use yarte_helpers::helpers::sanitize::SanitizePolicy;
+    ///
+    /// let html = SanitizePolicy::DEFAULT.sanitize(r#"<p onclick="x()">Hi <script>x()</script><b>you"#);
+    /// assert_eq!(html.as_str(), "<p>Hi <b>you</b></p>");
+    /// ```
+    pub fn sanitize(&self, html: &str) -> SafeHtml {
+        let mut out = String::with_capacity(html.len());
+        let _ = self.sanitize_fmt(html, &mut out);
+        SafeHtml::trusted(out)
+    }
+
+    /// Writes `html` sanitized
+    pub fn sanitize_fmt<W: fmt::Write + ?Sized>(&self, html: &str, w: &mut W) -> fmt::Result {
+        let bytes = html.as_bytes();
+        let mut open: Vec<&'static str> = vec![];
+        let mut text = 0;
+        let mut i = 0;
+        while i < bytes.len() {
+            let escaped = match bytes[i] {
+                b'<' => {
+                    w.write_str(&html[text..i])?;
+                    text = i;
+                    match self.markup(html, i, &mut open, w)? {
+                        Some(end) => {
+                            i = end;
+                            text = end;
+                            continue;
+                        }
+                        None => "&lt;",
+                    }
+                }
+                b'>' => "&gt;",
+                b'&' if entity(&html[i..]).is_none() => "&amp;",
+                _ => {
+                    i += 1;
+                    continue;
+                }
+            };
+            w.write_str(&html[text..i])?;
+            w.write_str(escaped)?;
+            i += 1;
+            text = i;
+        }
+        w.write_str(&html[text..])?;
+        for tag in open.iter().rev() {
+            write!(w, "</{tag}>")?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the markup at `<` in `at`, returns its end or `None` when it is text
+    fn markup<W: fmt::Write + ?Sized>(
+        &self,
+        html: &str,
+        at: usize,
+        open: &mut Vec<&'static str>,
+        w: &mut W,
+    ) -> Result<Option<usize>, fmt::Error> {
+        let rest = &html[at..];
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            return Ok(Some(
+                comment.find("-->").map_or(html.len(), |end| at + 7 + end),
+            ));
+        }
+        if rest.starts_with("<!") || rest.starts_with("<?") {
+            return Ok(Some(rest.find('>').map_or(html.len(), |end| at + end + 1)));
+        }
+        let closing = rest.starts_with("</");
+        let start = at + if closing { 2 } else { 1 };
+        if !html[start..].starts_with(|c: char| c.is_ascii_alphabetic()) {
+            return Ok(None);
+        }
+        // Unterminated tags drop the rest
+        let Some(tag) = Tag::parse(html, start) else {
+            return Ok(Some(html.len()));
+        };
+        let allowed = self
+            .tags
+            .iter()
+            .copied()
+            .find(|t| t.eq_ignore_ascii_case(tag.name));
+
+        if closing {
+            if let Some(name) = allowed {
+                if let Some(p) = open.iter().rposition(|t| *t == name) {
+                    for t in open.drain(p..).rev() {
+                        write!(w, "</{t}>")?;
+                    }
+                }
+            }
+            return Ok(Some(tag.end));
+        }
+
+        match allowed {
+            Some(name) => {
+                write!(w, "<{name}")?;
+                for (attr, value) in &tag.attrs {
+                    if !self.attributes.iter().any(|a| a.eq_ignore_ascii_case(attr)) {
+                        continue;
+                    }
+                    let attr = attr.to_ascii_lowercase();
+                    match value {
+                        Some(value) => {
+                            if URL_ATTRIBUTES.contains(&attr.as_str()) && !self.url_allowed(value) {
+                                continue;
+                            }
+                            write!(w, " {attr}=\"")?;
+                            attribute_value(value, w)?;
+                            w.write_char('"')?;
+                        }
+                        None => write!(w, " {attr}")?,
+                    }
+                }
+                w.write_char('>')?;
+                if !VOID.contains(&name) {
+                    open.push(name);
+                }
+                Ok(Some(tag.end))
+            }
+            None => match RAW_TEXT.iter().find(|t| t.eq_ignore_ascii_case(tag.name)) {
+                Some(raw) => Ok(Some(raw_text_end(html, tag.end, raw))),
+                None => Ok(Some(tag.end)),
+            },
+        }
+    }
+
+    /// Relative url or absolute url with an allowed scheme
+    fn url_allowed(&self, url: &str) -> bool {
+        // Browsers skip whitespace and control characters
+        let url: String = url
+            .chars()
+            .filter(|c| !c.is_ascii_whitespace() && !c.is_control())
+            .collect();
+        let end = url.find([':', '/', '?', '#']).unwrap_or(url.len());
+        // Character references can hide the scheme colon
+        if url[..end].contains('&') {
+            return false;
+        }
+        !url[end..].starts_with(':')
+            || self
+                .url_schemes
+                .iter()
+                .any(|s| s.eq_ignore_ascii_case(&url[..end]))
+    }
+}
+
+/// Start tag or end tag
+struct Tag<'a> {
+    name: &'a str,
+    attrs: Vec<(&'a str, Option<&'a str>)>,
+    /// After `>`
+    end: usize,
+}
+
+impl<'a> Tag<'a> {
+    /// Tag with name at `start`, `None` without `>`
+    fn parse(html: &'a str, start: usize) -> Option<Tag<'a>> {
+        let bytes = html.as_bytes();
+        let mut i = start;
+        while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'-') {
+            i += 1;
+        }
+        let name = &html[start..i];
+        let mut attrs = vec![];
+        loop {
+            while i < bytes.len() && (bytes[i].is_ascii_whitespace() || bytes[i] == b'/') {
+                i += 1;
+            }
+            match bytes.get(i)? {
+                b'>' => {
+                    return Some(Tag {
+                        name,
+                        attrs,
+                        end: i + 1,
+                    })
+                }
+                // Attribute names can start with `=`
+                b'=' => i += 1,
+                _ => (),
+            }
+            let attr = i;
+            while i < bytes.len() && !matches!(bytes[i], b'>' | b'/' | b'=') {
+                if bytes[i].is_ascii_whitespace() {
+                    break;
+                }
+                i += 1;
+            }
+            let attr = &html[attr..i];
+            while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+                i += 1;
+            }
+            if bytes.get(i) != Some(&b'=') {
+                attrs.push((attr, None));
+                continue;
+            }
+            i += 1;
+            while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+                i += 1;
+            }
+            let value = match bytes.get(i)? {
+                q @ (b'"' | b'\'') => {
+                    let end = i + 1 + html[i + 1..].find(*q as char)?;
+                    let value = &html[i + 1..end];
+                    i = end + 1;
+                    value
+                }
+                _ => {
+                    let value = i;
+                    while i < bytes.len() && !bytes[i].is_ascii_whitespace() && bytes[i] != b'>' {
+                        i += 1;
+                    }
+                    &html[value..i]
+                }
+            };
+            attrs.push((attr, Some(value)));
+        }
+    }
+}
+
+/// End of raw text element `name` with content from `start`
+fn raw_text_end(html: &str, start: usize, name: &str) -> usize {
+    let bytes = html.as_bytes();
+    let mut i = start;
+    while let Some(j) = html[i..].find("</") {
+        let at = i + j + 2;
+        if bytes.len() >= at + name.len() && html[at..at + name.len()].eq_ignore_ascii_case(name) {
+            return html[at..].find('>').map_or(html.len(), |end| at + end + 1);
+        }
+        i = at;
+    }
+    html.len()
+}
+
+/// `list` has `s`, ignoring ascii case
+const fn contains(list: &[&str], s: &str) -> bool {
+    let mut i = 0;
+    while i < list.len() {
+        if list[i].eq_ignore_ascii_case(s) {
+            return true;
+        }
+        i += 1;
+    }
+    false
+}
+
+/// Length of the character reference at the start of `s`
+fn entity(s: &str) -> Option<usize> {
+    let bytes = s.as_bytes();
+    let (digits, start): (fn(&u8) -> bool, usize) = match bytes.get(1)? {
+        b'#' if matches!(bytes.get(2)?, b'x' | b'X') => (u8::is_ascii_hexdigit, 3),
+        b'#' => (u8::is_ascii_digit, 2),
+        c if c.is_ascii_alphabetic() => (u8::is_ascii_alphanumeric, 1),
+        _ => return None,
+    };
+    let len = bytes[start..].iter().take_while(|&b| digits(b)).count();
+    (len > 0 && bytes.get(start + len) == Some(&b';')).then_some(start + len + 1)
+}
+
+/// Writes a double quoted attribute value
+fn attribute_value<W: fmt::Write + ?Sized>(value: &str, w: &mut W) -> fmt::Result {
+    let mut last = 0;
+    for (i, b) in value.bytes().enumerate() {
+        let escaped = match b {
+            b'"' => "&quot;",
+            b'<' => "&lt;",
+            b'>' => "&gt;",
+            b'&' if entity(&value[i..]).is_none() => "&amp;",
+            _ => continue,
+        };
+        w.write_str(&value[last..i])?;
+        w.write_str(escaped)?;
+        last = i + 1;
+    }
+    w.write_str(&value[last..])
+}
+
+/// Html sanitized at render time by `policy`, built by `@sanitize`
+pub struct Sanitize<'a, T: ?Sized>(&'a T, &'static SanitizePolicy);
+
+impl<'a, T: ?Sized> Clone for Sanitize<'a, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, T: ?Sized> Copy for Sanitize<'a, T> {}
+
+pub trait AsSanitize {
+    fn __sanitize(&self, policy: &'static SanitizePolicy) -> Sanitize<'_, Self>;
+}
+
+impl<S: AsRef<str> + ?Sized> AsSanitize for S {
+    #[inline]
+    fn __sanitize(&self, policy: &'static SanitizePolicy) -> Sanitize<'_, Self> {
+        Sanitize(self, policy)
+    }
+}
+
+impl<'a, T: AsRef<str> + ?Sized> fmt::Display for Sanitize<'a, T> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.1.sanitize_fmt(self.0.as_ref(), f)
+    }
+}
+
+impl<'a, T: AsRef<str> + ?Sized> TrustedHtml for Sanitize<'a, T> {}
+
+#[cfg(feature = "markup")]
+impl<'a, T: AsRef<str> + ?Sized> super::Render for Sanitize<'a, T> {
+    #[inline]
+    fn render(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.1.sanitize_fmt(self.0.as_ref(), f)
+    }
+}
+
+#[cfg(feature = "bytes-buf")]
+mod bytes {
+    use std::fmt;
+
+    use buf_min::Buffer;
+
+    use super::Sanitize;
+    use crate::helpers::{RenderBytes, RenderBytesSafe};
+
+    struct BufWriter<'a, B>(&'a mut B);
+
+    impl<'a, B: Buffer> fmt::Write for BufWriter<'a, B> {
+        #[inline]
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            self.0.extend(s);
+            Ok(())
+        }
+    }
+
+    impl<'a, T: AsRef<str> + ?Sized> RenderBytes for Sanitize<'a, T> {
+        #[inline]
+        fn render<B: Buffer>(self, buf: &mut B) {
+            let _ = self.1.sanitize_fmt(self.0.as_ref(), &mut BufWriter(buf));
+        }
+    }
+
+    impl<'a, T: AsRef<str> + ?Sized> RenderBytesSafe for Sanitize<'a, T> {
+        #[inline]
+        fn render<B: Buffer>(self, buf: &mut B) {
+            RenderBytes::render(self, buf)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sanitize(html: &str) -> String {
+        SanitizePolicy::DEFAULT.sanitize(html).into_string()
+    }
+
+    #[test]
+    fn test_tags() {
+        assert_eq!(sanitize("<p>a <b>b</b></p>"), "<p>a <b>b</b></p>");
+        assert_eq!(sanitize("<P>a</p>"), "<p>a</p>");
+        assert_eq!(sanitize("<div><p>a</div>"), "<p>a</p>");
+        assert_eq!(
+            sanitize("<ul><li>a<li>b</ul>"),
+            "<ul><li>a<li>b</li></li></ul>"
+        );
+        assert_eq!(sanitize("a<br/>b<hr>"), "a<br>b<hr>");
+        assert_eq!(sanitize("</b>a<b>"), "a<b></b>");
+        assert_eq!(
+            sanitize("<em>a<strong>b</em>c"),
+            "<em>a<strong>b</strong></em>c"
+        );
+    }
+
+    #[test]
+    fn test_dropped() {
+        assert_eq!(sanitize("a<script>alert('<b>')</script>b"), "ab");
+        assert_eq!(sanitize("a<STYLE>p {}</style >b"), "ab");
+        assert_eq!(sanitize("a<!-- <b> -->b<!doctype html>"), "ab");
+        assert_eq!(sanitize("a<script>x"), "a");
+        assert_eq!(sanitize("a<b title=\"x"), "a");
+        assert_eq!(sanitize("a<img src=x onerror=alert(1)>b"), "ab");
+    }
+
+    #[test]
+    fn test_attributes() {
+        assert_eq!(
+            sanitize(r#"<a href="https://a.b/?x=1&amp;y" onclick="x()" TITLE='t "q"'>a</a>"#),
+            r#"<a href="https://a.b/?x=1&amp;y" title="t &quot;q&quot;">a</a>"#
+        );
+        assert_eq!(
+            sanitize("<a href=/path title>a</a>"),
+            "<a href=\"/path\" title>a</a>"
+        );
+        for url in [
+            "javascript:alert(1)",
+            " JavaScript:alert(1)",
+            "java\tscript:alert(1)",
+            "javascript&#58;alert(1)",
+            "data:text/html,x",
+        ] {
+            assert_eq!(
+                sanitize(&format!("<a href='{url}'>a</a>")),
+                "<a>a</a>",
+                "{url}"
+            );
+        }
+        assert_eq!(
+            sanitize("<a href='mailto:a@b.c'>a</a>"),
+            "<a href=\"mailto:a@b.c\">a</a>"
+        );
+    }
+
+    #[test]
+    fn test_text() {
+        assert_eq!(
+            sanitize("1 < 2 > 0 & a &amp; &#60; &#x3c;"),
+            "1 &lt; 2 &gt; 0 &amp; a &amp; &#60; &#x3c;"
+        );
+        assert_eq!(sanitize("<3 <>"), "&lt;3 &lt;&gt;");
+        assert_eq!(sanitize("ñ<b>ü</b>"), "ñ<b>ü</b>");
+    }
+
+    #[test]
+    fn test_policy() {
+        const POLICY: SanitizePolicy = SanitizePolicy::new(&["img"], &["src", "alt"], &["https"]);
+        let mut out = String::new();
+        POLICY
+            .sanitize_fmt("<p><img alt=a src=https://a.b/c.png></p>", &mut out)
+            .unwrap();
+        assert_eq!(out, "<img alt=\"a\" src=\"https://a.b/c.png\">");
+        assert_eq!(
+            "<img src='http://a'>".__sanitize(&POLICY).to_string(),
+            "<img>"
+        );
+    }
+
+    #[test]
+    fn test_check() {
+        assert_eq!(
+            SanitizePolicy::check(&["p", "Script"], &[], &[]),
+            Err(PolicyError::RawTextTag("Script"))
+        );
+        assert_eq!(
+            SanitizePolicy::check(&["p"], &["title", "ONclick"], &[]),
+            Err(PolicyError::EventHandler("ONclick"))
+        );
+        assert_eq!(
+            SanitizePolicy::check(&["p"], &["on"], &["https", "data"]),
+            Err(PolicyError::ScriptScheme("data"))
+        );
+        assert_eq!(
+            SanitizePolicy::check(
+                SanitizePolicy::DEFAULT.tags(),
+                SanitizePolicy::DEFAULT.attributes(),
+                SanitizePolicy::DEFAULT.url_schemes()
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "sanitize policy allows a script scheme")]
+    fn test_new_panics() {
+        let schemes = vec!["javascript"].leak();
+        SanitizePolicy::new(&[], &[], schemes);
+    }
+}
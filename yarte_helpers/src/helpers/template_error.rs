@@ -0,0 +1,122 @@
+//! Errors of `?` expressions in templates with `error` attribute
+use std::{
+    cell::Cell,
+    error::Error,
+    fmt::{self, Display},
+};
+
+/// Template location of a `?` expression
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Location {
+    /// Template path relative to its template directory
+    pub file: &'static str,
+    pub line: usize,
+    pub column: usize,
+    /// Source of the expression
+    pub expr: &'static str,
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+/// Render error of `TryTemplate` and `TryTemplateBytes`
+///
+/// ```
+/// use yarte_helpers::helpers::template_error::{Location, TemplateError};
+///
+/// const AT: Location = Location {
+///     file: "page.hbs",
+///     line: 1,
+///     column: 4,
+///     expr: "id.parse::<u8>()?",
+/// };
+/// let err: TemplateError<_> = TemplateError::Expr("a".parse::<u8>().unwrap_err(), &AT);
+/// assert_eq!(
+///     err.to_string(),
+///     "invalid digit found in string, at `id.parse::<u8>()?` in page.hbs:1:4"
+/// );
+/// ```
+#[derive(Debug)]
+pub enum TemplateError<E> {
+    /// Error of a `?` expression converted with `From`
+    Expr(E, &'static Location),
+    /// Error of a `fmt::Display` implementation
+    Fmt(fmt::Error),
+}
+
+impl<E> TemplateError<E> {
+    /// Location of the failed `?` expression
+    pub fn location(&self) -> Option<&'static Location> {
+        match self {
+            TemplateError::Expr(_, at) => Some(at),
+            TemplateError::Fmt(_) => None,
+        }
+    }
+
+    /// Error of the failed `?` expression
+    pub fn into_inner(self) -> Option<E> {
+        match self {
+            TemplateError::Expr(e, _) => Some(e),
+            TemplateError::Fmt(_) => None,
+        }
+    }
+}
+
+impl<E> From<fmt::Error> for TemplateError<E> {
+    fn from(e: fmt::Error) -> Self {
+        TemplateError::Fmt(e)
+    }
+}
+
+impl<E: Display> Display for TemplateError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TemplateError::Expr(e, at) => write!(f, "{e}, at `{}` in {at}", at.expr),
+            TemplateError::Fmt(e) => e.fmt(f),
+        }
+    }
+}
+
+impl<E: Error + 'static> Error for TemplateError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TemplateError::Expr(e, _) => Some(e),
+            TemplateError::Fmt(e) => Some(e),
+        }
+    }
+}
+
+/// Failed `?` expression of a `fmt::Display` render, which only returns `fmt::Error`
+#[doc(hidden)]
+pub struct TrySlot<E>(Cell<Option<TemplateError<E>>>);
+
+impl<E> TrySlot<E> {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        TrySlot(Cell::new(None))
+    }
+
+    pub fn fail(&self, error: E, at: &'static Location) -> fmt::Error {
+        self.0.set(Some(TemplateError::Expr(error, at)));
+        fmt::Error
+    }
+
+    /// Error of the render
+    pub fn take(&self, e: fmt::Error) -> TemplateError<E> {
+        self.0.take().unwrap_or(TemplateError::Fmt(e))
+    }
+}
+
+/// Failed `?` expression of a bytes render
+#[doc(hidden)]
+pub struct TryBuf;
+
+impl TryBuf {
+    #[inline]
+    pub fn fail<E>(&self, error: E, at: &'static Location) -> TemplateError<E> {
+        TemplateError::Expr(error, at)
+    }
+}
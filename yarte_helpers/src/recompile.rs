@@ -13,7 +13,13 @@ pub fn when_changed() {
     let file = read_config_file();
     let config = Config::new(&file);
 
-    let mut stack = vec![config.get_dir().clone()];
+    // Override directories may not exist
+    let mut stack: Vec<_> = config
+        .get_dirs()
+        .iter()
+        .filter(|dir| dir.is_dir())
+        .cloned()
+        .collect();
     while let Some(dir) = stack.pop() {
         // rerun when dir change
         println!("cargo:rerun-if-changed={}", dir.to_str().unwrap());
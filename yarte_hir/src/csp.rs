@@ -0,0 +1,361 @@
+//! Scanner of template literal html for `csp_nonce` templates
+//!
+//! Tracks the html state across the literals of a template, expressions between them
+//! are attribute values, parts of attribute names or text. It follows the tokenizer states
+//! of the html spec that decide where tags are: comments, raw text elements and foreign
+//! content, like `<svg>`, where `<script>` and `<style>` have markup content
+
+/// Elements with a nonce attribute
+const NONCED: [&str; 2] = ["script", "style"];
+
+/// Elements of html content whose content is text until their end tag
+const RAW_TEXT: [&str; 10] = [
+    "script",
+    "style",
+    "textarea",
+    "title",
+    "xmp",
+    "iframe",
+    "noembed",
+    "noframes",
+    "noscript",
+    "plaintext",
+];
+
+/// Roots of foreign content
+const FOREIGN: [&str; 2] = ["svg", "math"];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Attr {
+    Before,
+    /// Name, with the head of its parts in previous literals
+    Name(Head),
+    AfterName,
+    BeforeValue,
+    Quoted(u8),
+    Unquoted,
+}
+
+/// First two bytes of an attribute name, in lowercase
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+struct Head {
+    bytes: [u8; 2],
+    len: usize,
+}
+
+impl Head {
+    fn push(mut self, part: &[u8]) -> Head {
+        for b in part.iter().take(2 - self.len) {
+            self.bytes[self.len] = b.to_ascii_lowercase();
+            self.len += 1;
+        }
+        self
+    }
+
+    fn is_handler(self) -> bool {
+        self.bytes == *b"on" && self.len == 2
+    }
+}
+
+/// Element opened by a start tag
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Element {
+    RawText(&'static str),
+    Foreign,
+    Other,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum State {
+    Data,
+    Comment,
+    /// Inside a tag, with the element it opens
+    Tag(Attr, Element),
+    /// Content of a raw text element
+    RawText(&'static str),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Mark {
+    /// End of a `script` or `style` start tag name
+    Nonce(usize),
+    /// Range of an inline event handler attribute name
+    Handler(usize, usize),
+}
+
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct Csp {
+    state: State,
+    /// Depth of foreign content
+    foreign: usize,
+}
+
+impl Default for Csp {
+    fn default() -> Self {
+        Csp {
+            state: State::Data,
+            foreign: 0,
+        }
+    }
+}
+
+impl Csp {
+    /// Marks of a literal, offsets in bytes
+    pub(crate) fn scan(&mut self, lit: &str) -> Vec<Mark> {
+        let bytes = lit.as_bytes();
+        let mut marks = vec![];
+        // Attribute names are split by expressions, like `<a on{{ x }}>`
+        let mut name = 0;
+        let mut i = 0;
+        while i < bytes.len() {
+            let c = bytes[i];
+            match self.state {
+                State::Data if c == b'<' => {
+                    if bytes[i + 1..].starts_with(b"!--") {
+                        self.state = State::Comment;
+                        i += 4;
+                        continue;
+                    }
+                    let close = bytes.get(i + 1) == Some(&b'/');
+                    let start = i + 1 + close as usize;
+                    if bytes.get(start).is_some_and(u8::is_ascii_alphabetic) {
+                        let end = start
+                            + bytes[start..]
+                                .iter()
+                                .take_while(|b| b.is_ascii_alphanumeric() || **b == b'-')
+                                .count();
+                        let element = self.element(&bytes[start..end], close);
+                        if !close && find(&NONCED, &bytes[start..end]).is_some() {
+                            marks.push(Mark::Nonce(end));
+                        }
+                        self.state = State::Tag(Attr::Before, element);
+                        i = end;
+                        continue;
+                    }
+                }
+                State::Data => (),
+                State::Comment => {
+                    if bytes[i..].starts_with(b"-->") {
+                        self.state = State::Data;
+                        i += 3;
+                        continue;
+                    }
+                }
+                State::RawText(tag) => {
+                    if closes(&bytes[i..], tag) {
+                        self.state = State::Data;
+                        continue;
+                    }
+                }
+                State::Tag(attr, element) => {
+                    let attr = match attr {
+                        Attr::Quoted(q) if c == q => Attr::Before,
+                        Attr::Quoted(_) => attr,
+                        _ if c == b'>' => {
+                            if let Attr::Name(head) = attr {
+                                handler(lit, (name, i), head, false, &mut marks);
+                            }
+                            let self_closing = matches!(attr, Attr::Before | Attr::AfterName)
+                                && i > 0
+                                && bytes[i - 1] == b'/';
+                            self.state = match element {
+                                Element::RawText(tag) => State::RawText(tag),
+                                Element::Foreign if !self_closing => {
+                                    self.foreign += 1;
+                                    State::Data
+                                }
+                                _ => State::Data,
+                            };
+                            i += 1;
+                            continue;
+                        }
+                        Attr::Name(head) if c == b'=' => {
+                            handler(lit, (name, i), head, false, &mut marks);
+                            Attr::BeforeValue
+                        }
+                        Attr::Name(head) if c.is_ascii_whitespace() || c == b'/' => {
+                            handler(lit, (name, i), head, false, &mut marks);
+                            Attr::AfterName
+                        }
+                        Attr::Name(_) => attr,
+                        Attr::AfterName if c == b'=' => Attr::BeforeValue,
+                        Attr::Before | Attr::AfterName if c.is_ascii_whitespace() || c == b'/' => {
+                            attr
+                        }
+                        Attr::Before | Attr::AfterName => {
+                            name = i;
+                            Attr::Name(Head::default())
+                        }
+                        Attr::BeforeValue if c.is_ascii_whitespace() => attr,
+                        Attr::BeforeValue if c == b'"' || c == b'\'' => Attr::Quoted(c),
+                        Attr::BeforeValue => Attr::Unquoted,
+                        Attr::Unquoted if c.is_ascii_whitespace() => Attr::Before,
+                        Attr::Unquoted => attr,
+                    };
+                    self.state = State::Tag(attr, element);
+                }
+            }
+            i += 1;
+        }
+
+        // The name goes on after the expression
+        if let State::Tag(Attr::Name(head), element) = self.state {
+            handler(lit, (name, bytes.len()), head, true, &mut marks);
+            self.state = State::Tag(Attr::Name(head.push(&bytes[name..])), element);
+        }
+
+        marks
+    }
+
+    /// Element of a tag name, end tags of foreign content roots close it
+    fn element(&mut self, name: &[u8], close: bool) -> Element {
+        let foreign = find(&FOREIGN, name).is_some();
+        if close {
+            if foreign {
+                self.foreign = self.foreign.saturating_sub(1);
+            }
+            return Element::Other;
+        }
+        match find(&RAW_TEXT, name) {
+            Some(tag) if self.foreign == 0 => Element::RawText(tag),
+            _ if foreign => Element::Foreign,
+            _ => Element::Other,
+        }
+    }
+}
+
+/// Tag of `list` named `name`, ignoring ascii case
+fn find(list: &[&'static str], name: &[u8]) -> Option<&'static str> {
+    list.iter()
+        .find(|tag| tag.as_bytes().eq_ignore_ascii_case(name))
+        .copied()
+}
+
+/// Starts with the end tag of `tag`
+fn closes(bytes: &[u8], tag: &str) -> bool {
+    bytes.starts_with(b"</")
+        && bytes.len() >= tag.len() + 2
+        && bytes[2..tag.len() + 2].eq_ignore_ascii_case(tag.as_bytes())
+        && !bytes
+            .get(tag.len() + 2)
+            .is_some_and(u8::is_ascii_alphanumeric)
+}
+
+/// Marks `on*` attribute names, the part of `lit` in `lo..hi` follows `head` of previous
+/// literals and it's `split` by an expression
+fn handler(lit: &str, (lo, hi): (usize, usize), head: Head, split: bool, marks: &mut Vec<Mark>) {
+    // Marked in a previous literal
+    if head.is_handler() {
+        return;
+    }
+    let part = &lit.as_bytes()[lo..hi];
+    if head.push(part).is_handler() && (split || head.len > 0 || part.len() > 2) {
+        marks.push(Mark::Handler(lo, hi));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn scan(lits: &[&str]) -> Vec<Vec<Mark>> {
+        let mut csp = Csp::default();
+        lits.iter().map(|lit| csp.scan(lit)).collect()
+    }
+
+    #[test]
+    fn test_nonce() {
+        assert_eq!(
+            scan(&["<script>let a = '<style>';</script><STYLE media=\"a\">"]),
+            vec![vec![Mark::Nonce(7), Mark::Nonce(41)]]
+        );
+        assert_eq!(
+            scan(&["<!-- <script> --><scripts></script>", "<style"]),
+            vec![vec![], vec![Mark::Nonce(6)]]
+        );
+        assert_eq!(
+            scan(&["<script src=\"", "\"><b>", "</script><style/>"]),
+            vec![vec![Mark::Nonce(7)], vec![], vec![Mark::Nonce(15)]]
+        );
+    }
+
+    #[test]
+    fn test_handler() {
+        assert_eq!(
+            scan(&["<a href=x onclick=\"f()\" title='onload=1' on>"]),
+            vec![vec![Mark::Handler(10, 17)]]
+        );
+        assert_eq!(
+            scan(&["<img src=\"", "\" ONERROR", "=\"f()\">onload=1"]),
+            vec![vec![], vec![Mark::Handler(2, 9)], vec![]]
+        );
+        assert_eq!(
+            scan(&["<script>document.body.onload = f;</script>"]),
+            vec![vec![Mark::Nonce(7)]]
+        );
+        assert_eq!(
+            scan(&["<textarea><a onclick=f()></textarea><title><script></title>"]),
+            vec![vec![]]
+        );
+    }
+
+    #[test]
+    fn test_split_handler() {
+        assert_eq!(
+            scan(&["<a on", "=\"f()\" title=\"", "\">"]),
+            vec![vec![Mark::Handler(3, 5)], vec![], vec![]]
+        );
+        assert_eq!(
+            scan(&["<a O", "nclick", "=\"f()\" o", "n", " on", "Load>"]),
+            vec![
+                vec![],
+                vec![Mark::Handler(0, 6)],
+                vec![],
+                vec![Mark::Handler(0, 1)],
+                vec![Mark::Handler(1, 3)],
+                vec![]
+            ]
+        );
+        assert_eq!(scan(&["<a o", "=1 x", "n>"]), vec![vec![], vec![], vec![]]);
+    }
+
+    #[test]
+    fn test_case_and_entities() {
+        assert_eq!(
+            scan(&["<A OnClick=f() HREF=x><SVG ONLOAD=f()>"]),
+            vec![vec![Mark::Handler(3, 10), Mark::Handler(27, 33)]]
+        );
+        // Character references aren't decoded in attribute names
+        assert_eq!(
+            scan(&["<a &#111;nclick=f() title=\"&#111;nload\" href=\"javascript&#58;f()\">"]),
+            vec![vec![]]
+        );
+    }
+
+    #[test]
+    fn test_foreign() {
+        // `<script>` and `<style>` of foreign content have markup content
+        assert_eq!(
+            scan(&["<svg><script>if (a<b onclick=x) {}</script></svg><script><b onclick=x>"]),
+            vec![vec![
+                Mark::Nonce(12),
+                Mark::Handler(21, 28),
+                Mark::Nonce(56)
+            ]]
+        );
+        assert_eq!(
+            scan(&[
+                "<svg><style/><math><svg/></math><b onload=x></svg><style>",
+                "<b onclick=x>"
+            ]),
+            vec![
+                vec![Mark::Nonce(11), Mark::Handler(35, 41), Mark::Nonce(56)],
+                vec![]
+            ]
+        );
+        assert_eq!(
+            scan(&["<svg", "><script>", "</svg><style>"]),
+            vec![vec![], vec![Mark::Nonce(8)], vec![Mark::Nonce(12)]]
+        );
+    }
+}
@@ -0,0 +1,209 @@
+//! Serde support for [`HIR`](crate::HIR)
+//!
+//! `syn` nodes have no serde representation, so they are written as their token string
+//! and parsed back on deserialization. Spans are not preserved.
+use std::borrow::Borrow;
+
+use quote::ToTokens;
+use serde::{de, ser::SerializeTuple, Deserialize, Deserializer, Serialize, Serializer};
+use syn::parse::Parse;
+
+use yarte_parser::StmtLocal;
+
+use crate::HIR;
+
+struct SerExpr<'a>(&'a syn::Expr);
+
+impl<'a> Serialize for SerExpr<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0.to_token_stream().to_string())
+    }
+}
+
+struct DeExpr(syn::Expr);
+
+impl<'de> Deserialize<'de> for DeExpr {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let src = String::deserialize(deserializer)?;
+        syn::parse_str(&src)
+            .map(DeExpr)
+            .map_err(|e| de::Error::custom(format!("invalid expression `{src}`: {e}")))
+    }
+}
+
+pub(crate) mod expr {
+    use super::*;
+
+    pub fn serialize<E, S>(expr: &E, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        E: Borrow<syn::Expr>,
+        S: Serializer,
+    {
+        SerExpr(expr.borrow()).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, E, D>(deserializer: D) -> Result<E, D::Error>
+    where
+        E: From<syn::Expr>,
+        D: Deserializer<'de>,
+    {
+        DeExpr::deserialize(deserializer).map(|DeExpr(e)| e.into())
+    }
+}
+
+pub(crate) mod local {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(local: &syn::Local, serializer: S) -> Result<S::Ok, S::Error> {
+        let local: StmtLocal = local.clone().into();
+        serializer.serialize_str(&local.to_token_stream().to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Box<syn::Local>, D::Error> {
+        let src = String::deserialize(deserializer)?;
+        syn::parse_str::<StmtLocal>(&src)
+            .map(|x| Box::new(x.into()))
+            .map_err(|e| de::Error::custom(format!("invalid local `{src}`: {e}")))
+    }
+}
+
+/// Any other `syn` node
+pub(crate) mod tokens {
+    use super::*;
+
+    pub fn serialize<T: ToTokens, S: Serializer>(t: &T, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&t.to_token_stream().to_string())
+    }
+
+    pub fn deserialize<'de, T: Parse, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<T, D::Error> {
+        let src = String::deserialize(deserializer)?;
+        syn::parse_str(&src).map_err(|e| de::Error::custom(format!("invalid `{src}`: {e}")))
+    }
+}
+
+/// `(condition, body)` pair of `IfElse`
+pub(crate) mod branch {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        (expr, body): &(syn::Expr, Vec<HIR>),
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut tuple = serializer.serialize_tuple(2)?;
+        tuple.serialize_element(&SerExpr(expr))?;
+        tuple.serialize_element(body)?;
+        tuple.end()
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<(syn::Expr, Vec<HIR>), D::Error> {
+        <(DeExpr, Vec<HIR>)>::deserialize(deserializer).map(|(DeExpr(e), body)| (e, body))
+    }
+}
+
+pub(crate) mod branches {
+    use serde::ser::SerializeSeq;
+
+    use super::*;
+
+    struct SerBranch<'a>(&'a (syn::Expr, Vec<HIR>));
+
+    impl<'a> Serialize for SerBranch<'a> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            branch::serialize(self.0, serializer)
+        }
+    }
+
+    pub fn serialize<S: Serializer>(
+        branches: &[(syn::Expr, Vec<HIR>)],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(branches.len()))?;
+        for b in branches {
+            seq.serialize_element(&SerBranch(b))?;
+        }
+        seq.end()
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<(syn::Expr, Vec<HIR>)>, D::Error> {
+        <Vec<(DeExpr, Vec<HIR>)>>::deserialize(deserializer)
+            .map(|v| v.into_iter().map(|(DeExpr(e), body)| (e, body)).collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use syn::parse_str;
+
+    use crate::{Each, IfElse, PartialFn, HIR};
+
+    fn round_trip(ir: Vec<HIR>) {
+        let json = serde_json::to_string(&ir).unwrap();
+        let de: Vec<HIR> = serde_json::from_str(&json).unwrap();
+        assert_eq!(ir, de);
+    }
+
+    #[test]
+    fn lit_expr() {
+        round_trip(vec![
+            HIR::Lit("Hello, ".into()),
+            HIR::Expr(Box::new(parse_str("self.name").unwrap())),
+            HIR::Safe(Box::new(parse_str("self.html()").unwrap())),
+        ]);
+    }
+
+    #[test]
+    fn local() {
+        let local: yarte_parser::StmtLocal = parse_str("let a: usize = 1 + 2").unwrap();
+        round_trip(vec![HIR::Local(Box::new(local.into()))]);
+    }
+
+    #[test]
+    fn helpers() {
+        round_trip(vec![
+            HIR::Each(Box::new(Each {
+                args: parse_str("((&(self.items)).__into_citer().enumerate())").unwrap(),
+                body: vec![HIR::Expr(Box::new(parse_str("__key__0x00000000").unwrap()))],
+                expr: parse_str("(__index__0x00000001, __key__0x00000000)").unwrap(),
+            })),
+            HIR::IfElse(Box::new(IfElse {
+                ifs: (
+                    parse_str("let Some(a) = self.a").unwrap(),
+                    vec![HIR::Lit("a".into())],
+                ),
+                if_else: vec![(parse_str("self.b").unwrap(), vec![HIR::Lit("b".into())])],
+                els: Some(vec![HIR::Lit("c".into())]),
+            })),
+        ]);
+    }
+
+    #[test]
+    fn partial() {
+        round_trip(vec![
+            HIR::Partial(Box::new(PartialFn {
+                sig: parse_str("fn __partial_tree(node: &Node)").unwrap(),
+                body: vec![
+                    HIR::Expr(Box::new(parse_str("node.name").unwrap())),
+                    HIR::Call(Box::new(
+                        parse_str("self.__partial_tree(node.child)").unwrap(),
+                    )),
+                ],
+            })),
+            HIR::Call(Box::new(
+                parse_str("self.__partial_tree(&self.root)").unwrap(),
+            )),
+        ]);
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(serde_json::from_str::<HIR>(r#"{"Expr":"self ."}"#).is_err());
+    }
+}
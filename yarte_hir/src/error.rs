@@ -37,8 +37,10 @@ impl From<MiddleError> for ErrorMessage<GError> {
 // TODO: #39 improve error messages
 #[derive(Display, Clone)]
 pub enum GError {
-    #[display(fmt = "Recursion limit")]
-    RecursionLimit,
+    #[display(
+        fmt = "partial '{_0}' is recursive past the recursion limit, call it with named arguments to render it by a recursive function"
+    )]
+    RecursionLimit(String),
     #[display(fmt = "Not available Rust expression in a template expression")]
     ValidatorExpression,
     #[display(fmt = "Not available Rust expression in a template `if helper` arguments")]
@@ -75,4 +77,30 @@ pub enum GError {
     Internal,
     #[display(fmt = "use super without any parent")]
     SuperWithoutParent,
+    #[display(
+        fmt = "inline event handler '{_0}' is blocked by the Content Security Policy, use a script"
+    )]
+    CspEventHandler(String),
+    #[display(fmt = "`unsafe` is not available in strict templates")]
+    StrictUnsafe,
+    #[display(fmt = "loops are not available in strict templates, use `each` helper")]
+    StrictLoop,
+    #[display(fmt = "`&mut` is not available in strict templates")]
+    StrictMutRef,
+    #[display(fmt = "macro `{_0}!` is not in the allowlist of strict templates")]
+    StrictMacro(String),
+    #[display(fmt = "method `{_0}` is not in the allowlist of strict templates")]
+    StrictMethod(String),
+    #[display(fmt = "function `{_0}` is not in the allowlist of strict templates")]
+    StrictFunction(String),
+    #[display(fmt = "out of line partial '{_0}' is called with the arguments ({_1})")]
+    OutlineArguments(String, String),
+    #[display(
+        fmt = "type of partial argument '{_0}' can't be inferred, declare it in the head comment of the partial"
+    )]
+    OutlineType(String),
+    #[display(fmt = "invalid arguments declaration of partial: {_0}")]
+    OutlineDeclaration(String),
+    #[display(fmt = "`[sanitize]` in yarte.toml can't allow the {_0}")]
+    SanitizePolicy(String),
 }
@@ -1,27 +1,54 @@
+#[cfg(feature = "deser")]
+use serde::{Deserialize, Serialize};
+
 /// High level intermediate representation after lowering Ast
-// TODO: serialize and deserialize for container builder
+///
+/// With `deser` feature it's serializable, `syn` nodes are written as token strings
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "deser", derive(Serialize, Deserialize))]
 pub enum HIR {
     Lit(String),
-    Expr(Box<syn::Expr>),
-    Safe(Box<syn::Expr>),
+    Expr(#[cfg_attr(feature = "deser", serde(with = "crate::deser::expr"))] Box<syn::Expr>),
+    Safe(#[cfg_attr(feature = "deser", serde(with = "crate::deser::expr"))] Box<syn::Expr>),
     Each(Box<Each>),
     IfElse(Box<IfElse>),
-    Local(Box<syn::Local>),
+    Local(#[cfg_attr(feature = "deser", serde(with = "crate::deser::local"))] Box<syn::Local>),
+    /// Call of an out of line partial, the writer is the first argument
+    Call(
+        #[cfg_attr(feature = "deser", serde(with = "crate::deser::tokens"))]
+        Box<syn::ExprMethodCall>,
+    ),
+    /// Out of line partial, only at top level
+    Partial(Box<PartialFn>),
 }
 
 // TODO: to switch
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "deser", derive(Serialize, Deserialize))]
 pub struct IfElse {
+    #[cfg_attr(feature = "deser", serde(with = "crate::deser::branch"))]
     pub ifs: (syn::Expr, Vec<HIR>),
+    #[cfg_attr(feature = "deser", serde(with = "crate::deser::branches"))]
     pub if_else: Vec<(syn::Expr, Vec<HIR>)>,
     pub els: Option<Vec<HIR>>,
 }
 
 /// for expr in args { body }
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "deser", derive(Serialize, Deserialize))]
 pub struct Each {
+    #[cfg_attr(feature = "deser", serde(with = "crate::deser::expr"))]
     pub args: syn::Expr,
     pub body: Vec<HIR>,
+    #[cfg_attr(feature = "deser", serde(with = "crate::deser::expr"))]
     pub expr: syn::Expr,
 }
+
+/// fn sig { body }, a template method without receiver nor writer
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "deser", derive(Serialize, Deserialize))]
+pub struct PartialFn {
+    #[cfg_attr(feature = "deser", serde(with = "crate::deser::tokens"))]
+    pub sig: syn::Signature,
+    pub body: Vec<HIR>,
+}
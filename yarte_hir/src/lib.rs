@@ -1,25 +1,41 @@
 #![allow(unknown_lints, clippy::type_complexity, clippy::match_on_vec_items)]
-use std::{collections::BTreeMap, mem, path::PathBuf, str};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet},
+    mem,
+    path::{Path, PathBuf},
+    rc::Rc,
+    str,
+};
 
-use quote::{format_ident, quote};
+use quote::{format_ident, quote, ToTokens};
 use syn::{
-    parse2, parse_str, punctuated::Punctuated, spanned::Spanned, visit_mut::VisitMut, ExprArray,
-    ExprBinary, ExprBlock, ExprCall, ExprCast, ExprClosure, ExprField, ExprGroup, ExprIf,
-    ExprIndex, ExprLoop, ExprMacro, ExprMatch, ExprMethodCall, ExprParen, ExprPath, ExprRange,
-    ExprReference, ExprRepeat, ExprTuple, ExprUnary, ExprUnsafe, PathSegment, Token,
+    parse2, parse_str, punctuated::Punctuated, spanned::Spanned, visit::Visit, visit_mut::VisitMut,
+    ExprArray, ExprBinary, ExprBlock, ExprCall, ExprCast, ExprClosure, ExprField, ExprGroup,
+    ExprIf, ExprIndex, ExprLoop, ExprMacro, ExprMatch, ExprMethodCall, ExprParen, ExprPath,
+    ExprRange, ExprReference, ExprRepeat, ExprTuple, ExprUnary, ExprUnsafe, PathSegment, Token,
 };
 
 use v_eval::{eval, Value};
 use v_htmlescape::escape;
 
-use yarte_helpers::config::Config;
+use yarte_helpers::{
+    config::Config,
+    helpers::{
+        escape::{CsvEscaper, Escaper, JsonEscaper, LatexEscaper, ShellEscaper, XmlEscaper},
+        sanitize::SanitizePolicy,
+    },
+};
 use yarte_parser::{
-    source_map::Span, AtHelperKind, ErrorMessage, Helper, Node, Partial, PartialBlock, SExpr,
-    SNode, SVExpr, Ws,
+    source_map::{LineColumn, SourceMap, Span},
+    AtHelperKind, ErrorMessage, Helper, Node, Partial, PartialBlock, SExpr, SNode, SVExpr, Ws,
 };
 
 #[macro_use]
 mod macros;
+mod csp;
+#[cfg(feature = "deser")]
+mod deser;
 mod error;
 mod hir;
 mod scope;
@@ -28,18 +44,21 @@ mod validator;
 mod visit_derive;
 mod visit_each;
 mod visit_partial;
+mod visit_try;
 mod visits;
 
 use self::{
+    csp::{Csp, Mark},
     error::{GError, GResult, MiddleError},
     scope::Scope,
     visit_each::find_loop_var,
     visit_partial::visit_partial,
+    visit_try::{TryCollect, TryRewrite},
 };
 pub use self::{
     hir::*,
     serialize::{serialize, serialize_resolved},
-    visit_derive::{visit_derive, Print, Struct},
+    visit_derive::{front_matter, visit_derive, Format, Options, Print, Struct},
 };
 
 #[derive(Copy, Clone, Debug)]
@@ -59,13 +78,18 @@ impl Default for HIROptions {
     }
 }
 
+/// Lowers the template nodes of `ctx`, `sm` resolves their spans
+///
+/// Partials of `cyclic`, in a cycle of the partial graph, are rendered by recursive functions
 pub fn generate(
     c: &Config,
     s: &Struct,
     ctx: Context,
+    cyclic: &BTreeSet<PathBuf>,
+    sm: &SourceMap,
     opt: HIROptions,
 ) -> Result<Vec<HIR>, Vec<ErrorMessage<GError>>> {
-    LoweringContext::new(c, s, ctx, opt).build()
+    LoweringContext::new(c, s, ctx, cyclic, sm, opt).build()
 }
 
 pub type Context<'a> = &'a BTreeMap<&'a PathBuf, Vec<SNode<'a>>>;
@@ -83,6 +107,23 @@ enum Writable<'a> {
     Expr(Box<syn::Expr>, bool),
 }
 
+/// Out of line partials of a template
+#[derive(Default)]
+struct Outlines {
+    /// path - function of the partial, added before lowering its body
+    sigs: BTreeMap<PathBuf, Outline>,
+    /// Lowered functions
+    fns: Vec<HIR>,
+}
+
+/// Function of an out of line partial
+#[derive(Clone)]
+struct Outline {
+    ident: syn::Ident,
+    /// name - type and if it's borrowed, inferred types are borrowed
+    params: BTreeMap<String, (syn::Type, bool)>,
+}
+
 /// lowering from `SNode` to `HIR`
 /// TODO: Document
 /// TODO: refactor for only left booleans on the stack at recursion
@@ -108,6 +149,11 @@ struct LoweringContext<'a> {
     /// current file path
     // TODO:
     on_path: PathBuf,
+    /// Partials in a cycle of the partial graph
+    // Copiable
+    cyclic: &'a BTreeSet<PathBuf>,
+    /// Out of line partials, shared by the clones
+    outlines: Rc<RefCell<Outlines>>,
     /// buffer for writable
     // UnAlloc init
     buf_w: Vec<Writable<'a>>,
@@ -120,6 +166,9 @@ struct LoweringContext<'a> {
     /// path - nodes
     // Copiable
     ctx: Context<'a>,
+    /// Files of the nodes
+    // Copiable
+    sm: &'a SourceMap,
     /// Last parent conditional
     spans: Vec<Span>,
     /// whitespace buffer adapted from [`askama`](https://github.com/djc/askama)
@@ -130,6 +179,9 @@ struct LoweringContext<'a> {
     skip_ws: bool,
     // Copiable
     recursion: usize,
+    /// html state of `csp_nonce` templates
+    // Copiable
+    csp: Csp,
 }
 
 // TODO: remove in favor of mut reference
@@ -148,10 +200,14 @@ impl<'a> Clone for LoweringContext<'a> {
             buf_err: vec![],
             errors: vec![],
             ctx: self.ctx,
+            sm: self.sm,
             on_path: self.on_path.clone(),
+            cyclic: self.cyclic,
+            outlines: self.outlines.clone(),
             recursion: self.recursion,
             next_ws: self.next_ws,
             skip_ws: self.skip_ws,
+            csp: self.csp,
         }
     }
 }
@@ -161,6 +217,8 @@ impl<'a> LoweringContext<'a> {
         c: &'n Config,
         s: &'n Struct<'n>,
         ctx: Context<'n>,
+        cyclic: &'n BTreeSet<PathBuf>,
+        sm: &'n SourceMap,
         opt: HIROptions,
     ) -> LoweringContext<'n> {
         LoweringContext {
@@ -168,10 +226,13 @@ impl<'a> LoweringContext<'a> {
             c,
             s,
             ctx,
+            sm,
             buf_w: vec![],
             next_ws: None,
             on: vec![],
             on_path: s.path.clone(),
+            cyclic,
+            outlines: Default::default(),
             partial: None,
             scp: Scope::new(parse_str("self").unwrap(), 0),
             skip_ws: false,
@@ -180,6 +241,7 @@ impl<'a> LoweringContext<'a> {
             recursion: 0,
             buf_err: vec![],
             spans: vec![],
+            csp: Csp::default(),
         }
     }
 
@@ -202,6 +264,7 @@ impl<'a> LoweringContext<'a> {
         }
         assert!((0..buf.len() - 1)
             .all(|i| !matches!((&buf[i], &buf[i + 1]), (HIR::Lit(..), HIR::Lit(..)))));
+        buf.append(&mut self.outlines.borrow_mut().fns);
 
         if self.errors.is_empty() {
             Ok(buf)
@@ -216,21 +279,31 @@ impl<'a> LoweringContext<'a> {
                 Node::Local(expr) => {
                     self.skip_ws();
                     self.write_buf_writable(buf);
-                    let mut expr = (***expr.t()).clone();
-                    self.visit_local_mut(&mut expr);
-                    buf.push(HIR::Local(Box::new(expr)));
+                    let mut local = (***expr.t()).clone();
+                    let tries = self.try_collect(|c| c.visit_local(&local));
+                    self.visit_local_mut(&mut local);
+                    self.write_errors(expr.span());
+                    if self.s.options.error.is_some() {
+                        self.try_rewrite(tries, expr.span())
+                            .visit_local_mut(&mut local);
+                    }
+                    buf.push(HIR::Local(Box::new(local)));
                 }
                 Node::Safe(ws, sexpr) => {
                     let mut expr = (***sexpr.t()).clone();
 
                     self.handle_ws(*ws);
-                    self.visit_expr_mut(&mut expr);
-                    self.write_errors(sexpr.span());
+                    let valid = self.visit_sexpr_mut(&mut expr, sexpr.span());
 
                     if self.read_attributes(&mut expr).is_none()
                         && self.const_eval(&expr, true).is_none()
                     {
-                        validator::expression(sexpr, &mut self.errors);
+                        if valid {
+                            validator::expression(sexpr, &mut self.errors);
+                        }
+                        if self.s.options.strict_safe && !self.is_safe_field(&expr) {
+                            expr = parse2(quote!(TrustedHtml::__trusted_html(&(#expr)))).unwrap();
+                        }
                         self.buf_w.push(Writable::Expr(Box::new(expr), true));
                     }
                 }
@@ -238,12 +311,14 @@ impl<'a> LoweringContext<'a> {
                     let mut expr = (***sexpr.t()).clone();
 
                     self.handle_ws(*ws);
-                    self.visit_expr_mut(&mut expr);
-                    self.write_errors(sexpr.span());
+                    let valid = self.visit_sexpr_mut(&mut expr, sexpr.span());
 
                     if self.const_eval(&expr, false).is_none() {
-                        validator::expression(sexpr, &mut self.errors);
-                        self.buf_w.push(Writable::Expr(Box::new(expr), false));
+                        if valid {
+                            validator::expression(sexpr, &mut self.errors);
+                        }
+                        let safe = self.is_safe_field(&expr);
+                        self.buf_w.push(Writable::Expr(Box::new(expr), safe));
                     }
                 }
                 #[cfg(feature = "wasm-app")]
@@ -251,12 +326,11 @@ impl<'a> LoweringContext<'a> {
                     let mut expr = (***sexpr.t()).clone();
 
                     self.handle_ws(*ws);
-                    self.visit_expr_mut(&mut expr);
-                    self.write_errors(sexpr.span());
+                    self.visit_sexpr_mut(&mut expr, sexpr.span());
 
                     self.buf_w.push(Writable::LitP(quote!(#expr).to_string()));
                 }
-                Node::Lit(l, lit, r) => self.visit_lit(l, lit.t(), r),
+                Node::Lit(l, lit, r) => self.visit_lit(l, lit.t(), r, lit.span()),
                 Node::Helper(h) => {
                     self.spans.push(n.span());
                     self.visit_helper(buf, h);
@@ -275,7 +349,7 @@ impl<'a> LoweringContext<'a> {
                 Node::Comment(_) => self.skip_ws(),
                 Node::Raw(ws, l, v, r) => {
                     self.handle_ws(ws.0);
-                    self.visit_lit(l, v.t(), r);
+                    self.visit_lit(l, v.t(), r, v.span());
                     self.handle_ws(ws.1);
                 }
                 Node::Block(ws) => {
@@ -318,6 +392,8 @@ impl<'a> LoweringContext<'a> {
                         })
                     }
                 }
+                // Reported by the parser
+                Node::Invalid => self.handle_ws((false, false)),
                 Node::Error(err) => {
                     self.skip_ws();
                     if let Some(msg) = self.format_error(err) {
@@ -333,16 +409,31 @@ impl<'a> LoweringContext<'a> {
                     match e {
                         Json => {
                             let mut arg = (*args.t()[0]).clone();
-                            self.visit_expr_mut(&mut arg);
+                            self.visit_sexpr_mut(&mut arg, args.span());
                             let expr = parse2(quote!((&(#arg).__as_json()))).unwrap();
                             self.buf_w.push(Writable::Expr(Box::new(expr), false))
                         }
                         JsonPretty => {
                             let mut arg = (*args.t()[0]).clone();
-                            self.visit_expr_mut(&mut arg);
+                            self.visit_sexpr_mut(&mut arg, args.span());
                             let expr = parse2(quote!(&(#arg).__as_json_pretty())).unwrap();
                             self.buf_w.push(Writable::Expr(Box::new(expr), false))
                         }
+                        Sanitize => {
+                            let mut arg = (*args.t()[0]).clone();
+                            self.visit_sexpr_mut(&mut arg, args.span());
+                            match self.sanitize_policy() {
+                                Ok(policy) => {
+                                    let expr =
+                                        parse2(quote!((&(#arg)).__sanitize(#policy))).unwrap();
+                                    self.buf_w.push(Writable::Expr(Box::new(expr), false))
+                                }
+                                Err(message) => self.errors.push(ErrorMessage {
+                                    message,
+                                    span: args.span(),
+                                }),
+                            }
+                        }
                     }
                 }
                 #[allow(unreachable_patterns)]
@@ -369,7 +460,7 @@ impl<'a> LoweringContext<'a> {
         }
     }
 
-    fn visit_lit(&mut self, lws: &'a str, lit: &'a str, rws: &'a str) {
+    fn visit_lit(&mut self, lws: &'a str, lit: &'a str, rws: &'a str, span: Span) {
         debug_assert!(self.next_ws.is_none(), "{:?} {:?} ", self.next_ws, lit);
         if !lws.is_empty() {
             if self.skip_ws {
@@ -383,7 +474,11 @@ impl<'a> LoweringContext<'a> {
         }
 
         if !lit.is_empty() {
-            self.buf_w.push(Writable::Lit(lit));
+            if self.s.options.csp_nonce.is_some() {
+                self.visit_csp(lit, span);
+            } else {
+                self.buf_w.push(Writable::Lit(lit));
+            }
         }
 
         if !rws.is_empty() {
@@ -391,6 +486,44 @@ impl<'a> LoweringContext<'a> {
         }
     }
 
+    /// Writes the nonce in `script` and `style` tags of the literal
+    fn visit_csp(&mut self, lit: &'a str, span: Span) {
+        let mut last = 0;
+        for mark in self.csp.scan(lit) {
+            match mark {
+                Mark::Nonce(at) => {
+                    self.buf_w.push(Writable::Lit(&lit[last..at]));
+                    self.buf_w.push(Writable::Lit(" nonce=\""));
+                    self.buf_w
+                        .push(Writable::Expr(Box::new(self.csp_nonce()), false));
+                    self.buf_w.push(Writable::Lit("\""));
+                    last = at;
+                }
+                Mark::Handler(lo, hi) => self.errors.push(ErrorMessage {
+                    message: GError::CspEventHandler(lit[lo..hi].into()),
+                    span: Span {
+                        lo: span.lo + lo as u32,
+                        hi: span.lo + hi as u32,
+                    },
+                }),
+            }
+        }
+        self.buf_w.push(Writable::Lit(&lit[last..]));
+    }
+
+    /// Expression of the `csp_nonce` field
+    fn csp_nonce(&self) -> syn::Expr {
+        let field = self.s.options.csp_nonce.as_deref().expect("csp_nonce");
+        let expr = if self.s.variant.is_some() {
+            format!("(*{field})")
+        } else if is_tuple_index(field.as_bytes()) {
+            format!("self.{}", &field[1..])
+        } else {
+            format!("self.{field}")
+        };
+        parse_str(&expr).unwrap()
+    }
+
     fn visit_helper(&mut self, buf: &mut Vec<HIR>, h: &'a Helper<'a>) {
         use yarte_parser::Helper::*;
         match h {
@@ -412,8 +545,7 @@ impl<'a> LoweringContext<'a> {
         self.spans.push(scond.span());
         let mut cond = (***scond.t()).clone();
         self.handle_ws(ws.0);
-        self.visit_expr_mut(&mut cond);
-        self.write_errors(scond.span());
+        let valid = self.visit_sexpr_mut(&mut cond, scond.span());
 
         if let Some(val) = self.eval_bool(&cond) {
             if !val {
@@ -423,7 +555,9 @@ impl<'a> LoweringContext<'a> {
             }
             self.handle_ws(ws.1);
         } else {
-            validator::unless(scond, &mut self.errors);
+            if valid {
+                validator::unless(scond, &mut self.errors);
+            }
 
             self.write_buf_writable(buf);
             self.scp.push_scope(vec![]);
@@ -457,8 +591,7 @@ impl<'a> LoweringContext<'a> {
 
         self.handle_ws(ws.0);
         let mut arg = (***args.t()).clone();
-        self.visit_expr_mut(&mut arg);
-        self.write_errors(args.span());
+        self.visit_sexpr_mut(&mut arg, args.span());
         self.on.push(On::With(self.scp.len()));
         self.scp.push_scope(vec![arg]);
 
@@ -486,8 +619,7 @@ impl<'a> LoweringContext<'a> {
         });
 
         let mut args = (***sargs.t()).clone();
-        self.visit_expr_mut(&mut args);
-        self.write_errors(sargs.span());
+        let valid = self.visit_sexpr_mut(&mut args, sargs.span());
 
         if let Some(args) = self.eval_iter(&args) {
             self.const_iter(buf, ws, args, nodes, loop_var);
@@ -495,7 +627,9 @@ impl<'a> LoweringContext<'a> {
             return;
         }
 
-        validator::each(sargs, &mut self.errors);
+        if valid {
+            validator::each(sargs, &mut self.errors);
+        }
 
         self.handle_ws(ws.0);
         self.write_buf_writable(buf);
@@ -542,8 +676,7 @@ impl<'a> LoweringContext<'a> {
     ) {
         self.scp.push_scope(vec![]);
         let mut cond: syn::Expr = (***scond.t()).clone();
-        self.visit_expr_mut(&mut cond);
-        self.write_errors(scond.span());
+        let valid = self.visit_sexpr_mut(&mut cond, scond.span());
         self.handle_ws(pws.0);
 
         self.spans.push(scond.span());
@@ -553,7 +686,9 @@ impl<'a> LoweringContext<'a> {
             }
             (val, None, val)
         } else {
-            validator::ifs(scond, &mut self.errors);
+            if valid {
+                validator::ifs(scond, &mut self.errors);
+            }
             self.write_buf_writable(buf);
             let mut body = Vec::new();
             self.handle(block, &mut body);
@@ -578,8 +713,7 @@ impl<'a> LoweringContext<'a> {
 
             self.scp.push_scope(vec![]);
             let mut cond: syn::Expr = (***scond.t()).clone();
-            self.visit_expr_mut(&mut cond);
-            self.write_errors(scond.span());
+            let valid = self.visit_sexpr_mut(&mut cond, scond.span());
 
             self.spans.push(scond.span());
             if let Some(val) = self.eval_bool(&cond) {
@@ -595,7 +729,9 @@ impl<'a> LoweringContext<'a> {
                     last = i + 1 != ifs.len();
                 }
             } else {
-                validator::ifs(scond, &mut self.errors);
+                if valid {
+                    validator::ifs(scond, &mut self.errors);
+                }
 
                 let mut body = Vec::new();
                 self.handle(block, &mut body);
@@ -664,11 +800,15 @@ impl<'a> LoweringContext<'a> {
     ) -> GResult<()> {
         self.recursion += 1;
         if self.s.recursion_limit < self.recursion {
-            return Err(GError::RecursionLimit);
+            return Err(GError::RecursionLimit(path.into()));
         }
 
         // TODO: identifiers
         let p = self.c.resolve_partial(&self.on_path, path);
+        if outline(self.s, &self.opt, self.cyclic, &p, exprs, block.is_some()) {
+            self.recursion -= 1;
+            return self.visit_outlined(buf, a_ws, path, p, exprs);
+        }
         let nodes = self.ctx.get(&p).unwrap();
 
         // TODO: to on path stack without duplicates
@@ -691,15 +831,13 @@ impl<'a> LoweringContext<'a> {
             let mut cur = BTreeMap::new();
             for (k, expr) in no_visited {
                 let mut expr = expr.clone();
-                self.visit_expr_mut(&mut expr);
-                self.write_errors(exprs.span());
+                self.visit_sexpr_mut(&mut expr, exprs.span());
                 cur.insert(k, expr);
             }
 
             if let Some(scope) = scope {
                 let mut scope = scope.clone();
-                self.visit_expr_mut(&mut scope);
-                self.write_errors(exprs.span());
+                self.visit_sexpr_mut(&mut scope, exprs.span());
                 let old = mem::replace(&mut self.opt.resolve_to_self, true);
                 let count = self.scp.count;
                 // TODO: to heap stack without realloc every block
@@ -738,6 +876,240 @@ impl<'a> LoweringContext<'a> {
         Ok(())
     }
 
+    /// Partial at `p` rendered by its function, lowered at the first call
+    fn visit_outlined(
+        &mut self,
+        buf: &mut Vec<HIR>,
+        ws: Ws,
+        path: &str,
+        p: PathBuf,
+        exprs: &'a SVExpr,
+    ) -> GResult<()> {
+        let mut args = BTreeMap::new();
+        if !exprs.t().is_empty() {
+            for (k, expr) in visit_partial(exprs, &mut self.errors).0 {
+                let mut expr = expr.clone();
+                self.visit_sexpr_mut(&mut expr, exprs.span());
+                args.insert(k, expr);
+            }
+        }
+
+        let known = self.outlines.borrow().sigs.get(&p).cloned();
+        let Outline { ident, params } = match known {
+            Some(outline) => outline,
+            None => self.outline(p, &args)?,
+        };
+        if !args.keys().eq(params.keys()) {
+            let names: Vec<_> = params.keys().map(String::as_str).collect();
+            return Err(GError::OutlineArguments(path.into(), names.join(", ")));
+        }
+        let args = args
+            .values()
+            .zip(params.values())
+            .map(|(expr, (_, borrowed))| {
+                if *borrowed {
+                    quote!(&(#expr))
+                } else {
+                    quote!(#expr)
+                }
+            });
+
+        self.flush_ws(ws);
+        self.write_buf_writable(buf);
+        buf.push(HIR::Call(Box::new(
+            parse2(quote!(self.#ident(#(#args),*))).unwrap(),
+        )));
+        self.prepare_ws(ws);
+        Ok(())
+    }
+
+    /// Lowers the function of the partial at `p`
+    ///
+    /// Its parameters are the fields of the struct declared in the head comment of the
+    /// partial or the arguments `args` of the first call, with their inferred types.
+    /// The body resolves its parameters and the template fields
+    fn outline(&mut self, p: PathBuf, args: &BTreeMap<String, syn::Expr>) -> GResult<Outline> {
+        let nodes = self.ctx.get(&p).unwrap();
+        let (generics, params) = match self.declared(nodes) {
+            Some(Ok(decl)) => match decl.data {
+                syn::Data::Struct(syn::DataStruct {
+                    fields: syn::Fields::Named(fields),
+                    ..
+                }) => {
+                    let params = fields
+                        .named
+                        .into_iter()
+                        .map(|mut f| {
+                            Elide.visit_type_mut(&mut f.ty);
+                            (f.ident.unwrap().to_string(), (f.ty, false))
+                        })
+                        .collect();
+                    (elided(decl.generics), params)
+                }
+                syn::Data::Struct(syn::DataStruct {
+                    fields: syn::Fields::Unit,
+                    ..
+                }) => (elided(decl.generics), BTreeMap::new()),
+                _ => {
+                    return Err(GError::OutlineDeclaration(
+                        "expected struct with named fields".into(),
+                    ))
+                }
+            },
+            Some(Err(e)) => return Err(GError::OutlineDeclaration(e.to_string())),
+            None => {
+                let mut params = BTreeMap::new();
+                for (k, expr) in args {
+                    let ty = self
+                        .infer(expr)
+                        .ok_or_else(|| GError::OutlineType(k.clone()))?;
+                    params.insert(k.clone(), (ty, true));
+                }
+                (syn::Generics::default(), params)
+            }
+        };
+        let outline = Outline {
+            ident: self.outline_ident(&p),
+            params,
+        };
+        self.outlines
+            .borrow_mut()
+            .sigs
+            .insert(p.clone(), outline.clone());
+
+        let mut cur = BTreeMap::new();
+        for (k, (_, borrowed)) in &outline.params {
+            let ident = format_ident!("{}", k);
+            let expr = if *borrowed {
+                quote!((*#ident))
+            } else {
+                quote!(#ident)
+            };
+            cur.insert(k.clone(), parse2(expr).unwrap());
+        }
+        let mut body_cx = self.clone();
+        body_cx.on_path = p;
+        body_cx.scp = Scope::new(parse_str("self").unwrap(), self.scp.count);
+        body_cx.on = vec![];
+        body_cx.partial = Some((cur, 0));
+        body_cx.block = vec![];
+        body_cx.next_ws = None;
+        body_cx.skip_ws = false;
+
+        let mut body = vec![];
+        body_cx.handle(nodes, &mut body);
+        body_cx.flush_ws((false, false));
+        body_cx.write_buf_writable(&mut body);
+        self.errors.append(&mut body_cx.errors);
+        self.scp.count = body_cx.scp.count;
+        self.csp = body_cx.csp;
+
+        let Outline { ident, params } = &outline;
+        let inputs = params.iter().map(|(k, (ty, borrowed))| {
+            let k = format_ident!("{}", k);
+            if *borrowed {
+                quote!(#k: &#ty)
+            } else {
+                quote!(#k: #ty)
+            }
+        });
+        let where_clause = &generics.where_clause;
+        let sig = parse2(quote!(fn #ident #generics (#(#inputs),*) #where_clause)).unwrap();
+        self.outlines
+            .borrow_mut()
+            .fns
+            .push(HIR::Partial(Box::new(PartialFn { sig, body })));
+
+        Ok(outline)
+    }
+
+    /// Struct declared in the head comment of the partial `nodes`
+    fn declared(&self, nodes: &[SNode]) -> Option<syn::Result<syn::DeriveInput>> {
+        let head = nodes
+            .iter()
+            .find(|n| !matches!(n.t(), Node::Lit(_, lit, _) if lit.t().is_empty()))?;
+        match head.t() {
+            Node::Comment(_) => front_matter(head.span().source_text(self.sm), self.s.syntax),
+            _ => None,
+        }
+    }
+
+    /// Type of the resolved partial argument `expr`, a template field or a literal
+    fn infer(&self, expr: &syn::Expr) -> Option<syn::Type> {
+        use syn::Expr::*;
+        match expr {
+            Paren(ExprParen { expr, .. }) | Group(ExprGroup { expr, .. }) => self.infer(expr),
+            Reference(ExprReference {
+                expr,
+                mutability: None,
+                ..
+            }) => {
+                let ty = self.infer(expr)?;
+                Some(parse2(quote!(&#ty)).unwrap())
+            }
+            Field(ExprField { base, member, .. }) if self.is_self(base) => {
+                let name = match member {
+                    syn::Member::Named(ident) => ident.to_string(),
+                    syn::Member::Unnamed(index) => format!("_{}", index.index),
+                };
+                self.s
+                    .fields
+                    .iter()
+                    .enumerate()
+                    .find(|(i, f)| match &f.ident {
+                        Some(ident) => *ident == name,
+                        None => name == format!("_{i}"),
+                    })
+                    // Field tokens come from the compiler
+                    .and_then(|(_, f)| parse_str(&f.ty.to_token_stream().to_string()).ok())
+            }
+            Cast(ExprCast { ty, .. }) => Some((**ty).clone()),
+            Lit(lit) => {
+                let ty = match &lit.lit {
+                    syn::Lit::Str(_) => "&'static str",
+                    syn::Lit::Int(i) if !i.suffix().is_empty() => i.suffix(),
+                    syn::Lit::Int(_) => "i32",
+                    syn::Lit::Float(f) if !f.suffix().is_empty() => f.suffix(),
+                    syn::Lit::Float(_) => "f64",
+                    syn::Lit::Bool(_) => "bool",
+                    syn::Lit::Char(_) => "char",
+                    _ => return None,
+                };
+                parse_str(ty).ok()
+            }
+            _ => None,
+        }
+    }
+
+    fn is_self(&self, expr: &syn::Expr) -> bool {
+        matches!(expr, syn::Expr::Path(p) if p.path.is_ident("self"))
+    }
+
+    /// Method of the partial at `p`, named by its path in the template directories
+    fn outline_ident(&self, p: &Path) -> syn::Ident {
+        let rel = self
+            .c
+            .get_dirs()
+            .iter()
+            .find_map(|dir| p.strip_prefix(dir).ok())
+            .unwrap_or(p)
+            .with_extension("");
+        let rel: String = rel
+            .to_string_lossy()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        let mut ident = match &self.s.name {
+            Some(name) => format!("__partial_{name}_{rel}"),
+            None => format!("__partial_{rel}"),
+        };
+        let outlines = self.outlines.borrow();
+        if outlines.sigs.values().any(|o| o.ident == ident) {
+            ident.push_str(&format!("_{}", outlines.sigs.len()));
+        }
+        format_ident!("{}", ident)
+    }
+
     fn const_eval(&mut self, expr: &syn::Expr, safe: bool) -> Option<()> {
         macro_rules! push_some {
             ($expr:expr) => {{
@@ -754,7 +1126,17 @@ impl<'a> LoweringContext<'a> {
             Value::Float(a) => push_some!(a),
             Value::Bool(a) => push_some!(a),
             Value::Str(a) if safe || self.opt.is_text => push_some!(a),
-            Value::Str(a) => push_some!(escape(&a)),
+            // Custom escapers run at render time
+            Value::Str(_) if self.s.options.escaper.is_some() => None,
+            Value::Str(a) => match self.s.options.format {
+                Some(Format::Text) => push_some!(a),
+                Some(Format::Xml) => push_some!(XmlEscaper::escape(&a)),
+                Some(Format::Json) => push_some!(JsonEscaper::escape(&a)),
+                Some(Format::Latex) => push_some!(LatexEscaper::escape(&a)),
+                Some(Format::Csv) => push_some!(CsvEscaper::escape(&a)),
+                Some(Format::Shell) => push_some!(ShellEscaper::escape(&a)),
+                Some(Format::Html) | None => push_some!(escape(&a)),
+            },
             _ => None,
         })
     }
@@ -853,6 +1235,70 @@ impl<'a> LoweringContext<'a> {
         }
     }
 
+    /// Allowlist of `@sanitize` by `[sanitize]` of the configuration file
+    fn sanitize_policy(&self) -> Result<proc_macro2::TokenStream, GError> {
+        let Some(config) = &self.c.sanitize else {
+            return Ok(quote!(&SanitizePolicy::DEFAULT));
+        };
+        let list = |list: &Option<Vec<String>>, default: &[&str]| -> Vec<String> {
+            match list {
+                Some(list) => list.iter().map(|x| x.to_ascii_lowercase()).collect(),
+                None => default.iter().map(|x| x.to_string()).collect(),
+            }
+        };
+        let default = SanitizePolicy::DEFAULT;
+        let tags = list(&config.tags, default.tags());
+        let attributes = list(&config.attributes, default.attributes());
+        let url_schemes = list(&config.url_schemes, default.url_schemes());
+        fn borrow(list: &[String]) -> Vec<&str> {
+            list.iter().map(String::as_str).collect()
+        }
+        SanitizePolicy::check(&borrow(&tags), &borrow(&attributes), &borrow(&url_schemes))
+            .map_err(|e| GError::SanitizePolicy(e.to_string()))?;
+        Ok(quote!({
+            const POLICY: SanitizePolicy = SanitizePolicy::new(
+                &[#(#tags),*],
+                &[#(#attributes),*],
+                &[#(#url_schemes),*],
+            );
+            &POLICY
+        }))
+    }
+
+    /// Resolved expression is a `#[template(safe)]` field
+    fn is_safe_field(&self, expr: &syn::Expr) -> bool {
+        let field = match expr {
+            syn::Expr::Field(ExprField { base, member, .. }) => match &**base {
+                syn::Expr::Path(p) if p.path.is_ident("self") => match member {
+                    syn::Member::Named(ident) => ident.to_string(),
+                    syn::Member::Unnamed(index) => format!("_{}", index.index),
+                },
+                _ => return false,
+            },
+            // Enum variant fields
+            syn::Expr::Paren(ExprParen { expr, .. }) if self.s.variant.is_some() => match &**expr {
+                syn::Expr::Unary(ExprUnary {
+                    op: syn::UnOp::Deref(_),
+                    expr,
+                    ..
+                }) => match &**expr {
+                    syn::Expr::Path(p) => match p.path.get_ident() {
+                        Some(ident) => ident.to_string(),
+                        None => return false,
+                    },
+                    _ => return false,
+                },
+                _ => return false,
+            },
+            _ => return false,
+        };
+        self.s.options.safe_fields.contains(&field)
+    }
+
+    fn is_self_root(&self) -> bool {
+        matches!(self.scp.root(), syn::Expr::Path(p) if p.path.is_ident("self"))
+    }
+
     fn resolve_path(
         &self,
         syn::ExprPath { attrs, qself, path }: &syn::ExprPath,
@@ -916,6 +1362,11 @@ impl<'a> LoweringContext<'a> {
 
         macro_rules! self_var {
             ($ident:ident) => {{
+                // Enum variant fields are bound by reference in the match arm
+                if self.s.variant.is_some() && self.opt.resolve_to_self && self.is_self_root() {
+                    let field = format_ident!("{}", $ident);
+                    writes!((*#field))
+                }
                 index_var!($ident, 0);
                 let field = format_ident!("{}", $ident);
                 if self.opt.resolve_to_self {
@@ -994,6 +1445,59 @@ impl<'a> LoweringContext<'a> {
         }
     }
 
+    /// Visits a template expression and writes its errors
+    ///
+    /// Returns if it hasn't errors, validators only check valid ones so a span is reported once
+    fn visit_sexpr_mut(&mut self, expr: &mut syn::Expr, span: Span) -> bool {
+        let tries = self.try_collect(|c| c.visit_expr(expr));
+        self.visit_expr_mut(expr);
+        let valid = self.buf_err.is_empty();
+        self.write_errors(span);
+        if self.s.options.error.is_some() {
+            self.try_rewrite(tries, span).visit_expr_mut(expr);
+        }
+        valid
+    }
+
+    /// `?` expressions of templates with `error` attribute
+    fn try_collect(&self, visit: impl FnOnce(&mut TryCollect)) -> TryCollect {
+        let mut tries = TryCollect::default();
+        if self.s.options.error.is_some() {
+            visit(&mut tries);
+        }
+        tries
+    }
+
+    /// Rewrites the collected `?` expressions of the template expression at `span`
+    fn try_rewrite(&self, tries: TryCollect, span: Span) -> TryRewrite {
+        let parent = format_ident!("{}", self.opt.parent);
+        let file = self
+            .c
+            .get_dirs()
+            .iter()
+            .find_map(|dir| self.on_path.strip_prefix(dir).ok())
+            .unwrap_or(&self.on_path)
+            .to_string_lossy()
+            .into_owned();
+        let locations: Vec<_> = tries
+            .0
+            .into_iter()
+            .map(|at| {
+                let at = span.join_proc(at);
+                let LineColumn { line, column } = at.start(self.sm);
+                let expr = at.source_text(self.sm);
+                let column = column + 1;
+                quote!(&#parent::Location {
+                    file: #file,
+                    line: #line,
+                    column: #column,
+                    expr: #expr,
+                })
+            })
+            .collect();
+        TryRewrite(locations.into_iter())
+    }
+
     fn write_errors(&mut self, span: Span) {
         for (message, range) in mem::take(&mut self.buf_err) {
             self.errors
@@ -1060,6 +1564,66 @@ impl<'a> LoweringContext<'a> {
     }
 }
 
+/// Partial call rendered by a function of the template
+///
+/// Partials in a cycle of the partial graph are out of line, they only compile at a
+/// constant depth otherwise, and all of them in templates without inlined partials.
+/// Partial blocks, calls with a scope argument, macros, enum variants and `Try`
+/// templates inline them
+/// Elides the lifetimes of the declared arguments, the template can be generic over the same names
+struct Elide;
+
+impl VisitMut for Elide {
+    fn visit_lifetime_mut(&mut self, i: &mut syn::Lifetime) {
+        if i.ident != "static" {
+            *i = syn::Lifetime::new("'_", i.span());
+        }
+    }
+}
+
+/// Declared generics without lifetimes
+fn elided(mut generics: syn::Generics) -> syn::Generics {
+    generics.params = generics
+        .params
+        .into_iter()
+        .filter(|p| !matches!(p, syn::GenericParam::Lifetime(_)))
+        .map(|mut p| {
+            Elide.visit_generic_param_mut(&mut p);
+            p
+        })
+        .collect();
+    if let Some(w) = &mut generics.where_clause {
+        w.predicates = w
+            .predicates
+            .clone()
+            .into_iter()
+            .filter(|p| !matches!(p, syn::WherePredicate::Lifetime(_)))
+            .collect();
+        Elide.visit_where_clause_mut(w);
+    }
+    generics
+}
+
+fn outline(
+    s: &Struct,
+    opt: &HIROptions,
+    cyclic: &BTreeSet<PathBuf>,
+    path: &Path,
+    exprs: &SVExpr,
+    block: bool,
+) -> bool {
+    let scope = exprs
+        .t()
+        .first()
+        .is_some_and(|e| matches!(**e, syn::Expr::Path(_)));
+    opt.resolve_to_self
+        && s.variant.is_none()
+        && s.options.error.is_none()
+        && !block
+        && !scope
+        && (cyclic.contains(path) || !s.options.inline_partials)
+}
+
 fn is_super<S>(i: &Punctuated<PathSegment, S>) -> Option<(usize, String)> {
     let idents: Vec<String> = Punctuated::pairs(i)
         .map(|x| x.value().ident.to_string())
@@ -8,7 +8,7 @@ use syn::{
 
 use yarte_parser::StmtLocal;
 
-use crate::{Each, IfElse, HIR};
+use crate::{Each, IfElse, PartialFn, HIR};
 
 #[inline]
 pub fn serialize<'a, W, I>(ir: I, writer: &mut W) -> fmt::Result
@@ -59,6 +59,21 @@ where
                 writer.write_str(&quote!(#expr).to_string())?;
                 writer.write_str(" }}")?
             }
+            HIR::Call(a) => {
+                let mut expr = syn::Expr::MethodCall(*a.clone());
+                visitor.visit_expr_mut(&mut expr);
+                writer.write_str("{{{ ")?;
+                writer.write_str(&quote!(#expr).to_string())?;
+                writer.write_str(" }}}")?
+            }
+            HIR::Partial(a) => {
+                let PartialFn { sig, body } = &**a;
+                writer.write_str("{{#*inline \"")?;
+                writer.write_str(&quote!(#sig).to_string())?;
+                writer.write_str("\" }}")?;
+                serialize(body.iter(), writer)?;
+                writer.write_str("{{/inline}}")?;
+            }
             HIR::IfElse(a) => {
                 let IfElse { ifs, if_else, els } = &**a;
                 let (expr, ir) = ifs;
@@ -243,10 +258,7 @@ mod test {
     use syn::parse2;
 
     use yarte_helpers::config::Config;
-    use yarte_parser::{
-        emitter, parse,
-        source_map::{clean, get_cursor},
-    };
+    use yarte_parser::{emitter, parse, source_map::SourceMap};
 
     use crate::{generate, visit_derive};
 
@@ -262,15 +274,20 @@ mod test {
         let s = visit_derive(&der, config).unwrap();
         let mut src = BTreeMap::new();
         src.insert(s.path.clone(), s.src.clone());
-        let sources = parse(get_cursor(&s.path, &s.src)).unwrap();
+        let mut sm = SourceMap::default();
+        let sources = parse(sm.get_cursor(&s.path, &s.src)).unwrap();
         let mut ctx = BTreeMap::new();
         ctx.insert(&s.path, sources);
 
-        let ir = generate(config, &s, &ctx, Default::default())
-            .unwrap_or_else(|e| emitter(&src, config, e.into_iter()));
-        clean();
-
-        ir
+        generate(
+            config,
+            &s,
+            &ctx,
+            &Default::default(),
+            &sm,
+            Default::default(),
+        )
+        .unwrap_or_else(|e| emitter(&src, config, &sm, e.into_iter()))
     }
 
     #[test]
@@ -1,5 +1,7 @@
+use proc_macro2::{Delimiter, Spacing, TokenStream, TokenTree};
 use syn::spanned::Spanned;
 
+use yarte_helpers::config::Config;
 use yarte_parser::{source_map::Span, ErrorMessage, SExpr};
 
 use crate::error::{GError, MiddleError};
@@ -87,3 +89,147 @@ pub(super) fn partial_assign<T: AsRef<syn::Expr>>(
         }),
     }
 }
+
+/// Macros of strict templates without side effects
+const STRICT_MACROS: &[&str] = &["format", "concat", "stringify"];
+
+/// Methods of strict templates without side effects
+#[rustfmt::skip]
+const STRICT_METHODS: &[&str] = &[
+    "len", "is_empty", "iter", "chars", "bytes", "lines", "enumerate", "rev", "take", "skip",
+    "zip", "map", "filter", "count", "first", "last", "get", "contains", "contains_key",
+    "starts_with", "ends_with", "keys", "values", "join", "trim", "to_string", "to_owned",
+    "to_uppercase", "to_lowercase", "as_str", "as_ref", "as_deref", "is_some", "is_none",
+    "is_ok", "is_err", "unwrap_or", "unwrap_or_default", "abs", "min", "max", "round",
+    "floor", "ceil", "pow", "clone", "eq", "ne", "cmp",
+];
+
+/// Functions of strict templates without side effects
+const STRICT_FUNCTIONS: &[&str] = &[
+    "Some",
+    "Ok",
+    "Err",
+    "String::from",
+    "String::new",
+    "Vec::new",
+    "Default::default",
+];
+
+/// Keywords followed by a parenthesized expression, like `if (a)`
+const CALL_KEYWORDS: &[&str] = &["if", "match", "in", "return", "as", "let", "else", "move"];
+
+pub(super) fn strict_macro(c: &Config, name: &str) -> bool {
+    STRICT_MACROS.contains(&name) || c.strict_allow.macros.iter().any(|m| m == name)
+}
+
+pub(super) fn strict_method(c: &Config, name: &str) -> bool {
+    STRICT_METHODS.contains(&name) || c.strict_allow.methods.iter().any(|m| m == name)
+}
+
+pub(super) fn strict_function(c: &Config, path: &str) -> bool {
+    STRICT_FUNCTIONS.contains(&path) || c.strict_allow.functions.iter().any(|f| f == path)
+}
+
+/// Path of a called function, without generic arguments
+pub(super) fn function_path(func: &syn::Expr) -> Option<String> {
+    match func {
+        syn::Expr::Path(syn::ExprPath {
+            qself: None, path, ..
+        }) => Some(
+            path.segments
+                .iter()
+                .map(|s| s.ident.to_string())
+                .collect::<Vec<_>>()
+                .join("::"),
+        ),
+        _ => None,
+    }
+}
+
+/// Strict template errors of macro arguments
+pub(super) fn strict_tokens(
+    c: &Config,
+    tokens: TokenStream,
+    out: &mut Vec<(GError, proc_macro2::Span)>,
+) {
+    let tokens: Vec<TokenTree> = tokens.into_iter().collect();
+    for (i, token) in tokens.iter().enumerate() {
+        let next = tokens.get(i + 1);
+        match token {
+            TokenTree::Group(g) => strict_tokens(c, g.stream(), out),
+            TokenTree::Ident(ident) if ident == "unsafe" => {
+                out.push((GError::StrictUnsafe, ident.span()))
+            }
+            TokenTree::Ident(ident) if ident == "loop" || ident == "while" || ident == "for" => {
+                out.push((GError::StrictLoop, ident.span()))
+            }
+            TokenTree::Ident(ident) => match next {
+                Some(TokenTree::Punct(p))
+                    if p.as_char() == '!' && p.spacing() == Spacing::Alone =>
+                {
+                    let name = ident.to_string();
+                    if !strict_macro(c, &name) {
+                        out.push((GError::StrictMacro(name), ident.span()))
+                    }
+                }
+                // `path(`, methods are checked at `.`
+                Some(TokenTree::Group(g)) if g.delimiter() == Delimiter::Parenthesis => {
+                    let method = i > 0
+                        && matches!(&tokens[i - 1], TokenTree::Punct(p) if p.as_char() == '.');
+                    let path = token_path(&tokens[..=i]);
+                    if !method
+                        && !CALL_KEYWORDS.contains(&path.as_str())
+                        && !strict_function(c, &path)
+                    {
+                        out.push((GError::StrictFunction(path), ident.span()))
+                    }
+                }
+                _ => (),
+            },
+            TokenTree::Punct(p) if p.as_char() == '&' => match next {
+                Some(TokenTree::Ident(ident)) if ident == "mut" => {
+                    out.push((GError::StrictMutRef, ident.span()))
+                }
+                _ => (),
+            },
+            // `.method(` or `.method::<`
+            TokenTree::Punct(p) if p.as_char() == '.' => {
+                if let (Some(TokenTree::Ident(ident)), Some(after)) = (next, tokens.get(i + 2)) {
+                    let call = match after {
+                        TokenTree::Group(g) => g.delimiter() == Delimiter::Parenthesis,
+                        TokenTree::Punct(p) => p.as_char() == ':',
+                        _ => false,
+                    };
+                    let name = ident.to_string();
+                    if call && !strict_method(c, &name) {
+                        out.push((GError::StrictMethod(name), ident.span()))
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Path ending at the last token, like `std::mem::take`
+fn token_path(tokens: &[TokenTree]) -> String {
+    let mut segments = vec![];
+    let mut i = tokens.len();
+    while let Some(TokenTree::Ident(ident)) = i.checked_sub(1).map(|i| &tokens[i]) {
+        segments.push(ident.to_string());
+        i -= 1;
+        match (
+            i.checked_sub(2).map(|i| &tokens[i]),
+            i.checked_sub(1).map(|i| &tokens[i]),
+        ) {
+            (Some(TokenTree::Punct(a)), Some(TokenTree::Punct(b)))
+                if a.as_char() == ':' && b.as_char() == ':' =>
+            {
+                i -= 2
+            }
+            _ => break,
+        }
+    }
+    segments.reverse();
+    segments.join("::")
+}
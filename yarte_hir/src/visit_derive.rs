@@ -1,13 +1,19 @@
 use std::{
     convert::{TryFrom, TryInto},
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 
 use proc_macro2::TokenStream;
-use quote::quote;
-use syn::{parse_str, visit::Visit, Data, Error, ItemEnum};
+use quote::{format_ident, quote, ToTokens};
+use syn::{
+    parse::{Parse, ParseStream},
+    parse_str,
+    punctuated::Punctuated,
+    visit::Visit,
+    Data, Error, Fields, ItemEnum,
+};
 
-use yarte_helpers::config::Config;
+use yarte_helpers::config::{Config, Syntax};
 
 // TODO:
 const RECURSION_LIMIT: usize = 128;
@@ -27,12 +33,62 @@ pub struct Struct<'a> {
     pub recursion_limit: usize,
     pub msgs: Option<ItemEnum>,
     pub script: Option<String>,
+    pub syntax: Syntax,
     pub fields: Vec<syn::Field>,
     pub ident: &'a syn::Ident,
     generics: &'a syn::Generics,
+    /// Variant of the enum this template renders
+    pub variant: Option<&'a syn::Variant>,
+    /// Templates of each enum variant, empty for structs
+    pub variants: Vec<Struct<'a>>,
+    /// Name of a named template, rendered by its own methods
+    pub name: Option<String>,
+    /// Named templates of the struct
+    pub formats: Vec<Struct<'a>>,
+    /// Rendering options of the template
+    pub options: Options,
+}
+
+/// Rendering options of a template, by its attributes and the configuration
+///
+/// Enum variants and named templates inherit the options of the struct attribute
+#[derive(Debug)]
+pub struct Options {
+    /// Output format, by `format` attribute or template inner extension
+    pub format: Option<Format>,
+    /// Escaper type of `escape` attribute, overrides the format escaper
+    pub escaper: Option<syn::Path>,
+    /// `{{{ }}}` only writes trusted html types
+    pub strict_safe: bool,
+    /// Fields with `#[template(safe)]`, tuple fields as `_0`, `_1`, ...
+    pub safe_fields: Vec<String>,
+    /// Field with the nonce of `script` and `style` tags
+    pub csp_nonce: Option<String>,
+    /// Restricted Rust of templates edited by non Rust developers
+    pub strict: bool,
+    /// Error type of `?` expressions, implements the `Try` template traits
+    pub error: Option<syn::Path>,
+    /// Inlines partials, they're rendered by functions of the template otherwise
+    pub inline_partials: bool,
 }
 
 impl<'a> Struct<'a> {
+    /// Has a template without name
+    pub fn has_template(&self) -> bool {
+        !self.path.as_os_str().is_empty()
+    }
+
+    /// Sets `format` of templates without it, enum variants use the enum format
+    pub fn default_format(&mut self, format: Format) {
+        let own = *self.options.format.get_or_insert(format);
+        for v in &mut self.variants {
+            v.options.format = Some(own);
+        }
+        for f in &mut self.formats {
+            f.options.format.get_or_insert(format);
+        }
+    }
+
     pub fn implement_head(&self, t: TokenStream, body: &TokenStream) -> TokenStream {
         let Struct {
             ident, generics, ..
@@ -41,6 +97,62 @@ impl<'a> Struct<'a> {
 
         quote!(impl #impl_generics #t for #ident #orig_ty_generics #where_clause { #body })
     }
+
+    pub fn implement_inherent(&self, body: &TokenStream) -> TokenStream {
+        let Struct {
+            ident, generics, ..
+        } = *self;
+        let (impl_generics, orig_ty_generics, where_clause) = generics.split_for_impl();
+
+        quote!(impl #impl_generics #ident #orig_ty_generics #where_clause { #body })
+    }
+
+    /// `match` over the enum variants with its fields in scope
+    ///
+    /// Tuple fields are bound as `_0`, `_1`, ...
+    pub fn implement_match(&self, scrutinee: TokenStream, bodies: &[TokenStream]) -> TokenStream {
+        debug_assert_eq!(self.variants.len(), bodies.len());
+        let pats = self.variants.iter().map(|s| s.pattern());
+
+        quote!(match #scrutinee {
+            #(#[allow(unused_variables)] #pats => { #bodies })*
+        })
+    }
+
+    fn pattern(&self) -> TokenStream {
+        let syn::Variant { ident, fields, .. } = self.variant.expect("enum variant");
+        match fields {
+            Fields::Named(f) => {
+                let names = f.named.iter().map(|f| &f.ident);
+                quote!(Self::#ident { #(#names),* })
+            }
+            Fields::Unnamed(f) => {
+                let names = (0..f.unnamed.len()).map(|i| format_ident!("_{}", i));
+                quote!(Self::#ident(#(#names),*))
+            }
+            Fields::Unit => quote!(Self::#ident),
+        }
+    }
+}
+
+/// Context struct declared in the template head comment
+///
+/// ```handlebars
+/// {{!-- yarte: struct Page { title: String, items: Vec<Item> } --!}}
+/// ```
+pub fn front_matter(src: &str, syntax: Syntax) -> Option<syn::Result<syn::DeriveInput>> {
+    let rest = src
+        .trim_start()
+        .strip_prefix(syntax.open())?
+        .strip_prefix('!')?;
+    let (rest, dashes) = match rest.strip_prefix("--") {
+        Some(rest) => (rest, "--"),
+        None => (rest, ""),
+    };
+    let end = rest.find(&format!("{dashes}!{}", syntax.close()))?;
+    let decl = rest[..end].trim_start().strip_prefix(FRONT_MATTER)?;
+
+    Some(parse_str(decl))
 }
 
 struct StructBuilder<'a> {
@@ -50,11 +162,23 @@ struct StructBuilder<'a> {
     script: Option<String>,
     recursion_limit: Option<usize>,
     src: Option<String>,
+    syntax: Option<Syntax>,
+    name: Option<String>,
+    format: Option<Format>,
+    escaper: Option<syn::Path>,
+    strict_safe: Option<bool>,
+    safe_fields: Vec<String>,
+    csp_nonce: Option<syn::LitStr>,
+    strict: Option<bool>,
+    error: Option<syn::Path>,
+    inline_partials: Option<bool>,
     err: Vec<Error>,
     ident: String,
     config: &'a Config,
 }
 
+type TemplateArgs = Punctuated<TemplateArg, syn::Token![,]>;
+
 impl<'a> StructBuilder<'a> {
     fn new(config: &Config) -> StructBuilder {
         StructBuilder {
@@ -66,6 +190,16 @@ impl<'a> StructBuilder<'a> {
             script: None,
             recursion_limit: None,
             src: None,
+            syntax: None,
+            name: None,
+            format: None,
+            escaper: None,
+            strict_safe: None,
+            safe_fields: vec![],
+            csp_nonce: None,
+            strict: None,
+            error: None,
+            inline_partials: None,
             err: vec![],
         }
     }
@@ -79,45 +213,98 @@ impl<'a> StructBuilder<'a> {
             ..
         } = i;
         self.ident = ident.to_string();
-        match data {
-            Data::Struct(ref i) => {
-                self.visit_data_struct(i);
-            }
-            Data::Enum(_) | Data::Union(_) => {
-                self.err.push(Error::new_spanned(i, "need a `struc`"))
+        let (msgs, named) = self.visit_attrs(attrs);
+        let template = attrs
+            .iter()
+            .find(|x| x.path.is_ident("template") && !named.iter().any(|(a, ..)| a == x));
+
+        let mut variants = vec![];
+        let mut formats: Vec<Struct> = vec![];
+        let (path, src) = match data {
+            Data::Struct(ref data) => {
+                self.visit_data_struct(data);
+                self.csp_field();
+                for (attr, name, args) in named {
+                    if formats.iter().any(|f| f.name.as_deref() == Some(&name)) {
+                        self.err.push(Error::new_spanned(
+                            attr,
+                            format!("duplicate template name '{name}'"),
+                        ));
+                    }
+                    let mut builder = self.child(&name);
+                    builder.fields = self.fields.clone();
+                    builder.safe_fields = self.safe_fields.clone();
+                    builder.name = Some(name);
+                    builder.visit_args(args);
+                    if builder.error.take().is_some() {
+                        self.err.push(Error::new_spanned(
+                            attr,
+                            "named templates don't support attribute 'error'",
+                        ));
+                    }
+                    formats.push(builder.finish(attr, ident, generics, None, &mut self.err));
+                }
+                match template {
+                    Some(attr) => self.template(attr),
+                    None if formats.is_empty() => self.template(i),
+                    None => (PathBuf::new(), String::new()),
+                }
             }
-        }
-        let mut msgs = None;
-        for i in attrs {
-            if i.path.is_ident("template") {
-                match i.parse_meta() {
-                    Ok(ref m) => self.visit_meta(m),
-                    Err(e) => {
-                        self.err.push(e);
-                        continue;
+            Data::Enum(ref data) => {
+                if let (Some(attr), Some(_)) = (template, &self.path) {
+                    self.err.push(Error::new_spanned(
+                        attr,
+                        "enum templates are specified on each variant",
+                    ));
+                }
+                if data.variants.is_empty() {
+                    self.err
+                        .push(Error::new_spanned(i, "need an enum with variants"));
+                }
+                self.no_named(&named);
+                for v in &data.variants {
+                    let mut builder = self.child(&v.ident);
+                    let (_, named) = builder.visit_attrs(&v.attrs);
+                    builder.no_named(&named);
+                    builder.visit_fields(&v.fields);
+                    variants.push(builder.finish(v, ident, generics, Some(v), &mut self.err));
+                }
+                let mut formats = variants.iter().filter_map(|v: &Struct| v.options.format);
+                let same_format = match formats.next() {
+                    Some(first) => {
+                        let format = *self.format.get_or_insert(first);
+                        format == first && formats.all(|f| f == format)
                     }
+                    None => true,
                 };
-            } else if i.path.is_ident("msg") {
-                let tokens = i.tokens.to_string();
-                // TODO:
-                let tokens = tokens.get(1..tokens.len() - 1).expect("some");
-                let enu: ItemEnum = parse_str(tokens).expect("valid enum");
-                msgs = Some(enu);
+                let escaper =
+                    |s: &Struct| s.options.escaper.as_ref().map(|e| quote!(#e).to_string());
+                let error = |s: &Struct| s.options.error.as_ref().map(|e| quote!(#e).to_string());
+                let same_escaper = variants
+                    .windows(2)
+                    .all(|v| escaper(&v[0]) == escaper(&v[1]) && error(&v[0]) == error(&v[1]));
+                if same_format && same_escaper {
+                    if let Some(v) = variants.first() {
+                        self.escaper = v.options.escaper.clone();
+                        self.error = v.options.error.clone();
+                    }
+                } else {
+                    self.err.push(Error::new_spanned(
+                        i,
+                        "enum variants need templates with the same format, escaper and error",
+                    ));
+                }
+                (PathBuf::new(), String::new())
             }
-        }
-
-        let (path, src) = match (self.path, self.src) {
-            (Some(path), Some(src)) => (path, src),
-            _ => {
-                self.err.push(Error::new_spanned(
-                    attrs.iter().find(|x| x.path.is_ident("template")).unwrap(),
-                    "must specify 'src' or 'path'",
-                ));
+            Data::Union(_) => {
+                self.err
+                    .push(Error::new_spanned(i, "need a `struct` or an `enum`"));
                 (PathBuf::new(), String::new())
             }
         };
 
         if self.err.is_empty() {
+            let options = self.options(&path);
             Ok(Struct {
                 recursion_limit: self.recursion_limit.unwrap_or(RECURSION_LIMIT),
                 fields: self.fields,
@@ -127,19 +314,265 @@ impl<'a> StructBuilder<'a> {
                 path,
                 print: self.print.unwrap_or(Print::None),
                 script: self.script,
+                syntax: self.syntax.unwrap_or(self.config.syntax),
                 src,
+                variant: None,
+                variants,
+                name: None,
+                formats,
+                options,
             })
         } else {
             Err(self.err.iter().flat_map(Error::to_compile_error).collect())
         }
     }
+
+    /// Builder of an enum variant or a named template with the template options as defaults
+    fn child<T: std::fmt::Display>(&self, name: T) -> StructBuilder<'a> {
+        StructBuilder {
+            config: self.config,
+            ident: format!("{}/{}", self.ident, name),
+            fields: vec![],
+            path: None,
+            print: self.print,
+            script: self.script.clone(),
+            recursion_limit: self.recursion_limit,
+            src: None,
+            syntax: self.syntax,
+            name: None,
+            format: None,
+            escaper: self.escaper.clone(),
+            strict_safe: self.strict_safe,
+            safe_fields: vec![],
+            csp_nonce: self.csp_nonce.clone(),
+            strict: self.strict,
+            error: self.error.clone(),
+            inline_partials: self.inline_partials,
+            err: vec![],
+        }
+    }
+
+    /// Child template moving its errors to `err`
+    fn finish<'s, T: ToTokens>(
+        mut self,
+        at: T,
+        ident: &'s syn::Ident,
+        generics: &'s syn::Generics,
+        variant: Option<&'s syn::Variant>,
+        err: &mut Vec<Error>,
+    ) -> Struct<'s> {
+        let (path, src) = self.template(at);
+        self.csp_field();
+        err.append(&mut self.err);
+        let options = self.options(&path);
+        Struct {
+            recursion_limit: self.recursion_limit.unwrap_or(RECURSION_LIMIT),
+            fields: self.fields,
+            generics,
+            ident,
+            msgs: None,
+            path,
+            print: self.print.unwrap_or(Print::None),
+            script: self.script,
+            syntax: self.syntax.unwrap_or(self.config.syntax),
+            src,
+            variant,
+            variants: vec![],
+            name: self.name,
+            formats: vec![],
+            options,
+        }
+    }
+
+    /// Options of the template at `path` with the configuration defaults
+    fn options(&mut self, path: &Path) -> Options {
+        Options {
+            format: self.format.or_else(|| Format::from_path(path)),
+            escaper: self.escaper.take(),
+            strict_safe: self.strict_safe.unwrap_or(false),
+            safe_fields: std::mem::take(&mut self.safe_fields),
+            csp_nonce: self.csp_nonce.take().map(|f| f.value()),
+            strict: self.strict.unwrap_or(self.config.strict),
+            error: self.error.take(),
+            inline_partials: self.inline_partials.unwrap_or(self.config.inline_partials),
+        }
+    }
+
+    fn template<T: ToTokens>(&mut self, at: T) -> (PathBuf, String) {
+        match (self.path.take(), self.src.take()) {
+            (Some(path), Some(src)) => (path, src),
+            _ => {
+                self.err
+                    .push(Error::new_spanned(at, "must specify 'src' or 'path'"));
+                (PathBuf::new(), String::new())
+            }
+        }
+    }
+
+    /// `csp_nonce` is a template field
+    fn csp_field(&mut self) {
+        if let Some(lit) = &self.csp_nonce {
+            let field = lit.value();
+            let exists = self.fields.iter().enumerate().any(|(i, f)| match &f.ident {
+                Some(ident) => *ident == field,
+                None => field == format!("_{i}"),
+            });
+            if !exists {
+                self.err.push(Error::new_spanned(
+                    lit,
+                    format!("attribute 'csp_nonce' field '{field}' not found"),
+                ));
+            }
+        }
+    }
+
+    fn no_named(&mut self, named: &[(&syn::Attribute, String, TemplateArgs)]) {
+        for (attr, ..) in named {
+            self.err.push(Error::new_spanned(
+                attr,
+                "named templates are only supported on structs",
+            ));
+        }
+    }
+
+    fn visit_args(&mut self, args: TemplateArgs) {
+        for arg in args {
+            match arg {
+                TemplateArg::Meta(ref m) => self.visit_nested_meta(m),
+                TemplateArg::Delimiters(tuple) => self.delimiters(&tuple),
+            }
+        }
+    }
+
+    /// Visits template attributes without name and returns the named ones
+    #[allow(clippy::type_complexity)]
+    fn visit_attrs<'i>(
+        &mut self,
+        attrs: &'i [syn::Attribute],
+    ) -> (
+        Option<ItemEnum>,
+        Vec<(&'i syn::Attribute, String, TemplateArgs)>,
+    ) {
+        let mut msgs = None;
+        let mut named = vec![];
+        for i in attrs {
+            if i.path.is_ident("template") {
+                match i.parse_args_with(TemplateArgs::parse_terminated) {
+                    Ok(args) => match args.iter().find_map(TemplateArg::name) {
+                        Some(name) => named.push((i, name, args)),
+                        None => self.visit_args(args),
+                    },
+                    Err(e) => {
+                        self.err.push(e);
+                        continue;
+                    }
+                };
+            } else if i.path.is_ident("msg") {
+                let tokens = i.tokens.to_string();
+                // TODO:
+                let tokens = tokens.get(1..tokens.len() - 1).expect("some");
+                let enu: ItemEnum = parse_str(tokens).expect("valid enum");
+                msgs = Some(enu);
+            }
+        }
+
+        (msgs, named)
+    }
+}
+
+impl<'a> StructBuilder<'a> {
+    fn delimiters(&mut self, tuple: &syn::ExprTuple) {
+        let lits = tuple
+            .elems
+            .iter()
+            .map(|e| match e {
+                syn::Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Str(s),
+                    ..
+                }) => Some(s.value()),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>();
+        match lits.as_deref() {
+            Some([open, close]) => match Syntax::new(open, close) {
+                Ok(syntax) => self.syntax = Some(syntax),
+                Err(e) => self.err.push(Error::new_spanned(tuple, e)),
+            },
+            _ => self.err.push(Error::new_spanned(
+                tuple,
+                "attribute 'delimiters' must be a pair of string literals",
+            )),
+        }
+    }
+}
+
+/// Argument of `template` attribute
+enum TemplateArg {
+    /// `delimiters = ("[[", "]]")`
+    Delimiters(syn::ExprTuple),
+    Meta(syn::NestedMeta),
+}
+
+impl TemplateArg {
+    /// Value of `name` argument
+    fn name(&self) -> Option<String> {
+        match self {
+            TemplateArg::Meta(syn::NestedMeta::Meta(syn::Meta::NameValue(
+                syn::MetaNameValue {
+                    path,
+                    lit: syn::Lit::Str(s),
+                    ..
+                },
+            ))) if path.is_ident("name") => Some(s.value()),
+            _ => None,
+        }
+    }
+}
+
+impl Parse for TemplateArg {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        if input.peek(syn::Ident) && input.peek2(syn::Token![=]) && input.peek3(syn::token::Paren) {
+            let ident: syn::Ident = input.fork().parse()?;
+            if ident == "delimiters" {
+                input.parse::<syn::Ident>()?;
+                input.parse::<syn::Token![=]>()?;
+                return input.parse().map(TemplateArg::Delimiters);
+            }
+        }
+
+        input.parse().map(TemplateArg::Meta)
+    }
 }
 
 impl<'a, 'b> Visit<'a> for StructBuilder<'b> {
     fn visit_field(&mut self, e: &'a syn::Field) {
+        for attr in e.attrs.iter().filter(|a| a.path.is_ident("template")) {
+            match attr.parse_args::<syn::Path>() {
+                Ok(p) if p.is_ident("safe") => self.safe_fields.push(match &e.ident {
+                    Some(ident) => ident.to_string(),
+                    None => format!("_{}", self.fields.len()),
+                }),
+                _ => self.err.push(Error::new_spanned(
+                    attr,
+                    "field attribute must be `#[template(safe)]`",
+                )),
+            }
+        }
         self.fields.push(e.clone());
     }
 
+    fn visit_meta(&mut self, i: &'a syn::Meta) {
+        match i {
+            syn::Meta::Path(p) if p.is_ident("strict_safe") => self.strict_safe = Some(true),
+            syn::Meta::Path(p) if p.is_ident("strict") => self.strict = Some(true),
+            syn::Meta::Path(p) => self.err.push(Error::new_spanned(
+                p,
+                format!("invalid attribute '{}'", p.to_token_stream()),
+            )),
+            _ => syn::visit::visit_meta(self, i),
+        }
+    }
+
     fn visit_meta_name_value(&mut self, i: &'a syn::MetaNameValue) {
         let syn::MetaNameValue { path, lit, .. } = i;
         if path.is_ident("path") {
@@ -151,8 +584,10 @@ impl<'a, 'b> Visit<'a> for StructBuilder<'b> {
                     ))
                 }
                 let mut path = PathBuf::from(s.value());
-                if let Some(ext) = path.extension() {
-                    if ext != DEFAULT_EXTENSION {
+                if let Some(ext) = path.extension().and_then(|ext| ext.to_str()) {
+                    if Format::from_extension(ext).is_some() {
+                        path = path.with_extension(format!("{ext}.{DEFAULT_EXTENSION}"));
+                    } else if ext != DEFAULT_EXTENSION {
                         self.err.push(Error::new_spanned(
                             i,
                             "Default extension for yarte templates is `.hbs`",
@@ -205,6 +640,74 @@ impl<'a, 'b> Visit<'a> for StructBuilder<'b> {
                     "attribute 'print' must be string literal",
                 ));
             }
+        } else if path.is_ident("name") {
+            match lit {
+                syn::Lit::Str(s) if syn::parse_str::<syn::Ident>(&s.value()).is_ok() => {
+                    self.name = Some(s.value());
+                }
+                _ => self.err.push(Error::new_spanned(
+                    i,
+                    "attribute 'name' must be a string literal with an identifier",
+                )),
+            }
+        } else if path.is_ident("format") {
+            if let syn::Lit::Str(ref s) = lit {
+                match s.value().try_into() {
+                    Ok(f) => self.format = Some(f),
+                    Err(e) => {
+                        self.err.push(Error::new_spanned(i, e));
+                    }
+                }
+            } else {
+                self.err.push(Error::new_spanned(
+                    i,
+                    "attribute 'format' must be string literal",
+                ));
+            }
+        } else if path.is_ident("escape") {
+            match lit {
+                syn::Lit::Str(s) => match s.parse() {
+                    Ok(p) => self.escaper = Some(p),
+                    Err(_) => self.err.push(Error::new_spanned(
+                        i,
+                        "attribute 'escape' must be the path of an escaper type",
+                    )),
+                },
+                _ => self.err.push(Error::new_spanned(
+                    i,
+                    "attribute 'escape' must be string literal",
+                )),
+            }
+        } else if path.is_ident("error") {
+            match lit {
+                syn::Lit::Str(s) => match s.parse() {
+                    Ok(p) => self.error = Some(p),
+                    Err(_) => self.err.push(Error::new_spanned(
+                        i,
+                        "attribute 'error' must be the path of an error type",
+                    )),
+                },
+                _ => self.err.push(Error::new_spanned(
+                    i,
+                    "attribute 'error' must be string literal",
+                )),
+            }
+        } else if path.is_ident("inline_partials") {
+            match lit {
+                syn::Lit::Bool(b) => self.inline_partials = Some(b.value),
+                _ => self.err.push(Error::new_spanned(
+                    i,
+                    "attribute 'inline_partials' must be bool literal",
+                )),
+            }
+        } else if path.is_ident("csp_nonce") {
+            match lit {
+                syn::Lit::Str(s) => self.csp_nonce = Some(s.clone()),
+                _ => self.err.push(Error::new_spanned(
+                    i,
+                    "attribute 'csp_nonce' must be string literal",
+                )),
+            }
         } else if path.is_ident("script") {
             if let syn::Lit::Str(ref s) = lit {
                 self.script = Some(s.value());
@@ -232,11 +735,12 @@ impl<'a, 'b> Visit<'a> for StructBuilder<'b> {
     }
 }
 
-#[derive(PartialEq, Eq, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum Print {
     All,
     Ast,
     Code,
+    Size,
     None,
 }
 
@@ -247,18 +751,99 @@ impl TryFrom<String> for Print {
             "all" => Ok(Print::All),
             "ast" => Ok(Print::Ast),
             "code" => Ok(Print::Code),
+            "size" => Ok(Print::Size),
             v => Err(format!("invalid value for print attribute: {v}")),
         }
     }
 }
 
+/// Output format of a template, selects its escaper
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Format {
+    /// Escaped html
+    Html,
+    /// Text without escape
+    Text,
+    /// Escaped xml
+    Xml,
+    /// Escaped json string content
+    Json,
+    /// Escaped LaTeX text
+    Latex,
+    /// Escaped csv quoted fields
+    Csv,
+    /// Escaped shell single quoted arguments
+    Shell,
+}
+
+impl Format {
+    /// Format of an inner template extension, like `html` in `page.html.hbs`
+    pub fn from_extension(ext: &str) -> Option<Format> {
+        match ext {
+            "html" | "htm" => Some(Format::Html),
+            "txt" => Some(Format::Text),
+            "xml" | "svg" | "rss" | "atom" => Some(Format::Xml),
+            "json" => Some(Format::Json),
+            "tex" => Some(Format::Latex),
+            "csv" => Some(Format::Csv),
+            "sh" => Some(Format::Shell),
+            _ => None,
+        }
+    }
+
+    /// Format of the inner extension of template `path`
+    pub fn from_path(path: &Path) -> Option<Format> {
+        Path::new(path.file_stem()?)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(Format::from_extension)
+    }
+
+    /// Extension of the format output
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Html => "html",
+            Format::Text => "txt",
+            Format::Xml => "xml",
+            Format::Json => "json",
+            Format::Latex => "tex",
+            Format::Csv => "csv",
+            Format::Shell => "sh",
+        }
+    }
+}
+
+impl TryFrom<String> for Format {
+    type Error = String;
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        match s.as_ref() {
+            "html" => Ok(Format::Html),
+            "text" => Ok(Format::Text),
+            "xml" => Ok(Format::Xml),
+            "json" => Ok(Format::Json),
+            "latex" => Ok(Format::Latex),
+            "csv" => Ok(Format::Csv),
+            "shell" => Ok(Format::Shell),
+            v => Err(format!("invalid value for format attribute: {v}")),
+        }
+    }
+}
+
 static DEFAULT_EXTENSION: &str = "hbs";
+static FRONT_MATTER: &str = "yarte:";
 
 #[cfg(test)]
 mod test {
     use super::*;
     use syn::parse_str;
 
+    /// Asserts the derive of `src` fails with `message`
+    fn assert_err(src: &str, message: &str) {
+        let i = parse_str::<syn::DeriveInput>(src).unwrap();
+        let err = visit_derive(&i, &Config::new("")).unwrap_err().to_string();
+        assert!(err.contains(&format!("{message:?}")), "{src}: {err}");
+    }
+
     #[test]
     #[should_panic]
     fn test_panic() {
@@ -287,4 +872,362 @@ mod test {
         assert_eq!(s.path, config.get_dir().join(PathBuf::from("Test.hbs")));
         assert_eq!(s.print, Print::Code);
     }
+
+    #[test]
+    fn test_front_matter() {
+        let src = "{{!-- yarte:\n #[derive(TemplateText)]\n struct Page { title: String } --!}}\n{{ title }}";
+        let i = front_matter(src, Syntax::default()).unwrap().unwrap();
+        assert_eq!(i.ident, "Page");
+        assert_eq!(i.attrs.len(), 1);
+
+        let i = front_matter("{{! yarte: struct Page(String); !}}", Syntax::default())
+            .unwrap()
+            .unwrap();
+        assert_eq!(i.ident, "Page");
+
+        assert!(front_matter("{{!-- Page --!}}{{ title }}", Syntax::default()).is_none());
+        assert!(front_matter(
+            "{{ title }}{{!-- yarte: struct Page; --!}}",
+            Syntax::default()
+        )
+        .is_none());
+        assert!(front_matter("{{!-- yarte: struct --!}}", Syntax::default())
+            .unwrap()
+            .is_err());
+
+        let syntax = Syntax::new("[[", "]]").unwrap();
+        let i = front_matter("[[!-- yarte: struct Page; --!]]", syntax)
+            .unwrap()
+            .unwrap();
+        assert_eq!(i.ident, "Page");
+        assert!(front_matter("{{!-- yarte: struct Page; --!}}", syntax).is_none());
+    }
+
+    #[test]
+    fn test_delimiters() {
+        let src = r#"
+            #[derive(Template)]
+            #[template(src = "", delimiters = ("[[", "]]"))]
+            struct Test;
+        "#;
+        let i = parse_str::<syn::DeriveInput>(src).unwrap();
+        let config = Config::new("");
+        let s = visit_derive(&i, &config).unwrap();
+        assert_eq!(s.syntax, Syntax::new("[[", "]]").unwrap());
+
+        let i = parse_str::<syn::DeriveInput>("#[template(src = \"\")] struct Test;").unwrap();
+        assert_eq!(visit_derive(&i, &config).unwrap().syntax, Syntax::default());
+    }
+
+    #[test]
+    fn test_formats() {
+        let src = r#"
+            #[template(src = "", print = "code")]
+            #[template(name = "html", src = "")]
+            #[template(format = "text", name = "text", src = "")]
+            struct Mail;
+        "#;
+        let i = parse_str::<syn::DeriveInput>(src).unwrap();
+        let config = Config::new("");
+        let s = visit_derive(&i, &config).unwrap();
+
+        assert!(s.has_template());
+        assert_eq!(s.formats.len(), 2);
+        assert_eq!(s.formats[0].name.as_deref(), Some("html"));
+        assert_eq!(s.formats[0].options.format, None);
+        assert_eq!(s.formats[0].print, Print::Code);
+        assert_eq!(s.formats[0].path, config.get_dir().join("Mail/html.hbs"));
+        assert_eq!(s.formats[1].options.format, Some(Format::Text));
+        assert_eq!(s.formats[1].path, config.get_dir().join("Mail/text.hbs"));
+
+        let i =
+            parse_str::<syn::DeriveInput>("#[template(name = \"html\", src = \"\")] struct Mail;")
+                .unwrap();
+        assert!(!visit_derive(&i, &config).unwrap().has_template());
+
+        assert_err(
+            "#[template(name = \"a\", src = \"\")] #[template(name = \"a\", src = \"\")] struct Mail;",
+            "duplicate template name 'a'",
+        );
+        assert_err(
+            "#[template(name = \"a-b\", src = \"\")] struct Mail;",
+            "attribute 'name' must be a string literal with an identifier",
+        );
+        assert_err(
+            "#[template(name = \"a\", format = \"yaml\", src = \"\")] struct Mail;",
+            "invalid value for format attribute: yaml",
+        );
+    }
+
+    #[test]
+    fn test_format_extension() {
+        assert_eq!(
+            Format::from_path(Path::new("a/feed.xml.hbs")),
+            Some(Format::Xml)
+        );
+        assert_eq!(
+            Format::from_path(Path::new("mail.txt.hbs")),
+            Some(Format::Text)
+        );
+        assert_eq!(Format::from_path(Path::new("page.hbs")), None);
+        assert_eq!(Format::from_path(Path::new("page.css.hbs")), None);
+
+        let src = r#"
+            #[template(src = "", format = "json")]
+            struct Data;
+        "#;
+        let i = parse_str::<syn::DeriveInput>(src).unwrap();
+        let mut s = visit_derive(&i, &Config::new("")).unwrap();
+        assert_eq!(s.options.format, Some(Format::Json));
+        s.default_format(Format::Html);
+        assert_eq!(s.options.format, Some(Format::Json));
+
+        assert_err(
+            r#"
+            enum Page {
+                #[template(src = "", format = "xml")]
+                A,
+                #[template(src = "", format = "html")]
+                B,
+            }
+            "#,
+            "enum variants need templates with the same format, escaper and error",
+        );
+    }
+
+    #[test]
+    fn test_enum() {
+        let src = r#"
+            #[derive(Template)]
+            #[template(print = "code")]
+            enum Page {
+                #[template(src = "Loading")]
+                Loading,
+                #[template(src = "{{ items }}", delimiters = ("[[", "]]"))]
+                Ready { items: Vec<usize> },
+                #[template(src = "{{ _0 }}", print = "ast")]
+                Error(String),
+            }
+        "#;
+        let i = parse_str::<syn::DeriveInput>(src).unwrap();
+        let config = Config::new("");
+        let s = visit_derive(&i, &config).unwrap();
+
+        assert_eq!(s.variants.len(), 3);
+        let [loading, ready, error] = &s.variants[..] else {
+            unreachable!()
+        };
+        assert_eq!(loading.src, "Loading");
+        assert_eq!(loading.path, config.get_dir().join("Page/Loading.hbs"));
+        assert_eq!(loading.print, Print::Code);
+        assert_eq!(ready.syntax, Syntax::new("[[", "]]").unwrap());
+        assert_eq!(ready.fields.len(), 1);
+        assert_eq!(error.print, Print::Ast);
+
+        let bodies = vec![quote!(a), quote!(b), quote!(c)];
+        assert_eq!(
+            s.implement_match(quote!(self), &bodies).to_string(),
+            quote!(match self {
+                #[allow(unused_variables)]
+                Self::Loading => {
+                    a
+                }
+                #[allow(unused_variables)]
+                Self::Ready { items } => {
+                    b
+                }
+                #[allow(unused_variables)]
+                Self::Error(_0) => {
+                    c
+                }
+            })
+            .to_string()
+        );
+
+        assert_err(
+            "#[template(src = \"\")] enum Page { Loading }",
+            "enum templates are specified on each variant",
+        );
+    }
+
+    #[test]
+    fn test_escape() {
+        let src = r#"
+            #[template(src = "", escape = "crate::Quotes")]
+            struct Data;
+        "#;
+        let i = parse_str::<syn::DeriveInput>(src).unwrap();
+        let s = visit_derive(&i, &Config::new("")).unwrap();
+        assert_eq!(s.options.escaper, Some(parse_str("crate::Quotes").unwrap()));
+
+        assert_eq!(
+            Format::from_path(Path::new("report.tex.hbs")),
+            Some(Format::Latex)
+        );
+        assert_eq!(
+            Format::from_path(Path::new("table.csv.hbs")),
+            Some(Format::Csv)
+        );
+        assert_eq!(
+            Format::from_path(Path::new("run.sh.hbs")),
+            Some(Format::Shell)
+        );
+
+        assert_err(
+            "#[template(src = \"\", escape = \"a b\")] struct Data;",
+            "attribute 'escape' must be the path of an escaper type",
+        );
+        assert_err(
+            "#[template(src = \"\", escape = 1)] struct Data;",
+            "attribute 'escape' must be string literal",
+        );
+        assert_err(
+            "enum Data { #[template(src = \"\", escape = \"crate::A\")] A, #[template(src = \"\")] B }",
+            "enum variants need templates with the same format, escaper and error",
+        );
+    }
+
+    #[test]
+    fn test_safe() {
+        let src = r#"
+            #[template(src = "", strict_safe)]
+            struct Post {
+                #[template(safe)]
+                html: String,
+                text: String,
+            }
+        "#;
+        let i = parse_str::<syn::DeriveInput>(src).unwrap();
+        let s = visit_derive(&i, &Config::new("")).unwrap();
+        assert!(s.options.strict_safe);
+        assert_eq!(s.options.safe_fields, ["html"]);
+
+        let src = r#"
+            #[template(strict_safe)]
+            enum Card {
+                #[template(src = "")]
+                Html(String, #[template(safe)] String),
+            }
+        "#;
+        let i = parse_str::<syn::DeriveInput>(src).unwrap();
+        let s = visit_derive(&i, &Config::new("")).unwrap();
+        assert!(s.variants[0].options.strict_safe);
+        assert_eq!(s.variants[0].options.safe_fields, ["_1"]);
+
+        assert_err(
+            "#[template(src = \"\", strict_html)] struct Post;",
+            "invalid attribute 'strict_html'",
+        );
+        assert_err(
+            "#[template(src = \"\")] struct Post { #[template(raw)] a: String }",
+            "field attribute must be `#[template(safe)]`",
+        );
+    }
+
+    #[test]
+    fn test_csp_nonce() {
+        let src = r#"
+            #[template(csp_nonce = "nonce")]
+            enum Page {
+                #[template(src = "")]
+                Home { nonce: String },
+                #[template(src = "", csp_nonce = "_0")]
+                Error(String),
+            }
+        "#;
+        let i = parse_str::<syn::DeriveInput>(src).unwrap();
+        let s = visit_derive(&i, &Config::new("")).unwrap();
+        assert_eq!(s.variants[0].options.csp_nonce.as_deref(), Some("nonce"));
+        assert_eq!(s.variants[1].options.csp_nonce.as_deref(), Some("_0"));
+
+        assert_err(
+            "#[template(src = \"\", csp_nonce = \"nonce\")] struct Page { id: String }",
+            "attribute 'csp_nonce' field 'nonce' not found",
+        );
+        assert_err(
+            "#[template(src = \"\", csp_nonce = \"_1\")] struct Page(String);",
+            "attribute 'csp_nonce' field '_1' not found",
+        );
+        assert_err(
+            "#[template(src = \"\", csp_nonce = 1)] struct Page(String);",
+            "attribute 'csp_nonce' must be string literal",
+        );
+    }
+
+    #[test]
+    fn test_strict() {
+        let src = r#"
+            #[template(src = "", strict)]
+            #[template(name = "text", src = "")]
+            struct Page;
+        "#;
+        let i = parse_str::<syn::DeriveInput>(src).unwrap();
+        let s = visit_derive(&i, &Config::new("")).unwrap();
+        assert!(s.options.strict);
+        assert!(s.formats[0].options.strict);
+
+        let i = parse_str::<syn::DeriveInput>("#[template(src = \"\")] struct Page;").unwrap();
+        assert!(
+            visit_derive(&i, &Config::new("[main]\nstrict = true"))
+                .unwrap()
+                .options
+                .strict
+        );
+    }
+
+    #[test]
+    fn test_error() {
+        let src = r#"
+            #[template(src = "", error = "crate::Error")]
+            struct Page;
+        "#;
+        let i = parse_str::<syn::DeriveInput>(src).unwrap();
+        let s = visit_derive(&i, &Config::new("")).unwrap();
+        assert_eq!(s.options.error, Some(parse_str("crate::Error").unwrap()));
+
+        assert_err(
+            "#[template(name = \"html\", src = \"\", error = \"Error\")] struct Page;",
+            "named templates don't support attribute 'error'",
+        );
+        assert_err(
+            "#[template(src = \"\", error = 1)] struct Page;",
+            "attribute 'error' must be string literal",
+        );
+        assert_err(
+            "enum Page { #[template(src = \"\", error = \"E\")] A, #[template(src = \"\")] B }",
+            "enum variants need templates with the same format, escaper and error",
+        );
+    }
+
+    #[test]
+    fn test_inline_partials() {
+        let src = r#"
+            #[template(src = "", inline_partials = false)]
+            #[template(name = "text", src = "")]
+            struct Page;
+        "#;
+        let i = parse_str::<syn::DeriveInput>(src).unwrap();
+        let s = visit_derive(&i, &Config::new("")).unwrap();
+        assert!(!s.options.inline_partials);
+        assert!(!s.formats[0].options.inline_partials);
+
+        let i = parse_str::<syn::DeriveInput>("#[template(src = \"\")] struct Page;").unwrap();
+        assert!(
+            visit_derive(&i, &Config::new(""))
+                .unwrap()
+                .options
+                .inline_partials
+        );
+        assert!(
+            !visit_derive(&i, &Config::new("[main]\ninline_partials = false"))
+                .unwrap()
+                .options
+                .inline_partials
+        );
+
+        assert_err(
+            "#[template(src = \"\", inline_partials = \"false\")] struct Page;",
+            "attribute 'inline_partials' must be bool literal",
+        );
+    }
 }
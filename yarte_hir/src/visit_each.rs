@@ -1,13 +1,13 @@
 #![allow(clippy::cognitive_complexity)]
 
-use std::{mem, path::PathBuf};
+use std::{collections::BTreeSet, mem, path::PathBuf};
 
 use syn::visit::Visit;
 
 use yarte_helpers::config::Config;
 use yarte_parser::{Helper, Node, Partial, PartialBlock, SNode};
 
-use super::{is_super, Context, LoweringContext};
+use super::{is_super, outline, Context, HIROptions, LoweringContext};
 use crate::{
     error::{GError, GResult},
     Struct,
@@ -25,6 +25,8 @@ pub struct FindEach<'a> {
     c: &'a Config,
     ctx: Context<'a>,
     on_path: PathBuf,
+    opt: HIROptions,
+    cyclic: &'a BTreeSet<PathBuf>,
     block: Vec<(&'a [SNode<'a>], FindEach<'a>)>,
     on_: usize,
     recursion: usize,
@@ -39,6 +41,8 @@ impl<'a> From<&LoweringContext<'a>> for FindEach<'a> {
             s: g.s,
             ctx: g.ctx,
             on_path: g.on_path.clone(),
+            opt: g.opt,
+            cyclic: g.cyclic,
             block: g.block.iter().map(|(_, x, g)| (*x, g.into())).collect(),
             on_: 0,
             recursion: g.recursion,
@@ -59,14 +63,16 @@ impl<'a> FindEach<'a> {
     // TODO: #39
     pub fn find(&mut self, nodes: &'a [SNode]) -> GResult<bool> {
         macro_rules! partial {
-            ($path:ident, $expr:ident) => {{
+            ($path:ident, $expr:ident, $block:expr) => {{
                 self.recursion += 1;
                 if self.s.recursion_limit <= self.recursion {
-                    self.on_error.replace(GError::RecursionLimit);
+                    self.on_error
+                        .replace(GError::RecursionLimit($path.t().to_string()));
                     break;
                 }
 
                 let p = self.c.resolve_partial(&self.on_path, $path.t());
+                let outlined = outline(self.s, &self.opt, self.cyclic, &p, $expr, $block);
                 let nodes = self.ctx.get(&p).unwrap();
                 let expr = $expr.t();
                 if !expr.is_empty() {
@@ -83,6 +89,11 @@ impl<'a> FindEach<'a> {
                         break;
                     }
                 }
+                // Out of line partials find their own loop variables
+                if outlined {
+                    self.recursion -= 1;
+                    continue;
+                }
                 (mem::replace(&mut self.on_path, p), nodes)
             }};
         }
@@ -143,7 +154,7 @@ impl<'a> FindEach<'a> {
                     }
                 }
                 Node::Partial(Partial(_, path, expr)) => {
-                    let (parent, nodes) = partial!(path, expr);
+                    let (parent, nodes) = partial!(path, expr, false);
 
                     self.find(nodes)?;
 
@@ -151,7 +162,7 @@ impl<'a> FindEach<'a> {
                     self.recursion -= 1;
                 }
                 Node::PartialBlock(PartialBlock(_, path, expr, block)) => {
-                    let (parent, nodes) = partial!(path, expr);
+                    let (parent, nodes) = partial!(path, expr, true);
 
                     self.block.push((block, self.clone()));
                     self.find(nodes)?;
@@ -0,0 +1,53 @@
+//! `?` expressions of templates with `error` attribute
+//!
+//! Locations are collected before the path resolution, which changes the expression spans,
+//! and each `?` is replaced after it in the same order
+use std::vec::IntoIter;
+
+use proc_macro2::TokenStream;
+use syn::{
+    parse_quote,
+    spanned::Spanned,
+    visit::{self, Visit},
+    visit_mut::{self, VisitMut},
+};
+
+/// Spans of `?` expressions, closures have their own ones
+#[derive(Default)]
+pub(super) struct TryCollect(pub(super) Vec<proc_macro2::Span>);
+
+impl<'a> Visit<'a> for TryCollect {
+    fn visit_expr_try(&mut self, i: &'a syn::ExprTry) {
+        self.0.push(i.span());
+        visit::visit_expr_try(self, i);
+    }
+
+    fn visit_expr_closure(&mut self, _: &'a syn::ExprClosure) {}
+
+    fn visit_expr_async(&mut self, _: &'a syn::ExprAsync) {}
+}
+
+/// Replaces `?` expressions with a `match` returning the error at its location
+pub(super) struct TryRewrite(pub(super) IntoIter<TokenStream>);
+
+impl VisitMut for TryRewrite {
+    fn visit_expr_mut(&mut self, expr: &mut syn::Expr) {
+        match expr {
+            syn::Expr::Try(i) => {
+                let at = self.0.next().expect("collected `?` location");
+                self.visit_expr_mut(&mut i.expr);
+                let inner = &i.expr;
+                *expr = parse_quote!(match #inner {
+                    ::std::result::Result::Ok(__yarte_ok) => __yarte_ok,
+                    ::std::result::Result::Err(__yarte_err) => {
+                        return ::std::result::Result::Err(
+                            __yarte_try.fail(::std::convert::From::from(__yarte_err), #at),
+                        )
+                    }
+                });
+            }
+            syn::Expr::Closure(_) | syn::Expr::Async(_) => (),
+            _ => visit_mut::visit_expr_mut(self, expr),
+        }
+    }
+}
@@ -6,7 +6,7 @@ use syn::{
 };
 
 use super::LoweringContext;
-use crate::error::GError;
+use crate::{error::GError, validator};
 
 impl<'a> VisitMut for LoweringContext<'a> {
     fn visit_arm_mut(
@@ -76,6 +76,16 @@ impl<'a> VisitMut for LoweringContext<'a> {
     fn visit_expr_call_mut(&mut self, syn::ExprCall { func, args, .. }: &mut syn::ExprCall) {
         if let Some(ident) = self.scp.get_by(&quote!(#func).to_string()) {
             *func = Box::new(ident.clone());
+        } else if self.s.options.strict {
+            let path = validator::function_path(func);
+            if !path
+                .as_ref()
+                .is_some_and(|p| validator::strict_function(self.c, p))
+            {
+                let path = path.unwrap_or_else(|| quote!(#func).to_string());
+                self.buf_err
+                    .push((GError::StrictFunction(path), func.span()));
+            }
         }
         visit_punctuated!(self, args, visit_expr_mut);
     }
@@ -102,9 +112,17 @@ impl<'a> VisitMut for LoweringContext<'a> {
     fn visit_expr_for_loop_mut(
         &mut self,
         syn::ExprForLoop {
-            pat, expr, body, ..
+            for_token,
+            pat,
+            expr,
+            body,
+            ..
         }: &mut syn::ExprForLoop,
     ) {
+        if self.s.options.strict {
+            self.buf_err.push((GError::StrictLoop, for_token.span));
+        }
+
         self.scp.push_scope(vec![]);
         self.visit_pat_mut(pat);
         let last = self.scp.pops();
@@ -135,6 +153,55 @@ impl<'a> VisitMut for LoweringContext<'a> {
         };
     }
 
+    fn visit_expr_loop_mut(&mut self, i: &mut syn::ExprLoop) {
+        if self.s.options.strict {
+            self.buf_err.push((GError::StrictLoop, i.loop_token.span));
+        }
+        visit_mut::visit_expr_loop_mut(self, i);
+    }
+
+    fn visit_expr_macro_mut(&mut self, i: &mut syn::ExprMacro) {
+        if self.s.options.strict {
+            let name = i.mac.path.segments.last().unwrap().ident.to_string();
+            if validator::strict_macro(self.c, &name) {
+                validator::strict_tokens(self.c, i.mac.tokens.clone(), &mut self.buf_err);
+            } else {
+                self.buf_err
+                    .push((GError::StrictMacro(name), i.mac.path.span()));
+            }
+        }
+    }
+
+    fn visit_expr_method_call_mut(&mut self, i: &mut syn::ExprMethodCall) {
+        if self.s.options.strict && !validator::strict_method(self.c, &i.method.to_string()) {
+            self.buf_err
+                .push((GError::StrictMethod(i.method.to_string()), i.method.span()));
+        }
+        visit_mut::visit_expr_method_call_mut(self, i);
+    }
+
+    fn visit_expr_reference_mut(&mut self, i: &mut syn::ExprReference) {
+        if let (true, Some(mutability)) = (self.s.options.strict, i.mutability) {
+            self.buf_err.push((GError::StrictMutRef, mutability.span));
+        }
+        visit_mut::visit_expr_reference_mut(self, i);
+    }
+
+    fn visit_expr_unsafe_mut(&mut self, i: &mut syn::ExprUnsafe) {
+        if self.s.options.strict {
+            self.buf_err
+                .push((GError::StrictUnsafe, i.unsafe_token.span));
+        }
+        visit_mut::visit_expr_unsafe_mut(self, i);
+    }
+
+    fn visit_expr_while_mut(&mut self, i: &mut syn::ExprWhile) {
+        if self.s.options.strict {
+            self.buf_err.push((GError::StrictLoop, i.while_token.span));
+        }
+        visit_mut::visit_expr_while_mut(self, i);
+    }
+
     fn visit_expr_let_mut(&mut self, syn::ExprLet { expr, pat, .. }: &mut syn::ExprLet) {
         self.scp.push_scope(vec![]);
         self.visit_pat_mut(pat);
@@ -162,7 +229,19 @@ impl<'a> VisitMut for LoweringContext<'a> {
         self.scp.extend(scope);
     }
 
-    fn visit_pat_ident_mut(&mut self, syn::PatIdent { ident, subpat, .. }: &mut syn::PatIdent) {
+    fn visit_pat_ident_mut(
+        &mut self,
+        syn::PatIdent {
+            by_ref,
+            mutability,
+            ident,
+            subpat,
+            ..
+        }: &mut syn::PatIdent,
+    ) {
+        if let (true, Some(_), Some(mutability)) = (self.s.options.strict, by_ref, mutability) {
+            self.buf_err.push((GError::StrictMutRef, mutability.span));
+        }
         if let Some((at, pat)) = subpat {
             self.buf_err
                 .push((GError::NotAvailable, at.span().join(pat.span()).unwrap()));
@@ -11,6 +11,7 @@ mod arm;
 mod expr_list;
 mod parse;
 mod stmt_local;
+mod tokens;
 
 use self::arm::Arm;
 
@@ -29,6 +30,13 @@ pub struct Local(#[serde(deserialize_with = "de_local")] syn::Local);
 #[cfg(not(feature = "test"))]
 pub use syn::Local;
 
+#[cfg(feature = "test")]
+impl From<Local> for syn::Local {
+    fn from(local: Local) -> Self {
+        local.0
+    }
+}
+
 #[cfg(feature = "test")]
 fn de_local<'de, D>(deserializer: D) -> Result<syn::Local, D::Error>
 where
@@ -57,6 +65,13 @@ pub struct Expr(#[serde(deserialize_with = "de_expr")] syn::Expr);
 #[cfg(not(feature = "test"))]
 pub type Expr = syn::Expr;
 
+#[cfg(feature = "test")]
+impl From<Expr> for syn::Expr {
+    fn from(expr: Expr) -> Self {
+        expr.0
+    }
+}
+
 #[cfg(feature = "test")]
 fn de_expr<'de, D>(deserializer: D) -> Result<syn::Expr, D::Error>
 where
@@ -81,7 +96,7 @@ pub type SStr<'a> = S<&'a str>;
 pub type SVExpr = S<Vec<Expr>>;
 
 macro_rules! ki {
-    ($ty:ident: $($cname:ident: $cty:ty)+; $($method:ident -> $ret:ty)+) => {
+    ($ty:ident: $($cname:ident: $cty:ty)+; $($method:ident($($arg:ty),*) -> $ret:ty)+) => {
         pub trait $ty<'a>: Sized + 'a {
             type Error: KiError;
             $(
@@ -89,7 +104,7 @@ macro_rules! ki {
             )+
             $(
             #[inline]
-            fn $method(_: Cursor<'a>) -> PResult<'a, $ret, Self::Error> {
+            fn $method(_: Cursor<'a>, $(_: $arg),*) -> PResult<'a, $ret, Self::Error> {
                 Err(next!(Self::Error))
             }
             )+
@@ -108,6 +123,34 @@ ki!(
         WS: u8
         WS_AFTER: bool
     ;
-        parse -> Self
-        comment -> &'a str
+        parse() -> Self
+        comment(Delimiters) -> &'a str
+        raw(Delimiters) -> ((Ws, Ws), SStr<'a>)
 );
+
+/// Delimiters of a [`Lexer`], by default the ones declared by its [`Kinder`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Delimiters {
+    pub open: u8,
+    pub close: u8,
+    pub open_expr: u8,
+    pub close_expr: u8,
+    pub open_block: u8,
+    pub close_block: u8,
+    pub ws: u8,
+}
+
+impl Delimiters {
+    /// Delimiters declared by `K`
+    pub fn of<'a, K: Kinder<'a>>() -> Self {
+        Delimiters {
+            open: K::OPEN,
+            close: K::CLOSE,
+            open_expr: K::OPEN_EXPR,
+            close_expr: K::CLOSE_EXPR,
+            open_block: K::OPEN_BLOCK,
+            close_block: K::CLOSE_BLOCK,
+            ws: K::WS,
+        }
+    }
+}
@@ -1,16 +1,17 @@
 use std::fmt::Debug;
 use std::marker::PhantomData;
 
-use syn::parse_str;
+use syn::{parse::Parse, parse2};
 
 use crate::arm::Arm;
 use crate::expr_list::ExprList;
-use crate::{Kinder, SArm, SExpr, SLocal, SStr, SVExpr, StmtLocal, Ws};
+use crate::tokens::token_stream;
+use crate::{Delimiters, Kinder, SArm, SExpr, SLocal, SStr, SVExpr, StmtLocal, Ws};
 
 use yarte_strnom::error::{ErrorMessage, KiError, LexError, Result as PResult};
 use yarte_strnom::pipes::{is_some, opt};
 use yarte_strnom::source_map::{Span, S};
-use yarte_strnom::{_while, do_parse, get_chars, is_ws, tac, tag, ws, Cursor};
+use yarte_strnom::{_while, do_parse, is_ws, tac, tag, ws, Cursor};
 
 pub trait Ki<'a>: Kinder<'a> + Debug + PartialEq + Clone {}
 
@@ -19,12 +20,19 @@ impl<'a, T: Kinder<'a> + Debug + PartialEq + Clone> Ki<'a> for T {}
 /// Lexer for `K` tokens
 pub struct Lexer<'a, K: Ki<'a>, S: Sink<'a, K>> {
     sink: S,
+    delimiters: Delimiters,
+    /// Errors of a recovering lexer
+    errors: Option<Vec<ErrorMessage<K::Error>>>,
+    /// End of the last literal
+    lit_end: u32,
     _p: PhantomData<&'a K>,
 }
 
-pub type LexResult<E, O = ()> = Result<O, E>;
+pub type LexResult<E, O = ()> = Result<O, ErrorMessage<E>>;
 
 /// Consume tokens within a `K` lexer
+///
+/// Errors are reported at its own span, usually the span of the whole token
 pub trait Sink<'a, K: Ki<'a>>: 'a {
     fn arm(&mut self, ws: Ws, arm: SArm, span: Span) -> LexResult<K::Error>;
     fn arm_kind(&mut self, ws: Ws, kind: K, arm: SArm, span: Span) -> LexResult<K::Error>;
@@ -41,47 +49,96 @@ pub trait Sink<'a, K: Ki<'a>>: 'a {
 
     fn local(&mut self, ws: Ws, local: SLocal, span: Span) -> LexResult<K::Error>;
 
+    fn raw(
+        &mut self,
+        ws: (Ws, Ws),
+        left: &'a str,
+        src: SStr<'a>,
+        right: &'a str,
+        span: Span,
+    ) -> LexResult<K::Error>;
+
     fn safe(&mut self, ws: Ws, expr: SExpr, span: Span) -> LexResult<K::Error>;
 
+    /// Region skipped after an error with the kind of its tag, when it's known
+    fn error(&mut self, kind: Option<K>, span: Span);
+
     fn end(&mut self) -> LexResult<K::Error>;
 }
 
+#[inline]
+fn fail<E: KiError>(e: ErrorMessage<E>) -> LexError<E> {
+    LexError::Fail(e.message, e.span)
+}
+
 macro_rules! comment {
     ($_self:ident, $K:ty, $cur:expr, $i:ident, $at:ident, $j:ident) => {
-        match <$K>::comment($cur) {
+        match <$K>::comment($cur, $_self.delimiters) {
             Ok((c, s)) => {
                 $_self.eat_lit($i, $at + $j);
                 let span = Span::from_cursor($i.adv($at + $j), c);
-                $_self
-                    .sink
-                    .comment(s, span)
-                    .map_err(|e| LexError::Fail(e, span))?;
+                if let Err(e) = $_self.sink.comment(s, span) {
+                    $_self.recover(e, $i, $at + $j, Some(c), None)?;
+                }
                 $i = c;
                 $at = 0;
                 continue;
             }
             Err(LexError::Next(..)) => (),
-            Err(e) => break Err(e.into()),
+            Err(e) => {
+                $i = $_self.recover(e.into(), $i, $at + $j, None, None)?;
+                $at = 0;
+                continue;
+            }
+        }
+    };
+}
+
+macro_rules! raw {
+    ($_self:ident, $K:ty, $cur:expr, $i:ident, $at:ident, $j:ident) => {
+        match <$K>::raw($cur, $_self.delimiters) {
+            Ok((c, (ws, S(src, ins)))) => {
+                $_self.eat_lit($i, $at + $j);
+                let span = Span::from_cursor($i.adv($at + $j), c);
+                let (l, s, r) = trim(src);
+                let lo = ins.lo + l.len() as u32;
+                let s = S(s, Span::new(lo, lo + s.len() as u32));
+                if let Err(e) = $_self.sink.raw(ws, l, s, r, span) {
+                    $_self.recover(e, $i, $at + $j, Some(c), None)?;
+                }
+                $i = c;
+                $at = 0;
+                continue;
+            }
+            Err(LexError::Next(..)) => (),
+            Err(e) => {
+                $i = $_self.recover(e.into(), $i, $at + $j, None, None)?;
+                $at = 0;
+                continue;
+            }
         }
     };
 }
 
 macro_rules! safe {
     ($_self:ident, $K:ty, $cur:expr, $i:ident, $at:ident, $j:ident) => {
-        match safe::<$K>($cur) {
+        match safe::<$K>($cur, $_self.delimiters) {
             Ok((c, (ws, expr))) => {
                 $_self.eat_lit($i, $at + $j);
                 let span = Span::from_cursor($i.adv($at + $j), c);
-                $_self
-                    .sink
-                    .safe(ws, expr, span)
-                    .map_err(|e| LexError::Fail(e, span))?;
+                if let Err(e) = $_self.sink.safe(ws, expr, span) {
+                    $_self.recover(e, $i, $at + $j, Some(c), None)?;
+                }
                 $i = c;
                 $at = 0;
                 continue;
             }
             Err(LexError::Next(..)) => (),
-            Err(e) => break Err(e.into()),
+            Err(e) => {
+                $i = $_self.recover(e.into(), $i, $at + $j, None, None)?;
+                $at = 0;
+                continue;
+            }
         }
     };
 }
@@ -89,61 +146,122 @@ macro_rules! safe {
 impl<'a, K: Ki<'a>, Si: Sink<'a, K>> Lexer<'a, K, Si> {
     /// Consume a sink in a new Lexer
     pub fn new(sink: Si) -> Lexer<'a, K, Si> {
+        Self::with_delimiters(sink, Delimiters::of::<K>())
+    }
+
+    /// Consume a sink in a new Lexer with custom delimiters
+    pub fn with_delimiters(sink: Si, delimiters: Delimiters) -> Lexer<'a, K, Si> {
         Lexer {
             sink,
+            delimiters,
+            errors: None,
+            lit_end: 0,
             _p: PhantomData,
         }
     }
 
     /// Consume cursor and lexer returning `Sink` result
-    pub fn feed(mut self, mut i: Cursor<'a>) -> Result<Si, ErrorMessage<K::Error>> {
+    pub fn feed(mut self, i: Cursor<'a>) -> Result<Si, ErrorMessage<K::Error>> {
+        self.lex(i).map(|_| self.sink)
+    }
+
+    /// Consume cursor and lexer returning `Sink` and every error
+    ///
+    /// After an error lexing continues after the tag or at the next tag when it isn't closed,
+    /// the skipped region is reported to [`Sink::error`]
+    pub fn feed_recover(mut self, i: Cursor<'a>) -> (Si, Vec<ErrorMessage<K::Error>>) {
+        self.errors = Some(vec![]);
+        let _ = self.lex(i);
+        (self.sink, self.errors.unwrap_or_default())
+    }
+
+    fn lex(&mut self, mut i: Cursor<'a>) -> Result<(), ErrorMessage<K::Error>> {
+        let d = self.delimiters;
         let mut at = 0;
         loop {
-            if let Some(j) = i.adv_find(at, K::OPEN) {
+            if let Some(j) = i.adv_find(at, d.open) {
                 let n = i.rest[at + j + 1..].as_bytes();
-                if 3 < n.len() {
+                if 1 < n.len() {
                     let next = n[0];
 
-                    if K::OPEN_BLOCK == K::OPEN_EXPR && next == K::OPEN_EXPR {
+                    if d.open_block == d.open_expr && next == d.open_expr {
                         let next = i.adv(at + j + 2);
                         comment!(self, K, next, i, at, j);
+                        raw!(self, K, next, i, at, j);
                         safe!(self, K, next, i, at, j);
-                        if let Ok((c, inner)) = end::<K>(next, true) {
+                        if next.next_is(d.open_expr) {
+                            // Unclosed safe, the first open is literal
+                            at += j + 1;
+                            continue;
+                        }
+                        if let Ok((c, inner)) = end::<K::Error>(next, d, true) {
                             self.eat_lit(i, at + j);
                             let span = Span::new(next.off - 2, c.off);
-                            self.eat_expr(inner, span).or_else(|pe| {
+                            let res = self.eat_expr(inner, span).or_else(|pe| {
                                 self.eat_block(inner, span).map_err(|e| match e {
                                     LexError::Next(..) => pe,
                                     e => e,
                                 })
-                            })?;
+                            });
+                            if let Err(e) = res {
+                                let kind = self.kind(inner);
+                                self.recover(e.into(), i, at + j, Some(c), kind)?;
+                            }
                             at = 0;
                             i = c;
+                        } else if let Err(e) = self.uncompleted(next) {
+                            // Without close delimiters the rest of the source can't be lexed
+                            let rest = i.adv(i.len());
+                            i = self.recover(e.into(), i, at + j, Some(rest), None)?;
+                            at = 0;
                         } else {
                             at += j + 1;
                         }
-                    } else if next == K::OPEN_EXPR {
+                    } else if next == d.open_expr {
                         let next = i.adv(at + j + 2);
                         comment!(self, K, next, i, at, j);
+                        raw!(self, K, next, i, at, j);
                         safe!(self, K, next, i, at, j);
-                        if let Ok((c, inner)) = end::<K>(next, true) {
+                        if next.next_is(d.open_expr) {
+                            // Unclosed safe, the first open is literal
+                            at += j + 1;
+                            continue;
+                        }
+                        if let Ok((c, inner)) = end::<K::Error>(next, d, true) {
                             self.eat_lit(i, at + j);
                             let span = Span::new(next.off - 2, c.off);
-                            self.eat_expr(inner, span)?;
+                            if let Err(e) = self.eat_expr(inner, span) {
+                                let kind = self.kind(inner);
+                                self.recover(e.into(), i, at + j, Some(c), kind)?;
+                            }
                             at = 0;
                             i = c;
+                        } else if let Err(e) = self.uncompleted(next) {
+                            // Without close delimiters the rest of the source can't be lexed
+                            let rest = i.adv(i.len());
+                            i = self.recover(e.into(), i, at + j, Some(rest), None)?;
+                            at = 0;
                         } else {
                             at += j + 1;
                         }
-                    } else if next == K::OPEN_BLOCK {
+                    } else if next == d.open_block {
                         let next = i.adv(at + j + 2);
                         comment!(self, K, next, i, at, j);
-                        if let Ok((c, inner)) = end::<K>(next, false) {
+                        raw!(self, K, next, i, at, j);
+                        if let Ok((c, inner)) = end::<K::Error>(next, d, false) {
                             self.eat_lit(i, at + j);
                             let span = Span::new(next.off - 2, c.off);
-                            self.eat_block(inner, span)?;
+                            if let Err(e) = self.eat_block(inner, span) {
+                                let kind = self.kind(inner);
+                                self.recover(e.into(), i, at + j, Some(c), kind)?;
+                            }
                             at = 0;
                             i = c;
+                        } else if let Err(e) = self.uncompleted(next) {
+                            // Without close delimiters the rest of the source can't be lexed
+                            let rest = i.adv(i.len());
+                            i = self.recover(e.into(), i, at + j, Some(rest), None)?;
+                            at = 0;
                         } else {
                             at += j + 1;
                         }
@@ -155,22 +273,90 @@ impl<'a, K: Ki<'a>, Si: Sink<'a, K>> Lexer<'a, K, Si> {
                 };
             } else {
                 self.eat_lit(i, i.len());
-                self.sink
-                    .end()
-                    .map_err(|e| LexError::Fail(e, Span::from(i)))?;
-                break Ok(self.sink);
+                if let Err(e) = self.sink.end() {
+                    self.report(e)?;
+                }
+                break Ok(());
+            }
+        }
+    }
+
+    /// Keep error of a recovering lexer
+    fn report(&mut self, e: ErrorMessage<K::Error>) -> Result<(), ErrorMessage<K::Error>> {
+        match &mut self.errors {
+            Some(errors) => {
+                errors.push(e);
+                Ok(())
             }
+            None => Err(e),
         }
     }
 
+    /// Report error at the tag `at` bytes after literal start `i` and skip it until `next`
+    ///
+    /// Without `next` the tag ends after its close delimiters or at the next open tag
+    fn recover(
+        &mut self,
+        e: ErrorMessage<K::Error>,
+        i: Cursor<'a>,
+        at: usize,
+        next: Option<Cursor<'a>>,
+        kind: Option<K>,
+    ) -> Result<Cursor<'a>, ErrorMessage<K::Error>> {
+        self.report(e)?;
+        if self.lit_end != i.off + at as u32 {
+            self.eat_lit(i, at);
+        }
+        let tag = i.adv(at);
+        let next = next.unwrap_or_else(|| self.resync(tag));
+        self.sink.error(kind, Span::from_cursor(tag, next));
+        Ok(next)
+    }
+
+    /// Cursor after the close delimiters of the tag at `i` or at the next open tag
+    fn resync(&self, i: Cursor<'a>) -> Cursor<'a> {
+        let d = self.delimiters;
+        let c = i.adv(2);
+        let bytes = c.as_bytes();
+        let find = |a: u8, b: u8| bytes.windows(2).position(|w| w[0] == a && w[1] == b);
+        let close = [d.close_expr, d.close_block]
+            .into_iter()
+            .filter_map(|x| find(x, d.close))
+            .min();
+        let open = [d.open_expr, d.open_block]
+            .into_iter()
+            .filter_map(|x| find(d.open, x))
+            .min();
+        match (close, open) {
+            (Some(j), open) if open.is_none_or(|o| j < o) => {
+                let next = c.adv(j + 2);
+                // Close of a safe expression
+                if c.next_is(d.open_expr) && next.next_is(d.close) {
+                    next.adv(1)
+                } else {
+                    next
+                }
+            }
+            (_, Some(o)) => c.adv(o),
+            _ => c.adv(c.len()),
+        }
+    }
+
+    /// Kind of tag, when the tag is wrong
+    fn kind(&self, i: Cursor<'a>) -> Option<K> {
+        let (i, _) = self.eat_ws(i).ok()?;
+        K::parse(i).ok().map(|(_, kind)| kind)
+    }
+
     /// Push literal at cursor with length
     fn eat_lit(&mut self, i: Cursor<'a>, len: usize) {
+        self.lit_end = i.off + len as u32;
         let lit = &i.rest[..len];
         if !lit.is_empty() {
             let (l, lit, r) = trim(lit);
             let ins = Span {
                 lo: i.off + l.len() as u32,
-                hi: i.off + lit.len() as u32,
+                hi: i.off + (len - r.len()) as u32,
             };
             let out = Span {
                 lo: i.off,
@@ -180,10 +366,19 @@ impl<'a, K: Ki<'a>, Si: Sink<'a, K>> Lexer<'a, K, Si> {
         }
     }
 
+    /// Tokens of `K` without close delimiters can't be literals
+    fn uncompleted(&self, i: Cursor<'a>) -> Result<(), LexError<K::Error>> {
+        let c = tac::<K::Error>(i, self.delimiters.ws).map_or(i, |(c, _)| c);
+        match K::parse(c) {
+            Ok(_) => Err(LexError::Fail(K::Error::UNCOMPLETED, Span::from_len(i, 1))),
+            Err(_) => Ok(()),
+        }
+    }
+
     fn eat_expr(&mut self, i: Cursor<'a>, span: Span) -> Result<(), LexError<K::Error>> {
         const LET: &str = "let ";
 
-        let (i, gws) = Self::eat_ws(i)?;
+        let (i, gws) = self.eat_ws(i)?;
         if do_parse!(i, ws => tag::<K::Error>[LET] => ()).is_ok() {
             let (l, s, _) = trim(i.rest);
             let init = i.off + l.len() as u32;
@@ -197,7 +392,7 @@ impl<'a, K: Ki<'a>, Si: Sink<'a, K>> Lexer<'a, K, Si> {
                 .and_then(|e| {
                     self.sink
                         .local(gws, S(e, Span::new(init, init + s.len() as u32)), span)
-                        .map_err(|e| LexError::Fail(e, span))
+                        .map_err(fail)
                 })
         } else {
             let (i, kind) = match K::parse(i) {
@@ -210,13 +405,9 @@ impl<'a, K: Ki<'a>, Si: Sink<'a, K>> Lexer<'a, K, Si> {
             if let Ok(arm) = eat_arm(s) {
                 let arm = S(arm, Span::new(init, init + s.len() as u32));
                 return if let Some(kind) = kind {
-                    self.sink
-                        .arm_kind(gws, kind, arm, span)
-                        .map_err(|e| LexError::Fail(e, span))
+                    self.sink.arm_kind(gws, kind, arm, span).map_err(fail)
                 } else {
-                    self.sink
-                        .arm(gws, arm, span)
-                        .map_err(|e| LexError::Fail(e, span))
+                    self.sink.arm(gws, arm, span).map_err(fail)
                 };
             }
             let expr = eat_expr_list(s)
@@ -229,26 +420,23 @@ impl<'a, K: Ki<'a>, Si: Sink<'a, K>> Lexer<'a, K, Si> {
                 })?;
 
             if let Some(kind) = kind {
-                self.sink
-                    .expr_kind(gws, kind, expr, span)
-                    .map_err(|e| LexError::Fail(e, span))
+                self.sink.expr_kind(gws, kind, expr, span).map_err(fail)
             } else {
-                self.sink
-                    .expr(gws, expr, span)
-                    .map_err(|e| LexError::Fail(e, span))
+                self.sink.expr(gws, expr, span).map_err(fail)
             }
         }
     }
 
-    fn eat_ws(i: Cursor) -> PResult<(bool, bool), K::Error> {
-        let (i, lws) = match tac::<K::Error>(i, K::WS) {
+    fn eat_ws(&self, i: Cursor<'a>) -> PResult<'a, (bool, bool), K::Error> {
+        let ws = self.delimiters.ws;
+        let (i, lws) = match tac::<K::Error>(i, ws) {
             Ok((c, _)) => (c, true),
             _ => (i, false),
         };
         if i.is_empty() {
             return Err(LexError::Next(K::Error::WHITESPACE, Span::from(i)));
         }
-        let (rest, rws) = match tac::<K::Error>(i.adv(i.len() - 1), K::WS) {
+        let (rest, rws) = match tac::<K::Error>(i.adv(i.len() - 1), ws) {
             Ok(_) => (&i.rest[..i.len() - 1], true),
             _ => (i.rest, false),
         };
@@ -257,7 +445,7 @@ impl<'a, K: Ki<'a>, Si: Sink<'a, K>> Lexer<'a, K, Si> {
     }
 
     fn eat_block(&mut self, i: Cursor<'a>, span: Span) -> Result<(), LexError<K::Error>> {
-        let (i, gws) = Self::eat_ws(i)?;
+        let (i, gws) = self.eat_ws(i)?;
         let (i, kind) = match K::parse(i) {
             Ok((c, kind)) => (c, Some(kind)),
             Err(LexError::Next(..)) => (i, None),
@@ -275,33 +463,32 @@ impl<'a, K: Ki<'a>, Si: Sink<'a, K>> Lexer<'a, K, Si> {
             })?;
 
         if let Some(kind) = kind {
-            self.sink
-                .block_kind(gws, kind, expr, span)
-                .map_err(|e| LexError::Fail(e, span))
+            self.sink.block_kind(gws, kind, expr, span).map_err(fail)
         } else {
-            self.sink
-                .block(gws, expr, span)
-                .map_err(|e| LexError::Fail(e, span))
+            self.sink.block(gws, expr, span).map_err(fail)
         }
     }
 }
 
 // TODO: check rust token groups and LitStr, LitChar, LitBytes
 #[inline]
-fn end_safe_after<'a, K: Ki<'a>>(i: Cursor<'a>) -> PResult<(Cursor, bool), K::Error> {
-    let ws_end = &[K::WS, K::CLOSE_EXPR, K::CLOSE];
-    let end = &[K::CLOSE_EXPR, K::CLOSE];
+fn end_safe_after<'a, K: Ki<'a>>(
+    i: Cursor<'a>,
+    d: Delimiters,
+) -> PResult<(Cursor, bool), K::Error> {
+    let ws_end = &[d.close_expr, d.ws, d.close_expr, d.close];
+    let end = &[d.close_expr, d.close_expr, d.close];
 
     let mut at = 0;
 
     loop {
-        if let Some(j) = i.adv_find(at, K::CLOSE_EXPR) {
-            if 0 < at + j && i.adv_starts_with_bytes(at + j - 1, ws_end) {
-                let next = i.adv(at + j - 1 + ws_end.len());
-                let cur = Cursor::_new(&i.rest[..at + j - 2], i.off);
+        if let Some(j) = i.adv_find(at, d.close_expr) {
+            if i.adv_starts_with_bytes(at + j, ws_end) {
+                let next = i.adv(at + j + ws_end.len());
+                let cur = Cursor::_new(&i.rest[..at + j], i.off);
                 break Ok((next, (cur, true)));
             } else if i.adv_starts_with_bytes(at + j, end) {
-                let next = i.adv(at + j + 1 + end.len());
+                let next = i.adv(at + j + end.len());
                 let cur = Cursor::_new(&i.rest[..at + j], i.off);
                 break Ok((next, (cur, false)));
             }
@@ -318,14 +505,14 @@ fn end_safe_after<'a, K: Ki<'a>>(i: Cursor<'a>) -> PResult<(Cursor, bool), K::Er
 
 // TODO: check rust token groups and LitStr, LitChar, LitBytes
 #[inline]
-fn end_safe<'a, K: Ki<'a>>(i: Cursor<'a>) -> PResult<(Cursor, bool), K::Error> {
-    let ws_end = &[K::WS, K::CLOSE_EXPR, K::CLOSE_EXPR, K::CLOSE];
-    let end = &[K::CLOSE_EXPR, K::CLOSE_EXPR, K::CLOSE];
+fn end_safe<'a, K: Ki<'a>>(i: Cursor<'a>, d: Delimiters) -> PResult<(Cursor, bool), K::Error> {
+    let ws_end = &[d.ws, d.close_expr, d.close_expr, d.close];
+    let end = &[d.close_expr, d.close_expr, d.close];
 
     let mut at = 0;
 
     loop {
-        if let Some(j) = i.adv_find(at, K::CLOSE_EXPR) {
+        if let Some(j) = i.adv_find(at, d.close_expr) {
             if 0 < at + j && i.adv_starts_with_bytes(at + j - 1, ws_end) {
                 let next = i.adv(at + j - 1 + ws_end.len());
                 let cur = Cursor::_new(&i.rest[..at + j - 1], i.off);
@@ -347,19 +534,19 @@ fn end_safe<'a, K: Ki<'a>>(i: Cursor<'a>) -> PResult<(Cursor, bool), K::Error> {
 }
 
 #[inline]
-fn safe<'a, K: Ki<'a>>(i: Cursor<'a>) -> PResult<(Ws, SExpr), K::Error> {
+fn safe<'a, K: Ki<'a>>(i: Cursor<'a>, d: Delimiters) -> PResult<(Ws, SExpr), K::Error> {
     let (c, (i, ws)) = if K::WS_AFTER {
         do_parse!(i,
-            lws= tac[K::WS]:opt:is_some =>
-            tac[K::OPEN_EXPR]           =>
-            end= end_safe_after::<K>    =>
+            lws= tac[d.ws]:opt:is_some      =>
+            tac[d.open_expr]                =>
+            end= end_safe_after::<K>[d]     =>
             ((end.0, (lws, end.1)))
         )?
     } else {
         do_parse!(i,
-            tac[K::OPEN_EXPR]           =>
-            lws= tac[K::WS]:opt:is_some =>
-            end= end_safe::<K>          =>
+            tac[d.open_expr]                =>
+            lws= tac[d.ws]:opt:is_some      =>
+            end= end_safe::<K>[d]           =>
             ((end.0, (lws, end.1)))
         )?
     };
@@ -386,35 +573,14 @@ pub(crate) struct MiddleError {
     pub span: (u32, u32),
 }
 
-fn get_line_offset(src: &str, line_num: usize, column: usize) -> usize {
-    assert!(1 < line_num);
-    let mut line_num = line_num - 1;
-    let mut prev = 0;
-    while let Some(len) = src[prev..].find('\n') {
-        prev += len + 1;
-        line_num -= 1;
-        if line_num == 0 {
-            break;
-        }
-    }
-    assert_eq!(line_num, 0);
-
-    prev + get_chars(&src[prev..], 0, column).len()
-}
-
 impl MiddleError {
     fn new(src: &str, e: syn::Error) -> Self {
-        let start = e.span().start();
-        let end = e.span().end();
-        let lo = if start.line == 1 {
-            get_chars(src, 0, start.column).len()
+        // Call site is the end of input
+        let range = e.span().byte_range();
+        let (lo, hi) = if range.end == 0 {
+            (src.len(), src.len())
         } else {
-            get_line_offset(src, start.line, start.column)
-        };
-        let hi = if end.line == 1 {
-            end.column
-        } else {
-            get_line_offset(src, end.line, end.column)
+            (range.start, range.end)
         };
         Self {
             message: e.to_string(),
@@ -423,42 +589,39 @@ impl MiddleError {
     }
 }
 
+/// Tokenize with template spans and parse with syn
+fn parse_tokens<T: Parse>(i: &str) -> Result<T, MiddleError> {
+    token_stream(i).and_then(|tokens| parse2::<T>(tokens).map_err(|e| MiddleError::new(i, e)))
+}
+
 /// Parse Arm
 fn eat_arm(i: &str) -> Result<Box<Arm>, MiddleError> {
-    parse_str::<Arm>(i)
-        .map(Box::new)
-        .map_err(|e| MiddleError::new(i, e))
+    parse_tokens::<Arm>(i).map(Box::new)
 }
 
 /// Parse syn local
 fn eat_local(i: &str) -> Result<Box<crate::Local>, MiddleError> {
-    parse_str::<StmtLocal>(i)
-        .map(Into::into)
-        .map(Box::new)
-        .map_err(|e| MiddleError::new(i, e))
+    parse_tokens::<StmtLocal>(i).map(Into::into).map(Box::new)
 }
 
 /// Parse syn expression comma separated list
 pub(crate) fn eat_expr_list(i: &str) -> Result<Vec<crate::Expr>, MiddleError> {
-    parse_str::<ExprList>(i)
-        .map(Into::into)
-        .map_err(|e| MiddleError::new(i, e))
+    parse_tokens::<ExprList>(i).map(Into::into)
 }
 
 /// Parse syn expression comma separated list
 pub(crate) fn eat_expression(i: &str) -> Result<Box<crate::Expr>, MiddleError> {
-    parse_str::<crate::Expr>(i)
-        .map(Box::new)
-        .map_err(|e| MiddleError::new(i, e))
+    parse_tokens::<crate::Expr>(i).map(Box::new)
 }
 
 // TODO: check rust token groups and LitStr, LitChar, LitBytes
 #[inline]
-fn end<'a, K: Ki<'a>>(i: Cursor<'a>, expr: bool) -> PResult<Cursor<'a>, K::Error> {
+fn end<E: KiError>(i: Cursor, d: Delimiters, expr: bool) -> PResult<Cursor, E> {
     let mut at = 0;
     loop {
-        if let Some(j) = i.adv_find(at, if expr { K::CLOSE_EXPR } else { K::CLOSE_BLOCK }) {
-            if i.adv_next_is(at + j + 1, K::CLOSE) {
+        let close = if expr { d.close_expr } else { d.close_block };
+        if let Some(j) = i.adv_find(at, close) {
+            if i.adv_next_is(at + j + 1, d.close) {
                 let inner = Cursor {
                     rest: &i.rest[..at + j],
                     off: i.off,
@@ -468,7 +631,7 @@ fn end<'a, K: Ki<'a>>(i: Cursor<'a>, expr: bool) -> PResult<Cursor<'a>, K::Error
                 at += j + 1;
             }
         } else {
-            break Err(LexError::Next(K::Error::UNCOMPLETED, Span::from(i)));
+            break Err(LexError::Next(E::UNCOMPLETED, Span::from(i)));
         }
     }
 }
@@ -499,3 +662,21 @@ pub fn trim(i: &str) -> (&str, &str, &str) {
         (i, "", "")
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::trim;
+
+    #[test]
+    fn test_trim() {
+        assert_eq!(trim(" a "), (" ", "a", " "));
+        assert_eq!(trim(" a"), (" ", "a", ""));
+        assert_eq!(trim("a"), ("", "a", ""));
+        assert_eq!(trim(""), ("", "", ""));
+        assert_eq!(trim("a "), ("", "a", " "));
+        assert_eq!(trim("a a"), ("", "a a", ""));
+        assert_eq!(trim("a a "), ("", "a a", " "));
+        assert_eq!(trim(" \n\t\ra a "), (" \n\t\r", "a a", " "));
+        assert_eq!(trim(" \n\t\r "), (" \n\t\r ", "", ""));
+    }
+}
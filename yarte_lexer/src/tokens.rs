@@ -0,0 +1,200 @@
+//! Template expressions as `proc_macro2` tokens
+//!
+//! Expressions are tokenized by `yarte_rust` so the spans are template positions.
+//! `proc_macro2` only builds spans from source text, so the token spans come from a
+//! skeleton of the expression with every token at the same byte range,
+//! and `Span::byte_range` gives back the position in the expression.
+use std::str::FromStr;
+
+use proc_macro2::{Delimiter, Group, Ident, Literal, Span, TokenStream, TokenTree};
+
+use yarte_rust::error::Error;
+use yarte_rust::lexer;
+use yarte_rust::sink::{SResult, Sink, State};
+use yarte_rust::tokens;
+use yarte_strnom::source_map::{self, S};
+use yarte_strnom::{Cursor, LexError};
+
+use crate::parse::MiddleError;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token<'a> {
+    Open(tokens::Delimiter),
+    Close(tokens::Delimiter),
+    Ident(&'a str),
+    Punct(tokens::Punct),
+    Literal(&'a str),
+}
+
+#[derive(Default)]
+struct Tokens<'a> {
+    tokens: Vec<S<Token<'a>>>,
+    stack: Vec<S<tokens::Delimiter>>,
+}
+
+impl<'a> Sink<'a> for Tokens<'a> {
+    fn open_group(&mut self, del: S<tokens::Delimiter>) -> SResult {
+        self.tokens.push(S(Token::Open(del.0), del.1));
+        self.stack.push(del);
+        Ok(State::Continue)
+    }
+
+    fn close_group(&mut self, del: S<tokens::Delimiter>) -> SResult {
+        match self.stack.pop() {
+            Some(S(open, _)) if open == del.0 => {
+                self.tokens.push(S(Token::Close(del.0), del.1));
+                Ok(State::Continue)
+            }
+            _ => Err(LexError::Fail(Error::UnexpectedDelimiter, del.1)),
+        }
+    }
+
+    fn ident(&mut self, ident: S<tokens::Ident<'a>>) -> SResult {
+        self.tokens.push(S(Token::Ident(ident.0.inner), ident.1));
+        Ok(State::Continue)
+    }
+
+    fn punct(&mut self, punct: S<tokens::Punct>) -> SResult {
+        self.tokens.push(S(Token::Punct(punct.0), punct.1));
+        Ok(State::Continue)
+    }
+
+    fn literal(&mut self, literal: S<tokens::Literal<'a>>) -> SResult {
+        self.tokens.push(S(Token::Literal(literal.0.i), literal.1));
+        Ok(State::Continue)
+    }
+
+    fn end(&mut self) -> SResult {
+        Ok(State::Continue)
+    }
+}
+
+fn delimiter(del: tokens::Delimiter) -> (Delimiter, u8, u8) {
+    match del {
+        tokens::Delimiter::Parenthesis => (Delimiter::Parenthesis, b'(', b')'),
+        tokens::Delimiter::Brace => (Delimiter::Brace, b'{', b'}'),
+        tokens::Delimiter::Bracket => (Delimiter::Bracket, b'[', b']'),
+    }
+}
+
+fn error(message: impl ToString, span: source_map::Span) -> MiddleError {
+    MiddleError {
+        message: message.to_string(),
+        span: (span.lo, span.hi),
+    }
+}
+
+/// Same layout as the tokens at `src` where identifiers and literals are `_`
+fn skeleton(src: &str, tokens: &[S<Token>]) -> String {
+    let mut skeleton: Vec<u8> = src
+        .bytes()
+        .map(|b| if b == b'\n' { b'\n' } else { b' ' })
+        .collect();
+
+    for S(token, span) in tokens {
+        let (lo, hi) = (span.lo as usize, span.hi as usize);
+        match token {
+            Token::Open(del) => skeleton[lo] = delimiter(*del).1,
+            Token::Close(del) => skeleton[lo] = delimiter(*del).2,
+            Token::Punct(punct) => skeleton[lo] = *punct as u8,
+            Token::Ident(_) | Token::Literal(_) => skeleton[lo..hi].fill(b'_'),
+        }
+    }
+
+    String::from_utf8(skeleton).expect("ASCII skeleton")
+}
+
+/// Skeleton token trees in source order, groups before its content
+fn flatten(stream: TokenStream, trees: &mut Vec<TokenTree>) {
+    for tree in stream {
+        trees.push(tree.clone());
+        if let TokenTree::Group(group) = tree {
+            flatten(group.stream(), trees);
+        }
+    }
+}
+
+/// Tokenize a Rust expression
+pub(crate) fn token_stream(src: &str) -> Result<TokenStream, MiddleError> {
+    let mut sink = Tokens::default();
+    lexer::token_stream(Cursor { rest: src, off: 0 }, &mut sink).map_err(|e| match e {
+        LexError::Fail(Error::UnmatchedToken, span)
+        | LexError::Next(Error::UnmatchedToken, span) => {
+            let len = src[span.lo as usize..]
+                .chars()
+                .next()
+                .map_or(0, char::len_utf8);
+            error(
+                "unknown start of token",
+                source_map::Span::new(span.lo, span.lo + len as u32),
+            )
+        }
+        LexError::Fail(e, span) | LexError::Next(e, span) => error(e, span),
+    })?;
+    if let Some(S(_, span)) = sink.stack.pop() {
+        return Err(error(Error::UnclosedDelimiter, span));
+    }
+
+    // Adjacent identifiers and literals are merged in the skeleton
+    let unexpected = || {
+        error(
+            "unexpected token",
+            source_map::Span::new(0, src.len() as u32),
+        )
+    };
+    let mut skeleton_trees = vec![];
+    flatten(
+        TokenStream::from_str(&skeleton(src, &sink.tokens)).map_err(|_| unexpected())?,
+        &mut skeleton_trees,
+    );
+    let closes = sink
+        .tokens
+        .iter()
+        .filter(|S(token, _)| matches!(token, Token::Close(_)))
+        .count();
+    if skeleton_trees.len() + closes != sink.tokens.len() {
+        return Err(unexpected());
+    }
+    let mut skeleton_trees = skeleton_trees.into_iter();
+
+    let mut stack: Vec<(Delimiter, Span, Vec<TokenTree>)> =
+        vec![(Delimiter::None, Span::call_site(), vec![])];
+    for S(token, span) in sink.tokens {
+        let tree = match token {
+            Token::Close(_) => {
+                let (del, span, trees) = stack.pop().expect("open group");
+                let mut group = Group::new(del, trees.into_iter().collect());
+                group.set_span(span);
+                group.into()
+            }
+            token => {
+                let skeleton = skeleton_trees.next().expect("same length");
+                let proc_span = skeleton.span();
+                match token {
+                    Token::Open(del) => {
+                        stack.push((delimiter(del).0, proc_span, vec![]));
+                        continue;
+                    }
+                    Token::Ident(ident) => match ident.strip_prefix("r#") {
+                        Some(raw) if matches!(raw, "_" | "crate" | "self" | "Self" | "super") => {
+                            return Err(error(format!("`{raw}` cannot be a raw identifier"), span));
+                        }
+                        Some(raw) => Ident::new_raw(raw, proc_span).into(),
+                        None => Ident::new(ident, proc_span).into(),
+                    },
+                    Token::Literal(literal) => {
+                        let mut literal =
+                            Literal::from_str(literal).map_err(|_| error(Error::Literal, span))?;
+                        literal.set_span(proc_span);
+                        literal.into()
+                    }
+                    // Same char, spacing and span
+                    _ => skeleton,
+                }
+            }
+        };
+        stack.last_mut().expect("stream").2.push(tree);
+    }
+
+    Ok(stack.pop().expect("stream").2.into_iter().collect())
+}
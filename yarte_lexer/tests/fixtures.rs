@@ -8,7 +8,7 @@ use serde::Deserialize;
 
 use std::error::Error;
 use yarte_lexer::{
-    path, Ki, Kinder, LexResult, Lexer, SArm, SExpr, SLocal, SStr, SVExpr, Sink, Ws,
+    path, Delimiters, Ki, Kinder, LexResult, Lexer, SArm, SExpr, SLocal, SStr, SVExpr, Sink, Ws,
 };
 use yarte_strnom::error::{ErrorMessage, KiError, Result};
 use yarte_strnom::pipes::{
@@ -36,6 +36,12 @@ where
     ),
     Block(Ws, SVExpr),
     BlockKind(Ws, Kind, SVExpr),
+    Raw(
+        (Ws, Ws),
+        #[serde(borrow)] &'a str,
+        #[serde(borrow)] SStr<'a>,
+        #[serde(borrow)] &'a str,
+    ),
 }
 
 struct VecSink<'a, K: Ki<'a>>(Vec<S<Token<'a, K>>>);
@@ -85,11 +91,25 @@ impl<'a, K: Ki<'a>> Sink<'a, K> for VecSink<'a, K> {
         Ok(())
     }
 
+    fn raw(
+        &mut self,
+        ws: (Ws, Ws),
+        left: &'a str,
+        src: SStr<'a>,
+        right: &'a str,
+        span: Span,
+    ) -> LexResult<K::Error> {
+        self.0.push(S(Token::Raw(ws, left, src, right), span));
+        Ok(())
+    }
+
     fn safe(&mut self, ws: Ws, expr: SExpr, span: Span) -> LexResult<K::Error> {
         self.0.push(S(Token::Safe(ws, expr), span));
         Ok(())
     }
 
+    fn error(&mut self, _: Option<K>, _: Span) {}
+
     fn end(&mut self) -> LexResult<K::Error> {
         Ok(())
     }
@@ -141,6 +161,27 @@ fn comment<'a, K: Ki<'a>>(i: Cursor<'a>) -> Result<&'a str, K::Error> {
     }
 }
 
+fn raw<'a, K: Ki<'a>>(i: Cursor<'a>, d: Delimiters) -> Result<'a, ((Ws, Ws), SStr<'a>), K::Error> {
+    const RAW: u8 = b'R';
+    const WS: Ws = (false, false);
+
+    let end = [d.open, d.open_expr, b'/', RAW, d.close_expr, d.close];
+    let (c, _) = do_parse!(i, tac[RAW] => tac[d.close_expr] => tac[d.close] => ())?;
+
+    let mut at = 0;
+    loop {
+        if let Some(j) = c.adv_find(at, d.open) {
+            if c.adv_starts_with_bytes(at + j, &end) {
+                let src = S(&c.rest[..at + j], Span::from_len(c, at + j));
+                break Ok((c.adv(at + j + end.len()), ((WS, WS), src)));
+            }
+            at += j + 1;
+        } else {
+            break Err(LexError::Next(K::Error::UNCOMPLETED, Span::from(c)));
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Deserialize)]
 enum MyKindAfter<'a> {
     Partial(&'a str),
@@ -173,7 +214,7 @@ impl<'a> Kinder<'a> for MyKindAfter<'a> {
         )
     }
 
-    fn comment(i: Cursor<'a>) -> Result<&'a str, Self::Error> {
+    fn comment(i: Cursor<'a>, _: Delimiters) -> Result<&'a str, Self::Error> {
         comment::<Self>(i)
     }
 }
@@ -200,9 +241,13 @@ impl<'a> Kinder<'a> for MyKind<'a> {
         alt!(i, some | partial)
     }
 
-    fn comment(i: Cursor<'a>) -> Result<&'a str, Self::Error> {
+    fn comment(i: Cursor<'a>, _: Delimiters) -> Result<&'a str, Self::Error> {
         comment::<Self>(i)
     }
+
+    fn raw(i: Cursor<'a>, d: Delimiters) -> Result<((Ws, Ws), SStr<'a>), Self::Error> {
+        raw::<Self>(i, d)
+    }
 }
 
 fn partial(i: Cursor) -> Result<MyKind, MyError> {
@@ -271,7 +316,7 @@ impl<'a> Kinder<'a> for MyKindBlock<'a> {
         )
     }
 
-    fn comment(i: Cursor<'a>) -> Result<&'a str, Self::Error> {
+    fn comment(i: Cursor<'a>, _: Delimiters) -> Result<&'a str, Self::Error> {
         comment::<Self>(i)
     }
 }
@@ -360,3 +405,33 @@ fn test_panic() {
         }
     }
 }
+
+#[test]
+fn test_delimiters() {
+    let delimiters = Delimiters {
+        open: b'<',
+        close: b'>',
+        open_expr: b'%',
+        close_expr: b'%',
+        open_block: b'%',
+        close_block: b'%',
+        ..Delimiters::of::<MyKind>()
+    };
+    let fixtures: Vec<Fixture<'_, MyKind>> = ron::from_str(
+        r#"[
+            (src: "<% var %>", exp: [(Expr((false, false), (["var"], (lo:3, hi:6))), (lo:0, hi:9))]),
+            (src: "<%% var %%>", exp: [(Safe((false, false), ("var", (lo:4, hi:7))), (lo:0, hi:11))]),
+            (src: "<%R%> <% a %> <%/R%>", exp: [(Raw(((false, false), (false, false)), " ", ("<% a %>", (lo:6, hi:13)), " "), (lo:0, hi:20))]),
+            (src: "{{ var }}", exp: [(Lit("", ("{{ var }}", (lo:0, hi:9)), ""), (lo:0, hi:9))]),
+        ]"#,
+    )
+    .expect("Valid Fixtures");
+
+    for Fixture { src, exp } in fixtures {
+        let res = Lexer::<MyKind, _>::with_delimiters(VecSink(vec![]), delimiters)
+            .feed(unsafe { Cursor::new(src, 0) })
+            .expect("Valid parse")
+            .0;
+        assert_eq!(res, exp);
+    }
+}
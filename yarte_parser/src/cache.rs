@@ -0,0 +1,313 @@
+//! Per thread cache of template sources, parsed nodes and partial dependencies
+//!
+//! A proc-macro instance lives as long as the compilation of a crate, so every derive
+//! can reuse the work done by the previous ones. The cache keeps the last source of each
+//! path, keyed by its content hash, file sources are revalidated by mtime. A new source
+//! of a path drops the previous one with its nodes, so a long lived proc-macro server
+//! doesn't keep every edit of a template.
+//!
+//! Every interned source has its own offsets, so its cached nodes are valid in any
+//! source map. [`parse`] adds the source to the [`SourceMap`] of the caller.
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::SystemTime,
+};
+
+use yarte_helpers::{
+    calculate_hash,
+    config::{get_source, Syntax},
+};
+
+use crate::{
+    error::PError, parse::parse_recover as parse_nodes, pre_partials::partials as node_partials,
+    source_map::SourceMap, Cursor, ErrorMessage, SNode,
+};
+
+thread_local! {
+    static CACHE: RefCell<Cache> = RefCell::new(Default::default());
+}
+
+/// Nodes of a template source and its parse errors
+pub type Parsed<'a> = (Vec<SNode<'a>>, Vec<ErrorMessage<PError>>);
+
+/// Interned template source, nodes and partials of [`parse`] and [`partials`] borrow it
+pub type Source = Rc<str>;
+
+#[derive(Default)]
+struct Cache {
+    /// Start offset of the next source
+    next: u32,
+    /// path - mtime of the template files
+    files: BTreeMap<PathBuf, Option<SystemTime>>,
+    /// path - last interned source
+    entries: BTreeMap<PathBuf, Entry>,
+}
+
+/// Interned source of a path and its parsed nodes
+struct Entry {
+    src: Source,
+    hash: u64,
+    /// Start offset of the source
+    lo: u32,
+    /// delimiters - parsed nodes and its errors
+    ///
+    /// Nodes borrow `src`, they're only handed out to holders of `src`
+    nodes: BTreeMap<Syntax, Rc<Parsed<'static>>>,
+    /// delimiters - partial identifiers
+    partials: BTreeMap<Syntax, Rc<Vec<&'static str>>>,
+}
+
+impl Cache {
+    /// Entry of `path` when `src` is its interned source
+    fn entry(&mut self, path: &Path, src: &Source) -> Option<&mut Entry> {
+        self.entries
+            .get_mut(path)
+            .filter(|e| Rc::ptr_eq(&e.src, src))
+    }
+
+    /// Offsets of a new source of `len` bytes, disjoint of the previous ones
+    ///
+    /// Offsets start again, with an empty cache, when they run out
+    fn offset(&mut self, len: usize) -> u32 {
+        // Add 1 so there's always space between sources.
+        let end = u32::try_from(len)
+            .ok()
+            .and_then(|len| self.next.checked_add(len)?.checked_add(1));
+        match end {
+            Some(end) => std::mem::replace(&mut self.next, end),
+            None => {
+                *self = Cache::default();
+                self.next = len as u32 + 1;
+                0
+            }
+        }
+    }
+}
+
+/// Intern `src` as source of `path`
+///
+/// Equal sources of the same path are interned once, a different one replaces
+/// the previous source of the path in the cache
+pub fn intern(path: &Path, src: String) -> Source {
+    let hash = calculate_hash(&src);
+    CACHE.with(|c| {
+        let mut c = c.borrow_mut();
+        match c.entries.get(path) {
+            Some(e) if e.hash == hash && *e.src == *src => e.src.clone(),
+            _ => {
+                let lo = c.offset(src.len());
+                let src: Source = src.into();
+                let entry = Entry {
+                    src: src.clone(),
+                    hash,
+                    lo,
+                    nodes: BTreeMap::new(),
+                    partials: BTreeMap::new(),
+                };
+                c.entries.insert(path.to_owned(), entry);
+                src
+            }
+        }
+    })
+}
+
+/// Read template file at `path` and intern it
+///
+/// The file is only read again when its modification time changes
+///
+/// # Panics
+/// When the file can't be opened
+pub fn read(path: &Path) -> Source {
+    let modified = fs::metadata(path).and_then(|m| m.modified()).ok();
+    if let Some(src) = CACHE.with(|c| {
+        let c = c.borrow();
+        c.files
+            .get(path)
+            .filter(|m| modified.is_some() && **m == modified)
+            .and_then(|_| c.entries.get(path))
+            .map(|e| e.src.clone())
+    }) {
+        return src;
+    }
+
+    let src = intern(path, get_source(path));
+    CACHE.with(|c| c.borrow_mut().files.insert(path.to_owned(), modified));
+    src
+}
+
+/// Parse an interned source with `syntax` delimiters returning its nodes and every error
+///
+/// Wrong regions of the source are [`Node::Invalid`](crate::Node::Invalid) nodes.
+/// The source is added to `sm`, so it resolves the spans of the nodes. The first
+/// parse of a source is cached, following calls clone the cached nodes. Sources
+/// replaced in the cache are parsed again
+pub fn parse<'a>(sm: &mut SourceMap, path: &Path, src: &'a Source, syntax: Syntax) -> Parsed<'a> {
+    let (lo, parsed) = nodes(path, src, syntax);
+    sm.get_cursor_at(path, src, lo);
+    (*parsed).clone()
+}
+
+/// Start offset and cached nodes of an interned source
+fn nodes<'a>(path: &Path, src: &'a Source, syntax: Syntax) -> (u32, Rc<Parsed<'a>>) {
+    let (lo, cached) = CACHE.with(|c| {
+        let mut c = c.borrow_mut();
+        match c.entry(path, src) {
+            Some(e) => (e.lo, e.nodes.get(&syntax).cloned()),
+            None => (c.offset(src.len()), None),
+        }
+    });
+    if let Some(parsed) = cached {
+        return (lo, parsed);
+    }
+
+    let mut cursor = Cursor::new(src, lo);
+    cursor.syntax = syntax;
+    let parsed = Rc::new(parse_nodes(cursor));
+    CACHE.with(|c| {
+        if let Some(e) = c.borrow_mut().entry(path, src) {
+            // Safety: nodes borrow the source of the entry, they're dropped with it
+            // and only handed out with the lifetime of a borrow of the same source
+            let nodes = unsafe { extend((*parsed).clone()) };
+            e.nodes.insert(syntax, Rc::new(nodes));
+        }
+    });
+    (lo, parsed)
+}
+
+/// Partial identifiers used by an interned source, without resolve
+///
+/// Partials are collected from the nodes of [`parse`], its errors are reported there
+pub fn partials<'a>(path: &Path, src: &'a Source, syntax: Syntax) -> Rc<Vec<&'a str>> {
+    if let Some(partials) = CACHE.with(|c| {
+        c.borrow_mut()
+            .entry(path, src)
+            .and_then(|e| e.partials.get(&syntax).cloned())
+    }) {
+        return partials;
+    }
+
+    let partials: Vec<&'a str> = node_partials(&nodes(path, src, syntax).1 .0)
+        .iter()
+        .map(|p| *p.1.t())
+        .collect();
+    CACHE.with(|c| {
+        if let Some(e) = c.borrow_mut().entry(path, src) {
+            // Safety: same as the nodes of `parse`
+            let partials = unsafe { partials.iter().map(|p| &*(*p as *const str)).collect() };
+            e.partials.insert(syntax, Rc::new(partials));
+        }
+    });
+    Rc::new(partials)
+}
+
+/// Extends the lifetime of nodes borrowing a cached source
+///
+/// # Safety
+/// The nodes must not outlive the source
+unsafe fn extend(parsed: Parsed) -> Parsed<'static> {
+    std::mem::transmute(parsed)
+}
+
+/// Forget parsed nodes and partials
+pub fn clean() {
+    CACHE.with(|c| {
+        for e in c.borrow_mut().entries.values_mut() {
+            e.nodes.clear();
+            e.partials.clear();
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn test_intern() {
+        let path = PathBuf::from("foo.hbs");
+        let a = intern(&path, "{{ foo }}".into());
+        let b = intern(&path, "{{ foo }}".into());
+        let c = intern(&PathBuf::from("bar.hbs"), "{{ foo }}".into());
+        assert!(Rc::ptr_eq(&a, &b));
+        assert!(!Rc::ptr_eq(&a, &c));
+    }
+
+    #[test]
+    fn test_replace() {
+        let path = PathBuf::from("edit.hbs");
+        let a = intern(&path, "{{ foo }}".into());
+        let sm = &mut SourceMap::default();
+        let (nodes, _) = parse(sm, &path, &a, Syntax::default());
+        assert_eq!(Rc::strong_count(&a), 2);
+
+        let b = intern(&path, "{{ bar }}".into());
+        assert_eq!(Rc::strong_count(&a), 1);
+        assert_eq!(Rc::strong_count(&b), 2);
+        // Superseded sources still parse for its holders
+        let (renodes, _) = parse(sm, &path, &a, Syntax::default());
+        assert_eq!(renodes.len(), nodes.len());
+        assert_eq!(renodes[0].span().source_text(sm), "{{ foo }}");
+        assert!(!Rc::ptr_eq(&intern(&path, "{{ foo }}".into()), &a));
+        clean();
+    }
+
+    #[test]
+    fn test_parse() {
+        let path = PathBuf::from("foo.hbs");
+        let src = intern(&path, "Hello, {{ world }}!".into());
+        let sm = &mut SourceMap::default();
+        let (a, _) = parse(sm, &path, &src, Syntax::default());
+        let (b, _) = parse(sm, &path, &src, Syntax::default());
+        assert_eq!(a, b);
+        assert_eq!(a[1].span().start(sm).column, 7);
+
+        // Cached nodes resolve in every source map with its source
+        let other = &mut SourceMap::default();
+        let bar = PathBuf::from("bar.hbs");
+        let bar_src = intern(&bar, "\n{{ bar }}".into());
+        let (d, _) = parse(other, &bar, &bar_src, Syntax::default());
+        let (c, errors) = parse(other, &path, &src, Syntax::default());
+        assert!(errors.is_empty());
+        assert_eq!(a, c);
+        assert_eq!(c[1].span().start(other).column, 7);
+        assert_eq!(c[1].span().file_path(other), path);
+        assert_eq!(d[1].span().start(other).line, 2);
+        assert_eq!(d[1].span().file_path(other), bar);
+        clean();
+    }
+
+    #[test]
+    fn test_partials() {
+        let path = PathBuf::from("foo.hbs");
+        let src = intern(
+            &path,
+            "{{> foo }}{{#> bar }}{{#each a }}{{> baz }}{{/each }}{{/bar }}".into(),
+        );
+        assert_eq!(
+            *partials(&path, &src, Syntax::default()),
+            vec!["foo", "bar", "baz"]
+        );
+        assert!(Rc::ptr_eq(
+            &partials(&path, &src, Syntax::default()),
+            &partials(&path, &src, Syntax::default())
+        ));
+        clean();
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let path = PathBuf::from("foo.hbs");
+        let src = intern(&path, "{{ foo( }}{{> bar }}{{/baz }}".into());
+        let (nodes, errors) = parse(&mut SourceMap::default(), &path, &src, Syntax::default());
+        assert_eq!(nodes.len(), 3);
+        assert_eq!(errors.len(), 2);
+        assert_eq!(*partials(&path, &src, Syntax::default()), vec!["bar"]);
+        clean();
+    }
+}
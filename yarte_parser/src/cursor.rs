@@ -0,0 +1,36 @@
+use yarte_helpers::config::Syntax;
+
+/// Template source with its offset in the source map
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Cursor<'a> {
+    pub rest: &'a str,
+    pub off: u32,
+    /// Delimiters of the template
+    pub syntax: Syntax,
+}
+
+impl<'a> Cursor<'a> {
+    pub fn new(rest: &'a str, off: u32) -> Cursor<'a> {
+        Cursor {
+            rest,
+            off,
+            syntax: Syntax::default(),
+        }
+    }
+
+    pub fn adv(&self, amt: usize) -> Cursor<'a> {
+        Cursor {
+            rest: &self.rest[amt..],
+            off: self.off + (amt as u32),
+            syntax: self.syntax,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rest.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.rest.len()
+    }
+}
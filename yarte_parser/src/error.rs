@@ -1,5 +1,6 @@
 use std::{
     collections::BTreeMap,
+    error::Error,
     fmt::{self, Display, Write},
     path::PathBuf,
 };
@@ -11,8 +12,9 @@ use annotate_snippets::{
 use derive_more::Display;
 
 use yarte_helpers::config::Config;
+use yarte_strnom::error::KiError;
 
-use crate::{source_map::Span, strnom::LexError};
+use crate::source_map::{SourceMap, Span};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum DOption {
@@ -72,52 +74,73 @@ pub enum PError {
     AtHelperArgsLen(usize),
 }
 
-impl From<LexError> for ErrorMessage<PError> {
-    fn from(e: LexError) -> Self {
-        use LexError::*;
-        match e {
-            Next(m, s) | Fail(m, s) => ErrorMessage {
-                message: m,
-                span: s,
-            },
-        }
+impl Error for PError {}
+
+impl KiError for PError {
+    const EMPTY: Self = PError::Tag;
+    const UNCOMPLETED: Self = PError::Uncompleted;
+    const PATH: Self = PError::PartialPath;
+    const WHITESPACE: Self = PError::Whitespace;
+
+    fn str(_: &'static str) -> Self {
+        PError::Tag
+    }
+
+    fn char(_: char) -> Self {
+        PError::Tag
+    }
+
+    fn string(s: String) -> Self {
+        PError::Expr(DOption::Some(s))
     }
 }
 
 // TODO: T: Priority trait
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ErrorMessage<T: Display> {
     pub message: T,
     pub span: Span,
 }
 
 // TODO: Accumulate by priority
-pub fn emitter<I, T>(sources: &BTreeMap<PathBuf, String>, config: &Config, errors: I) -> !
+pub fn emitter<I, T, S>(
+    sources: &BTreeMap<PathBuf, S>,
+    config: &Config,
+    sm: &SourceMap,
+    errors: I,
+) -> !
 where
     I: Iterator<Item = ErrorMessage<T>>,
     T: Display,
+    S: AsRef<str>,
 {
-    let mut prefix = config.get_dir().clone();
-    prefix.pop();
     let mut errors: Vec<ErrorMessage<T>> = errors.collect();
 
     errors.sort_unstable_by(|a, b| a.span.lo.cmp(&b.span.lo));
     let slices: Vec<(String, PathBuf, Span)> = errors
         .into_iter()
-        .map(|err| (err.message.to_string(), err.span.file_path(), err.span))
+        .map(|err| (err.message.to_string(), err.span.file_path(sm), err.span))
         .collect();
     let slices = slices
         .iter()
         .map(|(label, origin, span)| {
-            let ((lo_line, hi_line), (lo, hi)) = span.range_in_file();
-            let start = span.start();
+            let ((lo_line, hi_line), (lo, hi)) = span.range_in_file(sm);
+            let start = span.start(sm);
             let source = sources
                 .get(origin)
                 .unwrap()
+                .as_ref()
                 .get(lo_line..hi_line)
                 .unwrap()
                 .trim_end();
-            let origin = origin.strip_prefix(&prefix).unwrap().to_str().unwrap();
+            // Relative to the crate of its template directory
+            let origin = config
+                .get_dirs()
+                .iter()
+                .find_map(|dir| origin.strip_prefix(dir.parent()?).ok())
+                .unwrap_or(origin)
+                .to_str()
+                .unwrap();
 
             Slice {
                 source,
@@ -5,14 +5,12 @@ use std::str;
 use serde::{Deserialize, Deserializer};
 use syn::parse::{Parse, ParseBuffer};
 
-#[macro_use]
-mod strnom;
-
 #[cfg(test)]
 mod test;
 
+pub mod cache;
+mod cursor;
 mod error;
-mod expr_list;
 mod parse;
 mod pre_partials;
 pub mod source_map;
@@ -21,11 +19,11 @@ mod stmt_local;
 use crate::source_map::S;
 
 pub use self::{
+    cursor::Cursor,
     error::{emitter, ErrorMessage},
     parse::*,
     pre_partials::parse_partials,
     stmt_local::StmtLocal,
-    strnom::Cursor,
 };
 
 pub type Ws = (bool, bool);
@@ -161,14 +159,18 @@ pub enum Node<'a> {
     ),
     Safe(Ws, SExpr),
     Error(SVExpr),
+    /// Wrong region of the source, reported by the parser
+    Invalid,
 }
 
 pub(crate) const JSON: &str = "json";
 pub(crate) const JSON_PRETTY: &str = "json_pretty";
+pub(crate) const SANITIZE: &str = "sanitize";
 #[derive(Debug, PartialEq, Eq, Clone, Deserialize)]
 pub enum AtHelperKind {
     Json,
     JsonPretty,
+    Sanitize,
 }
 
 #[derive(Debug, PartialEq, Clone, Deserialize)]
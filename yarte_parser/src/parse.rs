@@ -1,626 +1,559 @@
-use syn::parse_str;
+use std::mem;
+
 use unicode_xid::UnicodeXID;
 
+use yarte_helpers::config::Syntax;
+use yarte_lexer::{path, Delimiters, Kinder, LexResult, Lexer, Sink};
+use yarte_strnom::{
+    error::{ErrorMessage as LexMessage, Result as LResult},
+    skip_ws,
+    source_map::{spanned, S as LS},
+    tac, tag, Cursor as LCursor, LexError, Span as LSpan,
+};
+
 use crate::error::{DOption, PError};
-use crate::expr_list::ExprList;
-use crate::source_map::{spanned, Span, S};
-use crate::strnom::{is_ws, skip_ws, ws, LexError, PResult};
+use crate::source_map::S;
 use crate::{
-    AtHelperKind, Cursor, ErrorMessage, Expr, Helper, Node, Partial, PartialBlock, SExpr, SNode,
-    SVExpr, StmtLocal, Ws, JSON, JSON_PRETTY,
+    AtHelperKind, Cursor, ErrorMessage, Expr, Helper, Local, Node, Partial, PartialBlock, SExpr,
+    SNode, SStr, SVExpr, Ws, JSON, JSON_PRETTY, SANITIZE,
 };
 
+type LSStr<'a> = LS<&'a str>;
+
+const ELSE: &str = "else";
+const IF: &str = "if";
+const PARTIAL_BLOCK: &str = "@partial-block";
+const RAW: &str = "R";
+const CLOSE_RAW: &str = "/R";
+
+/// Parse template source at cursor with its delimiters
 pub fn parse(i: Cursor) -> Result<Vec<SNode>, ErrorMessage<PError>> {
-    let (c, res) = eat(i)?;
-    if c.is_empty() {
-        Ok(res)
-    } else {
-        Err(ErrorMessage {
-            message: PError::Uncompleted,
-            span: Span::from_len(c, 1),
-        })
-    }
+    Lexer::<Kind, Tree>::with_delimiters(Tree::default(), delimiters(i.syntax))
+        .feed(LCursor::_new(i.rest, i.off))
+        .map(|tree| tree.nodes)
+        .map_err(message)
 }
 
-/// Step in eater
-///     - Ok -> eat_lit -> push node -> restart in next cursor and continue
-///     - Err(Next) -> advance
-///     - Err(Fail) -> Insuperable error stop
-macro_rules! try_eat {
-    ($nodes:ident, $i:ident, $at:ident, $j:ident, $($t:tt)+) => {
-        match $($t)+ {
-            Ok((c, n)) => {
-                eat_lit(&mut $nodes, $i, $at + $j);
-                $nodes.push(S(n, Span::from_cursor($i.adv($at + $j), c)));
-                $i = c;
-                0
-            },
-            Err(e @ LexError::Fail(..)) => break Err(e),
-            Err(LexError::Next(..)) => $at + $j + 1,
-        }
-    };
+/// Parse template source at cursor with its delimiters reporting every error
+///
+/// Wrong tags are [`Node::Invalid`], wrong blocks until its close tag too
+pub fn parse_recover(i: Cursor) -> (Vec<SNode>, Vec<ErrorMessage<PError>>) {
+    let (tree, errors) =
+        Lexer::<Kind, Tree>::with_delimiters(Tree::default(), delimiters(i.syntax))
+            .feed_recover(LCursor::_new(i.rest, i.off));
+    (tree.nodes, errors.into_iter().map(message).collect())
+}
+
+fn message(e: LexMessage<PError>) -> ErrorMessage<PError> {
+    ErrorMessage {
+        message: e.message,
+        span: e.span.into(),
+    }
 }
 
-/// Exit of eater with ok in the current cursor
-macro_rules! kill {
-    ($nodes:ident, $c:expr, $i:expr, $len:expr) => {{
-        eat_lit(&mut $nodes, $i, $len);
-        break Ok(($c, $nodes));
-    }};
+/// Lexer delimiters of the template syntax
+fn delimiters(syntax: Syntax) -> Delimiters {
+    let open = syntax.open().as_bytes();
+    let close = syntax.close().as_bytes();
+    Delimiters {
+        open: open[0],
+        close: close[1],
+        open_expr: open[1],
+        close_expr: close[0],
+        open_block: open[1],
+        close_block: close[0],
+        ..Delimiters::of::<Kind>()
+    }
 }
 
-/// Eater builder
-///
-/// $callback: macro for special expressions like {{ else if }}
-macro_rules! make_eater {
-    ($name:ident, $callback:ident) => {
-        pub(crate) fn $name(mut i: Cursor) -> PResult<Vec<SNode>> {
-            let mut buf = vec![];
-            let mut at = 0;
-
-            loop {
-                if let Some(j) = i.adv_find(at, '{') {
-                    macro_rules! _switch {
-                        ($n:expr, $t:expr, $ws:expr) => {
-                            match $n {
-                                b'{' => try_eat!(buf, i, at, j, safe(i.adv(at + j + 3 + $t), $ws)),
-                                b'!' => try_eat!(buf, i, at, j, comment(i.adv(at + j + 3 + $t))),
-                                b'?' => try_eat!(buf, i, at, j, res(i.adv(at + j + 3 + $t), $ws)),
-                                b'#' => try_eat!(buf, i, at, j, hel(i.adv(at + j + 3 + $t), $ws)),
-                                b'>' => try_eat!(buf, i, at, j, par(i.adv(at + j + 3 + $t), $ws)),
-                                b'R' => try_eat!(buf, i, at, j, raw(i.adv(at + j + 3 + $t), $ws)),
-                                b'$' => try_eat!(buf, i, at, j, error(i.adv(at + j + 3 + $t))),
-                                b'/' => kill!(buf, i.adv(at + j + 2), i, at + j),
-                                _ => {
-                                    $callback!(buf, i, at, j, $t, $ws);
-                                    try_eat!(buf, i, at, j, expr(i.adv(at + j + 2 + $t), $ws))
-                                }
-                            }
-                        };
+/// Kind of yarte tags, the rest of the tag is lexed as a expression list
+#[derive(Debug, PartialEq, Clone)]
+enum Kind<'a> {
+    /// `{{#name args }}`
+    Open(LSStr<'a>),
+    /// `{{#> path args }}`
+    OpenPartial(LSStr<'a>),
+    /// `{{/name }}`
+    Close(LSStr<'a>),
+    /// `{{ else if args }}`
+    ElseIf,
+    /// `{{ else }}`
+    Else,
+    /// `{{> path args }}`
+    Partial(LSStr<'a>),
+    /// `{{> @partial-block }}`
+    Block,
+    /// `{{? expr }}`
+    RExpr,
+    /// `{{$ args }}`
+    Error,
+    /// `{{ @name args }}`
+    AtHelper(AtHelperKind),
+}
+
+impl<'a> Kinder<'a> for Kind<'a> {
+    type Error = PError;
+    const OPEN: u8 = b'{';
+    const CLOSE: u8 = b'}';
+    const OPEN_EXPR: u8 = b'{';
+    const CLOSE_EXPR: u8 = b'}';
+    const OPEN_BLOCK: u8 = b'{';
+    const CLOSE_BLOCK: u8 = b'}';
+    const WS: u8 = b'~';
+    const WS_AFTER: bool = true;
+
+    fn parse(i: LCursor<'a>) -> LResult<'a, Self, PError> {
+        match i.as_bytes().first() {
+            Some(b'#') if i.adv_next_is(1, b'>') => {
+                spanned(skip_ws::<PError>(i.adv(2)), path).map(|(c, p)| (c, Kind::OpenPartial(p)))
+            }
+            Some(b'#') => {
+                spanned(skip_ws::<PError>(i.adv(1)), identifier).map(|(c, n)| (c, Kind::Open(n)))
+            }
+            Some(b'/') => {
+                spanned(skip_ws::<PError>(i.adv(1)), path).map(|(c, n)| (c, Kind::Close(n)))
+            }
+            Some(b'>') => {
+                let c = skip_ws::<PError>(i.adv(1));
+                match tag::<PError>(c, PARTIAL_BLOCK) {
+                    Ok((c, _)) => Ok((c, Kind::Block)),
+                    Err(_) => spanned(c, path).map(|(c, p)| (c, Kind::Partial(p))),
+                }
+            }
+            Some(b'?') => Ok((i.adv(1), Kind::RExpr)),
+            Some(b'$') => Ok((i.adv(1), Kind::Error)),
+            _ => {
+                let c = skip_ws::<PError>(i);
+                if let Ok((c, _)) = keyword(c, ELSE) {
+                    match keyword(skip_ws::<PError>(c), IF) {
+                        Ok((c, _)) => Ok((c, Kind::ElseIf)),
+                        Err(_) => Ok((c, Kind::Else)),
+                    }
+                } else if let Ok((c, _)) = tac::<PError>(c, b'@') {
+                    let (c, name) = spanned(c, identifier)?;
+                    match *name.t() {
+                        JSON => Ok((c, Kind::AtHelper(AtHelperKind::Json))),
+                        JSON_PRETTY => Ok((c, Kind::AtHelper(AtHelperKind::JsonPretty))),
+                        SANITIZE => Ok((c, Kind::AtHelper(AtHelperKind::Sanitize))),
+                        _ => Err(LexError::Fail(PError::AtHelperNotExist, name.span())),
                     }
-
-                    let n = &i.rest[at + j + 1..].as_bytes();
-                    if 2 < n.len() {
-                        at = if n[0] == b'{' {
-                            if n[1] == b'~' {
-                                _switch!(n[2], 1, true)
-                            } else {
-                                _switch!(n[1], 0, false)
-                            }
-                        } else {
-                            // next
-                            at + j + 1
-                        }
-                    } else {
-                        at += j + 1;
-                    };
                 } else {
-                    kill!(buf, i.adv(i.len()), i, i.len());
+                    Err(LexError::Next(PError::Tag, LSpan::from(i)))
                 }
             }
         }
-    };
-}
-
-// Empty macro for use when build eater
-macro_rules! non {
-    ($($t:tt)*) => {};
-}
+    }
 
-// Main eater
-make_eater!(eat, non);
+    /// Eat comment `{{! .. !}}` or `{{!-- .. --!}}`
+    fn comment(i: LCursor<'a>, d: Delimiters) -> LResult<'a, &'a str, PError> {
+        let c = tac::<PError>(i, d.ws).map_or(i, |(c, _)| c);
+        let (c, _) = tac::<PError>(c, b'!')?;
+        let (c, dashes) = if c.starts_with("--") {
+            (c.adv(2), "--")
+        } else {
+            (c, "")
+        };
 
-const IF: &str = "if";
-const ELSE: &str = "else";
+        let close = [b'!', d.close_expr, d.close];
+        let mut at = 0;
+        loop {
+            if let Some(j) = c.adv_find(at, b'!') {
+                if c.adv_starts_with_bytes(at + j, &close) && c.rest[..at + j].ends_with(dashes) {
+                    break Ok((
+                        c.adv(at + j + close.len()),
+                        &c.rest[..at + j - dashes.len()],
+                    ));
+                } else {
+                    at += j + 1;
+                }
+            } else {
+                break Err(LexError::Next(PError::Comment, LSpan::from_cursor(i, c)));
+            }
+        }
+    }
 
-// Test special expression `{{ else ..` and kill eater at next brackets
-macro_rules! is_else {
-    ($buf:ident, $i:ident, $at:ident, $j:ident, $t:expr, $ws:expr) => {
-        if skip_ws($i.adv($at + $j + 2 + $t)).starts_with(ELSE) {
-            kill!($buf, $i.adv($at + $j + 2), $i, $at + $j);
+    /// Eat raw `{{R}} .. {{/R}}`
+    fn raw(i: LCursor<'a>, d: Delimiters) -> LResult<'a, ((Ws, Ws), LSStr<'a>), PError> {
+        let (c, a_lws) = ws_control(i, d);
+        let (c, _) = tag::<PError>(c, RAW)?;
+        let (c, a_rws) = end_tag(c, d)?;
+
+        let mut at = 0;
+        loop {
+            if let Some(j) = c.adv_find(at, d.open) {
+                let n = c.adv(at + j + 1);
+                if n.next_is(d.open_expr) {
+                    let (n, b_lws) = ws_control(n.adv(1), d);
+                    if let Ok((n, b_rws)) =
+                        tag::<PError>(n, CLOSE_RAW).and_then(|(n, _)| end_tag(n, d))
+                    {
+                        let src = LS(&c.rest[..at + j], LSpan::from_len(c, at + j));
+                        break Ok((n, (((a_lws, a_rws), (b_lws, b_rws)), src)));
+                    }
+                }
+                at += j + 1;
+            } else {
+                break Err(LexError::Fail(PError::Raw, LSpan::from(c)));
+            }
         }
-    };
+    }
 }
 
-// If else branch eater
-make_eater!(eat_if, is_else);
-
-const PARTIAL_BLOCK: &str = "@partial-block";
-pub(crate) fn expr_partial_block(c: Cursor, lws: bool) -> PResult<Node> {
-    do_parse!(
-        c,
-        ws >> tag!(PARTIAL_BLOCK) >> rws: end_expr >> (Node::Block((lws, rws)))
-    )
+/// Eat optional whitespace control
+fn ws_control(i: LCursor, d: Delimiters) -> (LCursor, bool) {
+    match tac::<PError>(i, d.ws) {
+        Ok((c, _)) => (c, true),
+        Err(_) => (i, false),
+    }
 }
 
-/// Push literal at cursor with length
-fn eat_lit<'a>(nodes: &mut Vec<SNode<'a>>, i: Cursor<'a>, len: usize) {
-    let lit = &i.rest[..len];
-    if !lit.is_empty() {
-        let (l, lit, r) = trim(lit);
-        let ins = Span {
-            lo: i.off + (l.len() as u32),
-            hi: i.off + ((len - r.len()) as u32),
-        };
-        let out = Span {
-            lo: i.off,
-            hi: i.off + (len as u32),
-        };
-        nodes.push(S(Node::Lit(l, S(lit, ins), r), out));
+/// Eat close delimiter `.. }}` or `.. ~}}` returning its whitespace control
+fn end_tag(i: LCursor, d: Delimiters) -> LResult<bool, PError> {
+    let (c, ws) = ws_control(skip_ws::<PError>(i), d);
+    if c.adv_starts_with_bytes(0, &[d.close_expr, d.close]) {
+        Ok((c.adv(2), ws))
+    } else {
+        Err(LexError::Next(PError::EndExpression, LSpan::from(i)))
     }
 }
 
-/// Eat comment
-pub(crate) fn comment(i: Cursor) -> PResult<Node> {
-    let (c, expected) = if i.starts_with("--") {
-        (i.adv(2), "--!}}")
+/// Eat keyword not followed by identifier characters
+fn keyword<'a>(i: LCursor<'a>, k: &'static str) -> LResult<'a, &'static str, PError> {
+    let (c, k) = tag::<PError>(i, k)?;
+    if c.rest.chars().next().is_some_and(is_ident_continue) {
+        Err(LexError::Next(PError::Tag, LSpan::from(i)))
     } else {
-        (i, "!}}")
-    };
-
-    let ch = expected.chars().next().unwrap();
-    let rest = &expected[1..];
-    let mut at = 0;
-    loop {
-        if let Some(j) = c.adv_find(at, ch) {
-            if c.adv_starts_with(at + j + 1, rest) {
-                break Ok((
-                    c.adv(at + j + expected.len()),
-                    Node::Comment(&c.rest[..at + j]),
-                ));
-            } else {
-                at += j + 1;
-            }
-        } else {
-            break Err(LexError::Next(PError::Comment, Span::from_cursor(i, c)));
-        }
+        Ok((c, k))
     }
 }
 
-fn res(c: Cursor, lws: bool) -> PResult<Node> {
-    do_parse!(
-        c,
-        ws >> expr: arguments >> rws: end_expr >> (Node::RExpr((lws, rws), expr))
-    )
+/// Open block waiting for its close tag
+enum Open<'a> {
+    Helper(Ws, LSStr<'a>, SExpr),
+    If(Ws, SExpr, IfElse<'a>),
+    PartialBlock(Ws, LSStr<'a>, SVExpr),
+    /// Wrong open tag, the block is an error
+    Error(LSStr<'a>),
 }
 
-/// Wrap Partial into the Node
-#[inline]
-fn par(i: Cursor, lws: bool) -> PResult<Node> {
-    match expr_partial_block(i, lws) {
-        Ok(x) => Ok(x),
-        Err(_) => partial(i, lws).map(|(c, p)| (c, Node::Partial(p))),
-    }
+/// Finished branches of an if helper
+#[derive(Default)]
+struct IfElse<'a> {
+    first: Option<Vec<SNode<'a>>>,
+    ifs: Vec<(Ws, SExpr, Vec<SNode<'a>>)>,
+    tail: Option<Ws>,
 }
 
-/// Eat partial expression
-#[inline]
-pub(crate) fn partial(i: Cursor, lws: bool) -> PResult<Partial> {
-    do_parse!(
-        i,
-        ws >> ident: call!(spanned, path)
-            >> args: args_list
-            >> rws: end_expr
-            >> (Partial((lws, rws), ident, args))
-    )
+/// Open block with the span of its open tag and the nodes of its current branch
+struct Frame<'a> {
+    open: Open<'a>,
+    span: LSpan,
+    nodes: Vec<SNode<'a>>,
 }
 
-fn partial_block(i: Cursor, a_lws: bool) -> PResult<PartialBlock> {
-    let (c, (ws, ident, args, block, c_ident)) = do_parse!(
-        i,
-        ws >> ident: call!(spanned, path)
-            >> args: args_list
-            >> a_rws: end_expr
-            >> block: eat
-            >> lws: opt!(tag!("~"))
-            >> tag!("/")
-            >> ws
-            >> c_ident: call!(spanned, path)
-            >> rws: end_expr
-            >> (
-                ((a_lws, a_rws), (lws.is_some(), rws)),
-                ident,
-                args,
-                block,
-                c_ident
-            )
-    )?;
+/// Sink building the node tree
+#[derive(Default)]
+struct Tree<'a> {
+    stack: Vec<Frame<'a>>,
+    nodes: Vec<SNode<'a>>,
+}
 
-    if ident.t() == c_ident.t() {
-        Ok((c, PartialBlock(ws, ident, args, block)))
-    } else {
-        Err(LexError::Fail(
-            PError::PartialBlock,
-            Span::from_cursor(i, c),
-        ))
-    }
+fn error<O>(message: PError, span: LSpan) -> LexResult<PError, O> {
+    Err(LexMessage { message, span })
 }
 
-/// Eat helper Node
-pub(crate) fn hel(i: Cursor, a_lws: bool) -> PResult<Node> {
-    if i.starts_with(">") {
-        return partial_block(i.adv(1), a_lws).map(|(c, x)| (c, Node::PartialBlock(x)));
-    }
+fn expr<E: Into<syn::Expr>>(e: E) -> Expr {
+    Expr(e.into())
+}
+
+fn local<L: Into<syn::Local>>(l: L) -> Local {
+    Local(l.into())
+}
 
-    let (i, (above_ws, ident, args)) = do_parse!(
-        i,
-        ws >> ident: call!(spanned, identifier)
-            >> args: arguments
-            >> rws: end_expr
-            >> (((a_lws, rws), ident, args))
-    )?;
+fn sstr(s: LSStr) -> SStr {
+    S(s.0, s.1.into())
+}
+
+fn svexpr(args: yarte_lexer::SVExpr) -> SVExpr {
+    S(args.0.into_iter().map(expr).collect(), args.1.into())
+}
 
-    if ident.0.eq("if") {
-        return if_else(above_ws, i, args);
+/// Only one expression
+fn single(args: yarte_lexer::SVExpr, message: fn(DOption) -> PError) -> LexResult<PError, SExpr> {
+    let LS(mut args, span) = args;
+    match args.len() {
+        1 => Ok(S(Box::new(expr(args.remove(0))), span.into())),
+        0 => error(message(DOption::Some("expected expression".into())), span),
+        _ => error(
+            message(DOption::Some("expected only one expression".into())),
+            span,
+        ),
     }
+}
 
-    let (c, (below_ws, block, c_ident)) = do_parse!(
-        i,
-        block: eat
-            >> lws: opt!(tag!("~"))
-            >> tag!("/")
-            >> ws
-            >> c_ident: call!(spanned, identifier)
-            >> rws: end_expr
-            >> (((lws.is_some(), rws), block, c_ident))
-    )?;
-
-    if ident.0.eq(c_ident.0) {
-        Ok((
-            c,
-            Node::Helper(Box::new({
-                match ident.0 {
-                    "each" => Helper::Each((above_ws, below_ws), args, block),
-                    "with" => Helper::With((above_ws, below_ws), args, block),
-                    "unless" => Helper::Unless((above_ws, below_ws), args, block),
-                    defined => Helper::Defined((above_ws, below_ws), defined, args, block),
-                }
-            })),
-        ))
+/// Without arguments
+fn empty(args: &yarte_lexer::SVExpr) -> LexResult<PError> {
+    if args.t().is_empty() {
+        Ok(())
     } else {
-        Err(LexError::Fail(PError::Helpers, Span::from_cursor(i, c)))
+        error(PError::EndExpression, args.span())
     }
 }
 
-/// Eat if else Node
-#[inline]
-pub(crate) fn if_else(abode_ws: Ws, i: Cursor, args: SExpr) -> PResult<Node> {
-    let mut nodes = vec![];
-    let mut tail = None;
-
-    let (mut i, first) = eat_if(i)?;
-
-    loop {
-        if let Ok((c, lws)) = do_parse!(
-            i,
-            lws: opt!(tag!("~")) >> ws >> tag!(ELSE) >> (lws.is_some())
-        ) {
-            if let Ok((c, _)) = tag!(skip_ws(c), IF) {
-                let (c, b) = map_fail!(do_parse!(
-                    c,
-                    ws >> args: arguments
-                        >> rws: end_expr
-                        >> block: eat_if
-                        >> (((lws, rws), args, block))
-                ))?;
-                nodes.push(b);
-                i = c;
-            } else {
-                let (c, b) = map_fail!(do_parse!(
-                    c,
-                    rws: end_expr >> block: eat >> (((lws, rws), block))
-                ))?;
-                tail = Some(b);
-                i = c;
-            }
-        } else if let Ok((c, lws)) = do_parse!(
-            i,
-            lws: opt!(tag!("~")) >> tag!("/") >> ws >> tag!(IF) >> (lws.is_some())
-        ) {
-            let (c, rws) = end_expr(c)?;
-
-            break Ok((
-                c,
-                Node::Helper(Box::new(Helper::If(
-                    ((abode_ws, (lws, rws)), args, first),
-                    nodes,
-                    tail,
-                ))),
-            ));
-        } else {
-            break Err(LexError::Fail(PError::IfElse, Span::from(i)));
+impl<'a> Tree<'a> {
+    /// Nodes of the current branch
+    fn nodes(&mut self) -> &mut Vec<SNode<'a>> {
+        match self.stack.last_mut() {
+            Some(frame) => &mut frame.nodes,
+            None => &mut self.nodes,
         }
     }
-}
 
-/// Eat error Node
-fn error(i: Cursor) -> PResult<Node> {
-    do_parse!(i, ws >> args: args_list >> end_expr >> (Node::Error(args)))
-}
+    fn push(&mut self, node: Node<'a>, span: LSpan) -> LexResult<PError> {
+        self.nodes().push(S(node, span.into()));
+        Ok(())
+    }
 
-/// Eat raw Node
-pub(crate) fn raw(i: Cursor, a_lws: bool) -> PResult<Node> {
-    let (i, a_rws) = end_expr(i)?;
-    let mut at = 0;
-
-    let (c, (j, b_ws)) = loop {
-        if let Some(j) = i.adv_find(at, '{') {
-            let n = i.adv(at + j + 1);
-            if n.chars().next().map(|x| '{' == x).unwrap_or(false) {
-                if let Ok((c, ws)) = do_parse!(
-                    n.adv(1),
-                    lws: opt!(tag!("~")) >> tag!("/R") >> rws: end_expr >> ((lws.is_some(), rws))
-                ) {
-                    break (c, (&i.rest[..at + j], ws));
-                } else {
-                    at += j + 4;
+    fn open(&mut self, open: Open<'a>, span: LSpan) -> LexResult<PError> {
+        self.stack.push(Frame {
+            open,
+            span,
+            nodes: vec![],
+        });
+        Ok(())
+    }
+
+    /// Start `else if` branch with condition or `else` branch without
+    fn branch(&mut self, ws: Ws, cond: Option<SExpr>, span: LSpan) -> LexResult<PError> {
+        match self.stack.last_mut() {
+            Some(Frame {
+                open: Open::If(_, _, branches),
+                nodes,
+                ..
+            }) if branches.tail.is_none() => {
+                let nodes = mem::take(nodes);
+                match branches.ifs.last_mut() {
+                    Some(last) => last.2 = nodes,
+                    None => branches.first = Some(nodes),
                 }
-            } else {
-                at += j + 1;
+                match cond {
+                    Some(cond) => branches.ifs.push((ws, cond, vec![])),
+                    None => branches.tail = Some(ws),
+                }
+                Ok(())
             }
-        } else {
-            return Err(LexError::Fail(PError::Raw, Span::from(i)));
+            Some(Frame {
+                open: Open::Error(_),
+                ..
+            }) => Ok(()),
+            _ => error(PError::IfElse, span),
         }
-    };
-
-    let (l, v, r) = trim(j);
-    let lo = i.off + (l.len() as u32);
-    let hi = lo + (v.len() as u32);
-    Ok((
-        c,
-        Node::Raw(((a_lws, a_rws), b_ws), l, S(v, Span { lo, hi }), r),
-    ))
-}
-
-/// Arguments builder
-macro_rules! make_argument {
-    ($name:ident, $fun:ident, $ret:ty) => {
-        fn $name(i: Cursor) -> $ret {
-            let mut at = 0;
-            loop {
-                if let Some(j) = i.adv_find(at, '}') {
-                    macro_rules! cl {
-                        ($s:ident, $d:expr) => {
-                            $fun($s)
-                                .map(|x| {
-                                    (
-                                        i.adv(at + j - $d),
-                                        S(x, Span::from_len(skip_ws(i), $s.len())),
-                                    )
-                                })
-                                .map_err(|e| {
-                                    LexError::Fail(
-                                        PError::Argument(DOption::Some(e.message)),
-                                        Span::from_range(skip_ws(i), e.span),
-                                    )
-                                })
-                        };
-                    }
-                    if 0 < j && i.adv_starts_with(at + j - 1, "~}}") {
-                        let (_, s, _) = trim(&i.rest[..j - 1]);
-                        break cl!(s, 1);
-                    } else if i.adv_starts_with(j + 1, "}") {
-                        let (_, s, _) = trim(&i.rest[..j]);
-                        break cl!(s, 0);
-                    }
+    }
 
-                    at += j + 1;
-                } else {
-                    break Err(LexError::Next(
-                        PError::EndExpression,
-                        Span::from_cursor(i, i),
-                    ));
+    fn close(&mut self, ws: Ws, name: LSStr<'a>, span: LSpan) -> LexResult<PError> {
+        let (matches, message, lo) = match self.stack.last() {
+            Some(Frame { open, span, .. }) => match open {
+                Open::Helper(_, open, _) => (open.t() == name.t(), PError::Helpers, span.lo),
+                Open::If(..) => (*name.t() == IF, PError::IfElse, span.lo),
+                Open::PartialBlock(_, open, _) => {
+                    (open.t() == name.t(), PError::PartialBlock, span.lo)
                 }
+                Open::Error(open) => (open.t() == name.t(), PError::Helpers, span.lo),
+            },
+            None => {
+                let lo = span.lo + 2 + u32::from(ws.0);
+                return error(PError::Uncompleted, LSpan::new(lo, lo + 1));
             }
+        };
+        if !matches {
+            return error(message, LSpan::new(lo, span.hi));
         }
-    };
-}
 
-// Eat arguments at helpers
-make_argument!(arguments, eat_expr, PResult<SExpr>);
-
-// Eat arguments at partials
-make_argument!(args_list, eat_expr_list, PResult<SVExpr>);
-
-/// Eat safe Node
-fn safe(i: Cursor, lws: bool) -> PResult<Node> {
-    let mut at = 0;
-    let (c, rws, s) = loop {
-        if let Some(j) = i.adv_find(at, '}') {
-            let n = &i.rest[at + j + 1..];
-            if n.starts_with("~}}") {
-                break (i.adv(at + j + 4), true, &i.rest[..at + j]);
-            } else if n.starts_with("}}") {
-                break (i.adv(at + j + 3), false, &i.rest[..at + j]);
+        let Frame {
+            open,
+            span: open_span,
+            nodes,
+        } = self.stack.pop().expect("open block");
+
+        let node = match open {
+            Open::Helper(a_ws, open, args) => Node::Helper(Box::new(match *open.t() {
+                "each" => Helper::Each((a_ws, ws), args, nodes),
+                "with" => Helper::With((a_ws, ws), args, nodes),
+                "unless" => Helper::Unless((a_ws, ws), args, nodes),
+                defined => Helper::Defined((a_ws, ws), defined, args, nodes),
+            })),
+            Open::If(
+                a_ws,
+                args,
+                IfElse {
+                    first,
+                    mut ifs,
+                    tail,
+                },
+            ) => {
+                let (first, tail) = match (first, tail) {
+                    (None, _) => (nodes, None),
+                    (Some(first), Some(t_ws)) => (first, Some((t_ws, nodes))),
+                    (Some(first), None) => {
+                        if let Some(last) = ifs.last_mut() {
+                            last.2 = nodes;
+                        }
+                        (first, None)
+                    }
+                };
+                Node::Helper(Box::new(Helper::If(((a_ws, ws), args, first), ifs, tail)))
             }
-
-            at += j + 1;
-        } else {
-            return Err(LexError::Next(PError::Safe(DOption::None), Span::from(i)));
-        }
-    };
-
-    let (_, s, _) = trim(s);
-    eat_expr(s)
-        .map(|e| {
-            (
-                c,
-                Node::Safe((lws, rws), S(e, Span::from_len(skip_ws(i), s.len()))),
-            )
-        })
-        .map_err(|e| {
-            LexError::Fail(
-                PError::Safe(DOption::Some(e.message)),
-                Span::from_range(skip_ws(i), e.span),
-            )
-        })
-}
-
-#[inline]
-fn at_helper(i: Cursor, lws: bool) -> PResult<Node> {
-    let (c, (name, args, rws)) = do_parse!(
-        i,
-        ws >> tag!("@")
-            >> name: call!(spanned, identifier)
-            >> args: args_list
-            >> rws: end_expr
-            >> ((name, args, rws))
-    )?;
-
-    macro_rules! check_args_len {
-        ($len:expr) => {
-            if args.t().len() != $len {
-                return Err(LexError::Fail(PError::AtHelperArgsLen($len), args.span()));
+            Open::PartialBlock(a_ws, open, args) => {
+                Node::PartialBlock(PartialBlock((a_ws, ws), sstr(open), args, nodes))
             }
+            Open::Error(_) => Node::Invalid,
         };
-    }
-    match *name.t() {
-        JSON => {
-            check_args_len!(1);
-            Ok((c, Node::AtHelper((lws, rws), AtHelperKind::Json, args)))
-        }
-        JSON_PRETTY => {
-            check_args_len!(1);
-            Ok((
-                c,
-                Node::AtHelper((lws, rws), AtHelperKind::JsonPretty, args),
-            ))
-        }
-        _ => Err(LexError::Fail(PError::AtHelperNotExist, name.span())),
+
+        self.push(node, LSpan::new(open_span.lo, span.hi))
     }
 }
 
-/// Eat expression Node
-fn expr(i: Cursor, lws: bool) -> PResult<Node> {
-    match at_helper(i, lws) {
-        Ok(x) => return Ok(x),
-        Err(e @ LexError::Fail(..)) => return Err(e),
-        _ => (),
+impl<'a> Sink<'a, Kind<'a>> for Tree<'a> {
+    fn arm(&mut self, _: Ws, arm: yarte_lexer::SArm, _: LSpan) -> LexResult<PError> {
+        error(PError::Expr(DOption::None), arm.span())
     }
 
-    let mut at = 0;
-    let (c, rws, s) = loop {
-        if let Some(j) = i.adv_find(at, '}') {
-            if 0 < at + j && i.adv_starts_with(at + j - 1, "~}}") {
-                break (i.adv(at + j + 2), true, &i.rest[..at + j - 1]);
-            } else if i.adv_starts_with(at + j + 1, "}") {
-                break (i.adv(at + j + 2), false, &i.rest[..at + j]);
-            }
+    fn arm_kind(
+        &mut self,
+        _: Ws,
+        _: Kind<'a>,
+        arm: yarte_lexer::SArm,
+        _: LSpan,
+    ) -> LexResult<PError> {
+        error(PError::Expr(DOption::None), arm.span())
+    }
 
-            at += j + 1;
-        } else {
-            return Err(LexError::Next(
-                PError::Expr(DOption::None),
-                Span::from_cursor(i, i.adv(at)),
-            ));
-        }
-    };
+    fn block(&mut self, ws: Ws, args: yarte_lexer::SVExpr, span: LSpan) -> LexResult<PError> {
+        self.expr(ws, args, span)
+    }
 
-    let (_, s, _) = trim(s);
-    macro_rules! s {
-        () => {
-            Span::from_len(skip_ws(i), s.len())
-        };
+    fn block_kind(
+        &mut self,
+        ws: Ws,
+        kind: Kind<'a>,
+        args: yarte_lexer::SVExpr,
+        span: LSpan,
+    ) -> LexResult<PError> {
+        self.expr_kind(ws, kind, args, span)
     }
-    if s.starts_with("let ") {
-        eat_local(s)
-            .map(|e| (c, Node::Local(S(e, s!()))))
-            .map_err(|e| {
-                LexError::Fail(
-                    PError::Local(DOption::Some(e.message)),
-                    Span::from_range(skip_ws(i), e.span),
-                )
-            })
-    } else {
-        eat_expr(s)
-            .map(|e| (c, Node::Expr((lws, rws), S(e, s!()))))
-            .map_err(|e| {
-                LexError::Fail(
-                    PError::Expr(DOption::Some(e.message)),
-                    Span::from_range(skip_ws(i), e.span),
-                )
-            })
+
+    fn comment(&mut self, src: &'a str, span: LSpan) -> LexResult<PError> {
+        self.push(Node::Comment(src), span)
     }
-}
 
-/// Intermediate error representation
-#[derive(Debug)]
-pub(crate) struct MiddleError {
-    pub message: String,
-    pub span: (usize, usize),
-}
+    fn expr(&mut self, ws: Ws, args: yarte_lexer::SVExpr, span: LSpan) -> LexResult<PError> {
+        let expr = single(args, PError::Expr)?;
+        self.push(Node::Expr(ws, expr), span)
+    }
 
-fn get_line_offset(src: &str, line_num: usize) -> usize {
-    assert!(1 < line_num);
-    let mut line_num = line_num - 1;
-    let mut prev = 0;
-    while let Some(len) = src[prev..].find('\n') {
-        prev += len + 1;
-        line_num -= 1;
-        if line_num == 0 {
-            break;
+    fn expr_kind(
+        &mut self,
+        ws: Ws,
+        kind: Kind<'a>,
+        args: yarte_lexer::SVExpr,
+        span: LSpan,
+    ) -> LexResult<PError> {
+        match kind {
+            Kind::Open(name) => {
+                let args = single(args, PError::Argument)?;
+                if *name.t() == IF {
+                    self.open(Open::If(ws, args, IfElse::default()), span)
+                } else {
+                    self.open(Open::Helper(ws, name, args), span)
+                }
+            }
+            Kind::OpenPartial(path) => self.open(Open::PartialBlock(ws, path, svexpr(args)), span),
+            Kind::Close(name) => {
+                empty(&args)?;
+                self.close(ws, name, span)
+            }
+            Kind::ElseIf => {
+                let cond = single(args, PError::Argument)?;
+                self.branch(ws, Some(cond), span)
+            }
+            Kind::Else => {
+                empty(&args)?;
+                self.branch(ws, None, span)
+            }
+            Kind::Partial(path) => {
+                self.push(Node::Partial(Partial(ws, sstr(path), svexpr(args))), span)
+            }
+            Kind::Block => {
+                empty(&args)?;
+                self.push(Node::Block(ws), span)
+            }
+            Kind::RExpr => {
+                let expr = single(args, PError::Expr)?;
+                self.push(Node::RExpr(ws, expr), span)
+            }
+            Kind::Error => self.push(Node::Error(svexpr(args)), span),
+            Kind::AtHelper(kind) => {
+                if args.t().len() != 1 {
+                    return error(PError::AtHelperArgsLen(1), args.span());
+                }
+                self.push(Node::AtHelper(ws, kind, svexpr(args)), span)
+            }
         }
     }
-    assert_eq!(line_num, 0);
 
-    prev
-}
+    fn lit(&mut self, left: &'a str, src: LSStr<'a>, right: &'a str, span: LSpan) {
+        self.nodes()
+            .push(S(Node::Lit(left, sstr(src), right), span.into()));
+    }
 
-impl MiddleError {
-    fn new(src: &str, e: syn::Error) -> Self {
-        let start = e.span().start();
-        let end = e.span().end();
-        let lo = if start.line == 1 {
-            start.column
-        } else {
-            get_line_offset(src, start.line) + start.column
-        };
-        let hi = if end.line == 1 {
-            end.column
-        } else {
-            get_line_offset(src, end.line) + end.column
-        };
-        Self {
-            message: e.to_string(),
-            span: (lo, hi),
-        }
+    fn local(&mut self, _: Ws, l: yarte_lexer::SLocal, span: LSpan) -> LexResult<PError> {
+        let LS(l, l_span) = l;
+        self.push(Node::Local(S(Box::new(local(*l)), l_span.into())), span)
     }
-}
 
-/// Parse syn expression
-fn eat_expr(i: &str) -> Result<Box<crate::Expr>, MiddleError> {
-    parse_str::<Expr>(i)
-        .map(Box::new)
-        .map_err(|e| MiddleError::new(i, e))
-}
+    fn raw(
+        &mut self,
+        ws: (Ws, Ws),
+        left: &'a str,
+        src: LSStr<'a>,
+        right: &'a str,
+        span: LSpan,
+    ) -> LexResult<PError> {
+        self.push(Node::Raw(ws, left, sstr(src), right), span)
+    }
 
-/// Parse syn local
-fn eat_local(i: &str) -> Result<Box<crate::Local>, MiddleError> {
-    parse_str::<StmtLocal>(i)
-        .map(Into::into)
-        .map(Box::new)
-        .map_err(|e| MiddleError::new(i, e))
-}
+    fn safe(&mut self, ws: Ws, e: yarte_lexer::SExpr, span: LSpan) -> LexResult<PError> {
+        let LS(e, e_span) = e;
+        self.push(Node::Safe(ws, S(Box::new(expr(*e)), e_span.into())), span)
+    }
 
-/// Parse syn expression comma separated list
-pub(crate) fn eat_expr_list(i: &str) -> Result<Vec<crate::Expr>, MiddleError> {
-    parse_str::<ExprList>(i)
-        .map(Into::into)
-        .map_err(|e| MiddleError::new(i, e))
-}
+    fn error(&mut self, kind: Option<Kind<'a>>, span: LSpan) {
+        match kind {
+            Some(Kind::Open(name) | Kind::OpenPartial(name)) => self.stack.push(Frame {
+                open: Open::Error(name),
+                span,
+                nodes: vec![],
+            }),
+            _ => self.nodes().push(S(Node::Invalid, span.into())),
+        }
+    }
 
-/// Eat whitespace flag in end of expressions `.. }}` or `.. ~}}`
-fn end_expr(i: Cursor) -> PResult<bool> {
-    let c = skip_ws(i);
-    if c.starts_with("~}}") {
-        Ok((c.adv(3), true))
-    } else if c.starts_with("}}") {
-        Ok((c.adv(2), false))
-    } else {
-        Err(LexError::Fail(
-            PError::EndExpression,
-            Span::from_cursor(i, c),
-        ))
+    fn end(&mut self) -> LexResult<PError> {
+        // Blocks opened by a wrong tag are already reported
+        let unclosed = self
+            .stack
+            .iter()
+            .rev()
+            .find(|frame| !matches!(frame.open, Open::Error(_)))
+            .map(|frame| frame.span);
+        if let Some(span) = self.stack.first().map(|frame| frame.span) {
+            self.stack.clear();
+            self.nodes.push(S(Node::Invalid, span.into()));
+        }
+        match unclosed {
+            Some(span) => error(PError::Uncompleted, span),
+            None => Ok(()),
+        }
     }
 }
 
@@ -640,57 +573,16 @@ fn is_ident_continue(c: char) -> bool {
 }
 
 /// Eat identifier
-fn identifier(i: Cursor) -> PResult<&str> {
+fn identifier(i: LCursor<'_>) -> LResult<'_, &str, PError> {
     let mut chars = i.chars();
     if chars.next().map(is_ident_start).unwrap_or(false) {
-        if chars.next().map(is_ident_continue).unwrap_or(false) {
-            if let Some(j) = chars.position(|c| !is_ident_continue(c)) {
-                Ok((i.adv(j + 2), &i.rest[..j + 2]))
-            } else {
-                Ok((i.adv(i.len()), i.rest))
-            }
-        } else {
-            Ok((i.adv(1), &i.rest[..1]))
-        }
+        let len = i
+            .rest
+            .char_indices()
+            .find(|(_, c)| !is_ident_continue(*c))
+            .map_or(i.len(), |(j, _)| j);
+        Ok((i.adv(len), &i.rest[..len]))
     } else {
-        Err(LexError::Next(PError::Ident, Span::from(i)))
+        Err(LexError::Next(PError::Ident, LSpan::from(i)))
     }
 }
-
-/// TODO: Define chars in path
-/// Eat path at partial
-/// Next white space close path
-fn path(i: Cursor) -> PResult<&str> {
-    take_while!(i, |i| !is_ws(i)).and_then(|(c, s)| {
-        if s.is_empty() {
-            Err(LexError::Fail(PError::PartialPath, Span::from(c)))
-        } else {
-            Ok((c, s))
-        }
-    })
-}
-
-pub fn trim(i: &str) -> (&str, &str, &str) {
-    if i.is_empty() {
-        return ("", "", "");
-    }
-
-    let b = i.as_bytes();
-
-    if let Some(ln) = b.iter().position(|x| !is_ws((*x).into())) {
-        let rn = b.iter().rposition(|x| !is_ws((*x).into())).unwrap();
-        (
-            safe_utf8(&b[..ln]),
-            safe_utf8(&b[ln..=rn]),
-            safe_utf8(&b[rn + 1..]),
-        )
-    } else {
-        (i, "", "")
-    }
-}
-
-/// Convert from bytes to str
-/// Use when previous check bytes it's valid utf8
-fn safe_utf8(s: &[u8]) -> &str {
-    unsafe { ::std::str::from_utf8_unchecked(s) }
-}
@@ -1,98 +1,54 @@
+use yarte_helpers::config::Syntax;
+
 use crate::{
-    comment,
-    error::PError,
-    expr_partial_block, partial, raw,
-    source_map::Span,
-    strnom::{Cursor, LexError, PResult},
-    ErrorMessage, Partial,
+    error::PError, parse, Cursor, ErrorMessage, Helper, Node, Partial, PartialBlock, SNode,
 };
 
-pub fn parse_partials(rest: &str) -> Result<Vec<Partial>, ErrorMessage<PError>> {
-    let (c, res) = eat_partials(Cursor { rest, off: 0 })?;
-    if c.is_empty() {
-        Ok(res)
-    } else {
-        let end = (rest.len() - c.len()) as u32;
-        Err(ErrorMessage {
-            message: PError::Uncompleted,
-            span: Span { lo: end, hi: end },
-        })
-    }
+/// Partials used by a template source, partial blocks included, without resolve
+pub fn parse_partials(
+    rest: &str,
+    syntax: Syntax,
+) -> Result<Vec<Partial<'_>>, ErrorMessage<PError>> {
+    let mut cursor = Cursor::new(rest, 0);
+    cursor.syntax = syntax;
+    parse(cursor).map(|nodes| partials(&nodes))
 }
 
-fn eat_partials(mut i: Cursor) -> PResult<Vec<Partial>> {
-    let mut nodes = vec![];
+/// Partials used by parsed nodes, in source order
+pub(crate) fn partials<'a>(nodes: &[SNode<'a>]) -> Vec<Partial<'a>> {
+    let mut buf = vec![];
+    collect(nodes, &mut buf);
+    buf
+}
 
-    loop {
-        if let Some(j) = i.find('{') {
-            macro_rules! _switch {
-                ($n:expr, $t:expr, $ws:expr) => {
-                    match $n {
-                        b'>' => {
-                            let i = i.adv(j + 3 + $t);
-                            match partial_block(i, $ws) {
-                                Ok((i, n)) => {
-                                    nodes.push(n);
-                                    i
-                                }
-                                Err(e @ LexError::Fail(..)) => break Err(e),
-                                Err(LexError::Next(..)) => i,
-                            }
-                        }
-                        b'R' => {
-                            let i = i.adv(j + 3 + $t);
-                            match raw(i, $ws) {
-                                Ok((i, _)) => i,
-                                Err(e @ LexError::Fail(..)) => break Err(e),
-                                Err(LexError::Next(..)) => i,
-                            }
-                        }
-                        b'!' => {
-                            let i = i.adv(j + 3);
-                            match comment(i) {
-                                Ok((i, _)) => i,
-                                Err(_) => i,
-                            }
-                        }
-                        b'#' if i.adv(j + 3 + $t).starts_with(">") => {
-                            match partial(i.adv(j + 4 + $t), $ws) {
-                                Ok((i, n)) => {
-                                    nodes.push(n);
-                                    i
-                                }
-                                Err(e @ LexError::Fail(..)) => break Err(e),
-                                Err(LexError::Next(..)) => i.adv(j + 3 + $t),
-                            }
-                        }
-                        _ => i.adv(j + 2 + $t),
-                    }
-                };
+fn collect<'a>(nodes: &[SNode<'a>], buf: &mut Vec<Partial<'a>>) {
+    for node in nodes {
+        match node.t() {
+            Node::Partial(partial) => buf.push(partial.clone()),
+            Node::PartialBlock(PartialBlock((ws, _), ident, args, block)) => {
+                buf.push(Partial(*ws, ident.clone(), args.clone()));
+                collect(block, buf);
             }
-            let n = i.rest[j + 1..].as_bytes();
-            i = if 2 < n.len() && n[0] == b'{' {
-                if n[1] == b'~' {
-                    _switch!(n[2], 1, true)
-                } else {
-                    _switch!(n[1], 0, false)
+            Node::Helper(helper) => match &**helper {
+                Helper::Each(_, _, block)
+                | Helper::With(_, _, block)
+                | Helper::Unless(_, _, block)
+                | Helper::Defined(_, _, _, block) => collect(block, buf),
+                Helper::If((_, _, first), ifs, tail) => {
+                    collect(first, buf);
+                    for (_, _, block) in ifs {
+                        collect(block, buf);
+                    }
+                    if let Some((_, block)) = tail {
+                        collect(block, buf);
+                    }
                 }
-            } else {
-                // next
-                i.adv(j + 1)
-            };
-        } else {
-            break Ok((i.adv(i.len()), nodes));
+            },
+            _ => (),
         }
     }
 }
 
-#[inline]
-fn partial_block(i: Cursor, lws: bool) -> PResult<Partial> {
-    match expr_partial_block(i, lws) {
-        Ok(_) => Err(LexError::Next(PError::PartialBlock, Span::from(i))),
-        Err(_) => partial(i, lws),
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -101,28 +57,28 @@ mod tests {
     #[test]
     fn test_empty() {
         let src = r#""#;
-        assert_eq!(parse_partials(src).unwrap(), vec![]);
+        assert_eq!(parse_partials(src, Syntax::default()).unwrap(), vec![]);
         let src = r#"{{/"#;
-        assert_eq!(parse_partials(src).unwrap(), vec![]);
+        assert_eq!(parse_partials(src, Syntax::default()).unwrap(), vec![]);
         let src = r#"{{"#;
-        assert_eq!(parse_partials(src).unwrap(), vec![]);
+        assert_eq!(parse_partials(src, Syntax::default()).unwrap(), vec![]);
         let src = r#"{"#;
-        assert_eq!(parse_partials(src).unwrap(), vec![]);
+        assert_eq!(parse_partials(src, Syntax::default()).unwrap(), vec![]);
         let src = r#"{{>"#;
-        assert_eq!(parse_partials(src).unwrap(), vec![]);
+        assert_eq!(parse_partials(src, Syntax::default()).unwrap(), vec![]);
         let src = r#"{{>}}"#;
-        assert_eq!(parse_partials(src).unwrap(), vec![]);
+        assert!(parse_partials(src, Syntax::default()).is_err());
         let src = r#"{{! {{> foo }} !}}"#;
-        assert_eq!(parse_partials(src).unwrap(), vec![]);
+        assert_eq!(parse_partials(src, Syntax::default()).unwrap(), vec![]);
         let src = r#"{{R}} {{> foo }} {{/R}}"#;
-        assert_eq!(parse_partials(src).unwrap(), vec![]);
+        assert_eq!(parse_partials(src, Syntax::default()).unwrap(), vec![]);
     }
 
     #[test]
     fn test_partial_block() {
         let src = "{{#> foo }}bar{{/foo }}";
         assert_eq!(
-            parse_partials(src).unwrap(),
+            parse_partials(src, Syntax::default()).unwrap(),
             vec![Partial(
                 (false, false),
                 S("foo", Span { lo: 5, hi: 8 }),
@@ -130,4 +86,18 @@ mod tests {
             )]
         );
     }
+
+    #[test]
+    fn test_delimiters() {
+        let syntax = Syntax::new("<%", "%>").unwrap();
+        assert_eq!(parse_partials("{{> foo }}", syntax).unwrap(), vec![]);
+        assert_eq!(
+            parse_partials("<%> foo %>", syntax).unwrap(),
+            vec![Partial(
+                (false, false),
+                S("foo", Span { lo: 4, hi: 7 }),
+                S(vec![], Span { lo: 8, hi: 8 })
+            )]
+        );
+    }
 }
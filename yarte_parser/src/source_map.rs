@@ -1,32 +1,10 @@
 //! Adapted from [`proc-macro2`](https://github.com/alexcrichton/proc-macro2).
 use std::{
-    cell::RefCell,
     fmt::{self, Debug},
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 
-use crate::strnom::{skip_ws, Cursor, PResult};
-
-thread_local! {
-    static SOURCE_MAP: RefCell<SourceMap> = RefCell::new(Default::default());
-}
-
-/// Add file to source map and return lower bound
-///
-/// Use in the same thread
-pub fn get_cursor<'a>(p: &PathBuf, rest: &'a str) -> Cursor<'a> {
-    SOURCE_MAP.with(|x| Cursor {
-        rest,
-        off: x.borrow_mut().add_file(p, rest).lo,
-    })
-}
-
-/// Reinitialize source map instance when run multiple times in the same thread
-///
-/// Use in the same thread
-pub fn clean() {
-    SOURCE_MAP.with(|x| *x.borrow_mut() = Default::default());
-}
+use crate::cursor::Cursor;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct LineColumn {
@@ -34,10 +12,12 @@ pub struct LineColumn {
     pub column: usize,
 }
 
+#[derive(Debug)]
 struct FileInfo {
     name: PathBuf,
     span: Span,
     lines: Vec<usize>,
+    src: String,
 }
 
 impl FileInfo {
@@ -73,7 +53,7 @@ impl FileInfo {
                 .lines
                 .get(idx)
                 .copied()
-                .unwrap_or(self.span.hi as usize),
+                .unwrap_or((self.span.hi - self.span.lo) as usize),
         };
         ((lo_line, hi_line), (lo - lo_line, hi - lo_line))
     }
@@ -94,29 +74,53 @@ fn lines_offsets(s: &str) -> Vec<usize> {
     lines
 }
 
-#[derive(Default)]
-struct SourceMap {
+/// Template files with disjoint offsets
+///
+/// Spans of the cursors of a source map, even across files, are resolved with it
+#[derive(Debug, Default)]
+pub struct SourceMap {
     files: Vec<FileInfo>,
 }
 
 impl SourceMap {
+    /// Add file to source map and return its cursor
+    pub fn get_cursor<'a>(&mut self, p: &Path, rest: &'a str) -> Cursor<'a> {
+        let lo = self.next_start_pos();
+        Cursor::new(rest, self.add_file(p, rest, lo).lo)
+    }
+
+    /// Add file starting at offset `lo`, unless it's already added, and return its cursor
+    ///
+    /// Offsets of files added by different source maps can be shared, like the ones
+    /// of [`cache`](crate::cache), but the files of a source map must be disjoint
+    pub fn get_cursor_at<'a>(&mut self, p: &Path, rest: &'a str, lo: u32) -> Cursor<'a> {
+        if !self.files.iter().any(|f| f.span.lo == lo && f.name == p) {
+            self.add_file(p, rest, lo);
+        }
+        Cursor::new(rest, lo)
+    }
+
     fn next_start_pos(&self) -> u32 {
         // Add 1 so there's always space between files.
-        self.files.last().map(|f| f.span.hi + 1).unwrap_or(0)
+        self.files.iter().map(|f| f.span.hi + 1).max().unwrap_or(0)
     }
 
-    fn add_file(&mut self, name: &PathBuf, src: &str) -> Span {
+    fn add_file(&mut self, name: &Path, src: &str, lo: u32) -> Span {
         let lines = lines_offsets(src);
-        let lo = self.next_start_pos();
         let span = Span {
             lo,
             hi: lo + (src.len() as u32),
         };
+        debug_assert!(self
+            .files
+            .iter()
+            .all(|f| span.hi < f.span.lo || f.span.hi < span.lo));
 
         self.files.push(FileInfo {
             name: name.to_owned(),
             span,
             lines,
+            src: src.to_owned(),
         });
 
         span
@@ -138,10 +142,6 @@ pub struct Span {
     pub hi: u32,
 }
 
-// Don't allow `Span` to transfer between thread
-// impl !Send for Span {}
-// impl !Sync for Span {}
-
 impl Span {
     /// Assume a <= b
     #[inline]
@@ -167,55 +167,33 @@ impl Span {
         }
     }
 
+    /// Span of `proc` inside the expression starting at `self`
     pub fn join_proc(self, proc: proc_macro2::Span) -> Self {
-        let start = self.start();
-        let p_start = proc.start();
-        let p_end = proc.end();
-        let lo = if p_start.line == 1 {
-            self.lo + p_start.column as u32
-        } else {
-            SOURCE_MAP.with(|cm| {
-                let cm = cm.borrow();
-                let fi = cm.fileinfo(self);
-                fi.lines[start.line + p_start.line - 2] as u32 + p_start.column as u32
-            })
-        };
-        let hi = if p_end.line == 1 {
-            self.lo + p_end.column as u32
-        } else {
-            SOURCE_MAP.with(|cm| {
-                let cm = cm.borrow();
-                let fi = cm.fileinfo(self);
-                fi.lines[start.line + p_end.line - 2] as u32 + p_end.column as u32
-            })
-        };
+        let range = proc.byte_range();
 
-        Self { lo, hi }
+        Self {
+            lo: self.lo + range.start as u32,
+            hi: self.lo + range.end as u32,
+        }
     }
 
     /// Returns line bounds and range in bounds
-    pub fn range_in_file(self) -> ((usize, usize), (usize, usize)) {
-        SOURCE_MAP.with(|cm| {
-            let cm = cm.borrow();
-            let fi = cm.fileinfo(self);
-            fi.get_ranges(self)
-        })
+    pub fn range_in_file(self, sm: &SourceMap) -> ((usize, usize), (usize, usize)) {
+        sm.fileinfo(self).get_ranges(self)
+    }
+
+    pub fn file_path(self, sm: &SourceMap) -> PathBuf {
+        sm.fileinfo(self).name.clone()
     }
 
-    pub fn file_path(self) -> PathBuf {
-        SOURCE_MAP.with(|cm| {
-            let cm = cm.borrow();
-            let fi = cm.fileinfo(self);
-            fi.name.clone()
-        })
+    pub fn start(self, sm: &SourceMap) -> LineColumn {
+        sm.fileinfo(self).offset_line_column(self.lo as usize)
     }
 
-    pub fn start(self) -> LineColumn {
-        SOURCE_MAP.with(|cm| {
-            let cm = cm.borrow();
-            let fi = cm.fileinfo(self);
-            fi.offset_line_column(self.lo as usize)
-        })
+    /// Source of the span
+    pub fn source_text(self, sm: &SourceMap) -> &str {
+        let file = sm.fileinfo(self);
+        &file.src[(self.lo - file.span.lo) as usize..(self.hi - file.span.lo) as usize]
     }
 }
 
@@ -225,6 +203,12 @@ impl<'a> From<Cursor<'a>> for Span {
     }
 }
 
+impl From<yarte_strnom::Span> for Span {
+    fn from(s: yarte_strnom::Span) -> Self {
+        Self { lo: s.lo, hi: s.hi }
+    }
+}
+
 impl fmt::Debug for Span {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "bytes({}..{})", self.lo, self.hi)
@@ -243,14 +227,37 @@ impl<T: Debug + PartialEq + Clone> S<T> {
     }
 }
 
-pub(crate) fn spanned<'a, T: Debug + PartialEq + Clone>(
-    input: Cursor<'a>,
-    f: fn(Cursor<'a>) -> PResult<'a, T>,
-) -> PResult<'a, S<T>> {
-    let input = skip_ws(input);
-    let lo = input.off;
-    let (a, b) = f(input)?;
-    let hi = a.off;
-    let span = Span { lo, hi };
-    Ok((a, S(b, span)))
+#[cfg(test)]
+mod test {
+    use std::{path::Path, thread};
+
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn test_source_maps() {
+        let resolve = |name: &str| {
+            let mut sm = SourceMap::default();
+            let _ = sm.get_cursor(Path::new("a.hbs"), "foo");
+            let nodes = parse(sm.get_cursor(Path::new(name), "bar\n  {{ baz }}")).unwrap();
+            let span = nodes[1].span();
+            (
+                span.file_path(&sm),
+                span.start(&sm),
+                span.range_in_file(&sm),
+            )
+        };
+
+        let (a, b) = thread::scope(|s| {
+            let a = s.spawn(|| resolve("b.hbs"));
+            let b = s.spawn(|| resolve("c.hbs"));
+            (a.join().unwrap(), b.join().unwrap())
+        });
+        assert_eq!(a.0, PathBuf::from("b.hbs"));
+        assert_eq!(b.0, PathBuf::from("c.hbs"));
+        assert_eq!(a.1, LineColumn { line: 2, column: 2 });
+        assert_eq!(a.1, b.1);
+        assert_eq!(a.2, ((4, 15), (2, 11)));
+        assert_eq!(a.2, b.2);
+    }
 }
@@ -1,11 +1,11 @@
 use syn::parse_str;
+use yarte_helpers::config::Syntax;
 
 use crate::{
-    eat_expr_list, eat_if,
     error::{DOption, PError},
-    hel, if_else, parse as _parse,
+    parse as _parse, parse_recover,
     source_map::{Span, S},
-    trim, Cursor, ErrorMessage, Helper,
+    Cursor, ErrorMessage, Helper,
     Node::*,
     Ws,
 };
@@ -21,256 +21,113 @@ macro_rules! bytes {
     };
 }
 
-#[test]
-fn test_trim() {
-    assert_eq!(trim(" a "), (" ", "a", " "));
-    assert_eq!(trim(" a"), (" ", "a", ""));
-    assert_eq!(trim("a"), ("", "a", ""));
-    assert_eq!(trim(""), ("", "", ""));
-    assert_eq!(trim("a "), ("", "a", " "));
-    assert_eq!(trim("a a"), ("", "a a", ""));
-    assert_eq!(trim("a a "), ("", "a a", " "));
-    assert_eq!(trim(" \n\t\ra a "), (" \n\t\r", "a a", " "));
-    assert_eq!(trim(" \n\t\r "), (" \n\t\r ", "", ""));
+fn expr(src: &str, span: Span) -> crate::SExpr {
+    S(Box::new(parse_str::<crate::Expr>(src).unwrap()), span)
+}
+
+fn lit(src: &str, span: Span) -> crate::SNode<'_> {
+    S(Lit("", S(src, span), ""), span)
 }
 
 #[test]
-fn test_eat_if() {
-    let rest = r#"foo{{ else }}"#;
-    let result = " else }}";
-    assert_eq!(
-        eat_if(Cursor { rest, off: 0 }).unwrap(),
-        (
-            Cursor {
-                rest: result,
-                off: (rest.len() - result.len()) as u32,
-            },
-            vec![S(
-                Lit("", S("foo", Span { lo: 0, hi: 3 }), ""),
-                Span { lo: 0, hi: 3 },
-            )]
-        )
-    );
-    let rest = r#"{{foo}}{{else}}"#;
-    let result = "else}}";
-    assert_eq!(
-        eat_if(Cursor { rest, off: 0 }).unwrap(),
-        (
-            Cursor {
-                rest: result,
-                off: (rest.len() - result.len()) as u32,
-            },
-            vec![S(
-                Expr(
-                    WS,
-                    S(
-                        Box::new(parse_str::<crate::Expr>("foo").unwrap()),
-                        Span { lo: 2, hi: 5 },
-                    ),
-                ),
-                Span { lo: 0, hi: 7 },
-            )]
-        )
-    );
-    let rest = r#"{{ let a = foo }}{{else if cond}}{{else}}"#;
+fn test_local() {
+    let rest = r#"{{ let a = foo }}"#;
     let local = parse_str::<crate::Local>("let a = foo").unwrap();
-    let result = "else if cond}}{{else}}";
     assert_eq!(
-        eat_if(Cursor { rest, off: 0 }).unwrap(),
-        (
-            Cursor {
-                rest: result,
-                off: (rest.len() - result.len()) as u32,
-            },
-            vec![S(
-                Local(S(Box::new(local), Span { lo: 3, hi: 14 })),
-                Span { lo: 0, hi: 17 },
-            )]
-        )
+        _parse(Cursor::new(rest, 0)).unwrap(),
+        vec![S(Local(S(Box::new(local), bytes!(3..14))), bytes!(0..17))]
     );
 }
 
 #[test]
 fn test_helpers() {
-    let rest = "each name }}{{first}} {{last}}{{/each}}";
+    let rest = "{{#each name }}{{first}} {{last}}{{/each}}";
     assert_eq!(
-        hel(Cursor { rest, off: 0 }, false).unwrap(),
-        (
-            Cursor {
-                rest: "",
-                off: rest.len() as u32,
-            },
+        _parse(Cursor::new(rest, 0)).unwrap(),
+        vec![S(
             Helper(Box::new(Helper::Each(
                 (WS, WS),
-                S(
-                    Box::new(parse_str::<crate::Expr>("name").unwrap()),
-                    Span { lo: 5, hi: 9 },
-                ),
+                expr("name", bytes!(8..12)),
                 vec![
-                    S(
-                        Expr(
-                            WS,
-                            S(
-                                Box::new(parse_str::<crate::Expr>("first").unwrap()),
-                                Span { lo: 14, hi: 19 },
-                            ),
-                        ),
-                        Span { lo: 12, hi: 21 },
-                    ),
-                    S(
-                        Lit(" ", S("", Span { lo: 22, hi: 22 }), ""),
-                        Span { lo: 21, hi: 22 },
-                    ),
-                    S(
-                        Expr(
-                            WS,
-                            S(
-                                Box::new(parse_str::<crate::Expr>("last").unwrap()),
-                                Span { lo: 24, hi: 28 },
-                            ),
-                        ),
-                        Span { lo: 22, hi: 30 },
-                    ),
+                    S(Expr(WS, expr("first", bytes!(17..22))), bytes!(15..24)),
+                    S(Lit(" ", S("", bytes!(25..25)), ""), bytes!(24..25)),
+                    S(Expr(WS, expr("last", bytes!(27..31))), bytes!(25..33)),
                 ],
-            )))
-        )
+            ))),
+            bytes!(0..42),
+        )]
     );
 }
 
 #[test]
 fn test_if_else() {
-    let rest = "foo{{/if}}";
-    let args = S(
-        Box::new(parse_str::<crate::Expr>("bar").unwrap()),
-        Span { lo: 0, hi: 0 },
-    );
-
+    let rest = "{{#if bar }}foo{{/if}}";
     assert_eq!(
-        if_else(WS, Cursor { rest, off: 0 }, args.clone()).unwrap(),
-        (
-            Cursor {
-                rest: "",
-                off: rest.len() as u32,
-            },
+        _parse(Cursor::new(rest, 0)).unwrap(),
+        vec![S(
             Helper(Box::new(Helper::If(
                 (
                     (WS, WS),
-                    args,
-                    vec![S(
-                        Lit("", S("foo", Span { lo: 0, hi: 3 }), ""),
-                        Span { lo: 0, hi: 3 },
-                    )]
+                    expr("bar", bytes!(6..9)),
+                    vec![lit("foo", bytes!(12..15))]
                 ),
                 vec![],
                 None,
-            )))
-        )
-    );
-
-    let rest = "foo{{else}}bar{{/if}}";
-    let args = S(
-        Box::new(parse_str::<crate::Expr>("bar").unwrap()),
-        Span { lo: 0, hi: 0 },
+            ))),
+            bytes!(0..22),
+        )]
     );
 
+    let rest = "{{#if bar }}foo{{else}}bar{{/if}}";
     assert_eq!(
-        if_else(WS, Cursor { rest, off: 0 }, args.clone()).unwrap(),
-        (
-            Cursor {
-                rest: "",
-                off: rest.len() as u32,
-            },
+        _parse(Cursor::new(rest, 0)).unwrap(),
+        vec![S(
             Helper(Box::new(Helper::If(
                 (
                     (WS, WS),
-                    args,
-                    vec![S(
-                        Lit("", S("foo", Span { lo: 0, hi: 3 }), ""),
-                        Span { lo: 0, hi: 3 },
-                    )]
+                    expr("bar", bytes!(6..9)),
+                    vec![lit("foo", bytes!(12..15))]
                 ),
                 vec![],
-                Some((
-                    WS,
-                    vec![S(
-                        Lit("", S("bar", Span { lo: 11, hi: 14 }), ""),
-                        Span { lo: 11, hi: 14 },
-                    )]
-                )),
-            )))
-        )
+                Some((WS, vec![lit("bar", bytes!(23..26))])),
+            ))),
+            bytes!(0..33),
+        )]
     );
 }
 
 #[test]
 fn test_else_if() {
-    let rest = "foo{{else if cond }}bar{{else}}foO{{/if}}";
-    let args = S(
-        Box::new(parse_str::<crate::Expr>("bar").unwrap()),
-        Span { lo: 0, hi: 0 },
-    );
-
+    let rest = "{{#if bar }}foo{{else if cond }}bar{{else}}foO{{/if}}";
     assert_eq!(
-        if_else(WS, Cursor { rest, off: 0 }, args.clone()).unwrap(),
-        (
-            Cursor {
-                rest: "",
-                off: rest.len() as u32,
-            },
+        _parse(Cursor::new(rest, 0)).unwrap(),
+        vec![S(
             Helper(Box::new(Helper::If(
                 (
                     (WS, WS),
-                    args,
-                    vec![S(
-                        Lit("", S("foo", Span { lo: 0, hi: 3 }), ""),
-                        Span { lo: 0, hi: 3 },
-                    )]
+                    expr("bar", bytes!(6..9)),
+                    vec![lit("foo", bytes!(12..15))]
                 ),
                 vec![(
                     WS,
-                    S(
-                        Box::new(parse_str::<crate::Expr>("cond").unwrap()),
-                        Span { lo: 13, hi: 17 },
-                    ),
-                    vec![S(
-                        Lit("", S("bar", Span { lo: 20, hi: 23 }), ""),
-                        Span { lo: 20, hi: 23 },
-                    )]
+                    expr("cond", bytes!(25..29)),
+                    vec![lit("bar", bytes!(32..35))]
                 )],
-                Some((
-                    WS,
-                    vec![S(
-                        Lit("", S("foO", Span { lo: 31, hi: 34 }), ""),
-                        Span { lo: 31, hi: 34 },
-                    )]
-                )),
-            )))
-        )
-    );
-}
-
-#[test]
-fn test_expr_list() {
-    let src = "bar, foo = \"bar\"\n, fuu = 1  , goo = true,    ";
-    assert_eq!(
-        eat_expr_list(src).unwrap(),
-        vec![
-            parse_str::<crate::Expr>("bar").unwrap(),
-            parse_str::<crate::Expr>("foo=\"bar\"").unwrap(),
-            parse_str::<crate::Expr>("fuu=1").unwrap(),
-            parse_str::<crate::Expr>("goo=true").unwrap(),
-        ]
+                Some((WS, vec![lit("foO", bytes!(43..46))])),
+            ))),
+            bytes!(0..53),
+        )]
     );
 }
 
 fn test_error(rest: &str, _message: PError, _span: Span) {
-    let cursor = Cursor { rest, off: 0 };
+    let cursor = Cursor::new(rest, 0);
     match _parse(cursor) {
         Err(ErrorMessage { message, span }) => {
             if _message != message || _span != span {
                 panic!(
                         "\n\nExpect:\n\tmessage: {:?}\n\tspan: {:?}\n\nResult:\n\tmessage: {:?}\n\tspan: {:?}",
-                        message.to_string(), span, _message.to_string(), _span
+                        _message.to_string(), _span, message.to_string(), span
                     )
             }
         }
@@ -296,7 +153,7 @@ fn test_error_expr() {
 fn test_error_safe() {
     test_error(
         "{{{ @ }}}",
-        PError::Safe(DOption::Some(String::from("expected expression"))),
+        PError::Expr(DOption::Some(String::from("expected expression"))),
         bytes!(4..5),
     );
 }
@@ -310,6 +167,24 @@ fn test_error_expr_multiline() {
     );
 }
 
+#[test]
+fn test_error_expr_unicode_multiline() {
+    test_error(
+        "{{ foo(\n  \"ñ\", a b) }}",
+        PError::Expr(DOption::Some(String::from("expected `,`"))),
+        bytes!(18..19),
+    );
+}
+
+#[test]
+fn test_error_unclosed_delimiter() {
+    test_error(
+        "{{ foo(a }}",
+        PError::Expr(DOption::Some(String::from("unclosed delimiter"))),
+        bytes!(6..7),
+    );
+}
+
 #[test]
 fn test_error_at_helper_not_exist() {
     test_error("{{ @foo }}", PError::AtHelperNotExist, bytes!(4..7));
@@ -323,3 +198,104 @@ fn test_error_at_helper_check_len() {
         bytes!(9..17),
     );
 }
+
+#[test]
+fn test_error_uncompleted() {
+    test_error("{{/o", PError::Uncompleted, bytes!(2..3));
+}
+
+#[test]
+fn test_error_unclosed_helper() {
+    test_error("{{#each foo }}bar", PError::Uncompleted, bytes!(0..14));
+}
+
+#[test]
+fn test_error_mismatched_helper() {
+    test_error("{{#each foo }}bar{{/with}}", PError::Helpers, bytes!(0..26));
+}
+
+#[test]
+fn test_delimiters() {
+    let src = "[[~ a ~]][[[ b ]]][[!-- c --!]][[#each d]][[> e ]][[/each]][[R]] f [[/R]]";
+    let default = src.replace('[', "{").replace(']', "}");
+    let mut cursor = Cursor::new(src, 0);
+    cursor.syntax = Syntax::new("[[", "]]").unwrap();
+    assert_eq!(
+        _parse(cursor).unwrap(),
+        _parse(Cursor::new(&default, 0)).unwrap()
+    );
+
+    let rest = "<p>{{ vue }}</p>";
+    let mut cursor = Cursor::new(rest, 0);
+    cursor.syntax = Syntax::new("[[", "]]").unwrap();
+    assert_eq!(
+        _parse(cursor).unwrap(),
+        vec![S(Lit("", S(rest, bytes!(0..16)), ""), bytes!(0..16))]
+    );
+}
+
+#[test]
+fn test_empty_compile_error() {
+    let nodes = _parse(Cursor::new("{{$ }}", 0)).unwrap();
+    assert!(matches!(nodes[..], [S(Error(ref args), _)] if args.t().is_empty()));
+}
+
+fn error(span: Span) -> crate::SNode<'static> {
+    S(Invalid, span)
+}
+
+#[test]
+fn test_recover() {
+    let rest = "{{ a( }} b {{#each c, d }}{{ e }}{{/each }}{{ f }}";
+    let (nodes, errors) = parse_recover(Cursor::new(rest, 0));
+    assert_eq!(
+        nodes,
+        vec![
+            error(bytes!(0..8)),
+            S(Lit(" ", S("b", bytes!(9..10)), " "), bytes!(8..11)),
+            error(bytes!(11..43)),
+            S(Expr(WS, expr("f", bytes!(46..47))), bytes!(43..50)),
+        ]
+    );
+    let errors: Vec<_> = errors.into_iter().map(|e| (e.message, e.span)).collect();
+    assert_eq!(
+        errors,
+        vec![
+            (
+                PError::Expr(DOption::Some(String::from("unclosed delimiter"))),
+                bytes!(4..5)
+            ),
+            (
+                PError::Argument(DOption::Some(String::from("expected only one expression"))),
+                bytes!(19..23)
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_recover_unclosed() {
+    let rest = "{{ a }}{{{ b( }}} c {{#if d }}e{{/f";
+    let (nodes, errors) = parse_recover(Cursor::new(rest, 0));
+    assert_eq!(
+        nodes,
+        vec![
+            S(Expr(WS, expr("a", bytes!(3..4))), bytes!(0..7)),
+            error(bytes!(7..17)),
+            S(Lit(" ", S("c", bytes!(18..19)), " "), bytes!(17..20)),
+            error(bytes!(20..30)),
+        ]
+    );
+    let errors: Vec<_> = errors.into_iter().map(|e| (e.message, e.span)).collect();
+    assert_eq!(
+        errors,
+        vec![
+            (
+                PError::Expr(DOption::Some(String::from("unclosed delimiter"))),
+                bytes!(12..13)
+            ),
+            (PError::Uncompleted, bytes!(33..34)),
+            (PError::Uncompleted, bytes!(20..30)),
+        ]
+    );
+}
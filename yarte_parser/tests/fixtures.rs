@@ -33,7 +33,7 @@ fn test_fixtures() {
             .expect("Valid Fixtures");
 
         for Fixture { src, exp } in fixtures {
-            let res = parse(Cursor { rest: src, off: 0 }).expect("Valid parse");
+            let res = parse(Cursor::new(src, 0)).expect("Valid parse");
             assert_eq!(res, exp);
         }
     }
@@ -49,7 +49,7 @@ fn test_fixtures_panic() {
             .expect("Valid Fixtures");
 
         for FixturePanic(src) in fixtures {
-            assert!(parse(Cursor { rest: src, off: 0 }).is_err());
+            assert!(parse(Cursor::new(src, 0)).is_err());
         }
     }
 }
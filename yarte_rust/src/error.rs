@@ -38,6 +38,10 @@ pub enum Error {
     SinkEnd,
     #[error("UnmatchedToken")]
     UnmatchedToken,
+    #[error("unexpected closing delimiter")]
+    UnexpectedDelimiter,
+    #[error("unclosed delimiter")]
+    UnclosedDelimiter,
     #[error("CursorParse")]
     CursorParse,
     #[error("Whitespace")]
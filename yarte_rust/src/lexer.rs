@@ -3,7 +3,7 @@ use yarte_strnom::source_map::{spanned, S};
 use yarte_strnom::{Cursor, LexError, Span};
 
 use crate::error::{CResult, Error, Result};
-use crate::literals::literal;
+use crate::literals::{is_ident_continue, is_ident_start, literal};
 use crate::sink::{Sink, State};
 use crate::tokens::{Delimiter, Ident, Punct};
 
@@ -33,7 +33,7 @@ pub fn token_stream<'a, O: Sink<'a>>(mut input: Cursor<'a>, sink: &mut O) -> CRe
             b'{' => Some(Delimiter::Brace),
             _ => None,
         } {
-            let span = Span::from(input);
+            let span = Span::from_len(input, 1);
             go!(sink.open_group(S(open_delimiter, span))? => input.adv(1));
         } else if let Some(close_delimiter) = match first {
             b')' => Some(Delimiter::Parenthesis),
@@ -41,14 +41,14 @@ pub fn token_stream<'a, O: Sink<'a>>(mut input: Cursor<'a>, sink: &mut O) -> CRe
             b'}' => Some(Delimiter::Brace),
             _ => None,
         } {
-            let span = Span::from(input);
+            let span = Span::from_len(input, 1);
             go!(sink.close_group(S(close_delimiter, span))? => input.adv(1));
         } else if let Ok((next, l)) = spanned(input, literal) {
             go!(sink.literal(l)? => next);
-        } else if let Ok((next, p)) = spanned(input, punct) {
-            go!(sink.punct(p)? => next);
         } else if let Ok((next, i)) = spanned(input, ident) {
             go!(sink.ident(i)? => next);
+        } else if let Ok((next, p)) = spanned(input, punct) {
+            go!(sink.punct(p)? => next);
         } else {
             return Err(LexError::Next(Error::UnmatchedToken, Span::from(input)));
         }
@@ -79,42 +79,28 @@ fn punct(input: Cursor) -> Result<Punct> {
 }
 
 #[inline]
-fn ident(mut input: Cursor) -> Result<Ident> {
+fn ident(input: Cursor) -> Result<Ident> {
     /* This is a name: true/false, function, reserved word, variable, struct, trait, enum, etc*/
-    if ["r\"", "r#\"", "r##", "b\"", "b\'", "br\"", "br#"]
-        .iter()
-        .any(|prefix| input.starts_with(prefix))
-    {
-        Err(LexError::Next(Error::Ident, Span::from(input)))
-    } else {
-        let mut cont = 0;
-        for byte in input.bytes() {
-            match byte {
-                b if (0x30..0x4A).contains(&b) => {
-                    // Number
-                    if cont == 0 {
-                        return Err(LexError::Next(Error::Ident, Span::from(input)));
-                    }
-                    cont += 1;
-                }
-                b if (0x41..0x5B).contains(&b) => cont += 1, // Caps
-                b if (0x61..0x7B).contains(&b) => cont += 1, // Uncap
-                b'_' => cont += 1,
-                _ => {
-                    let i = Ident {
-                        inner: &input.rest[..cont],
-                    };
-                    input = input.adv(cont);
-                    return Ok((input, i));
-                }
-            }
-        }
-        let i = Ident {
-            inner: &input.rest[..cont],
-        };
-        input = input.adv(cont); // Corresponds to end of string check if cont+1 makes sense
-        Ok((input, i))
+    let raw = input.starts_with("r#");
+    let rest = if raw { input.adv(2) } else { input };
+
+    let mut chars = rest.rest.char_indices();
+    match chars.next() {
+        Some((_, ch)) if is_ident_start(ch) => {}
+        _ => return Err(LexError::Next(Error::Ident, Span::from(input))),
     }
+
+    let end = chars
+        .find(|(_, ch)| !is_ident_continue(*ch))
+        .map_or(rest.len(), |(i, _)| i);
+    let len = input.len() - rest.len() + end;
+
+    Ok((
+        input.adv(len),
+        Ident {
+            inner: &input.rest[..len],
+        },
+    ))
 }
 
 #[inline]
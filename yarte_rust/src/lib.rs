@@ -1,8 +1,8 @@
-#![feature(portable_simd)]
-mod error;
+#![cfg_attr(feature = "portable_simd", feature(portable_simd))]
 mod literals;
 mod parser;
 
+pub mod error;
 pub mod lexer;
 pub mod sink;
 pub mod tokens;
@@ -1,6 +1,3 @@
-use std::mem::transmute;
-use std::simd::{u8x32, SimdPartialEq};
-
 use serde::{Deserialize, Deserializer};
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Deserialize)]
@@ -39,23 +36,88 @@ pub enum Punct {
     Tilde = b'~',
 }
 
+/// Every [`Punct`], its representation is its byte
+const PUNCTS: [Punct; 24] = {
+    use Punct::*;
+    [
+        Exclamation,
+        Hash,
+        Dollar,
+        Percent,
+        And,
+        Apostrophe,
+        Asterisk,
+        Plus,
+        Comma,
+        Hyphen,
+        Dot,
+        Slash,
+        Colon,
+        SemiColon,
+        GreaterThan,
+        Equal,
+        LessThan,
+        Question,
+        At,
+        Circumflex,
+        Underscore,
+        Backtick,
+        Bar,
+        Tilde,
+    ]
+};
+
+static PUNCT_TABLE: [Option<Punct>; 256] = {
+    let mut table = [None; 256];
+    let mut i = 0;
+    while i < PUNCTS.len() {
+        table[PUNCTS[i] as u8 as usize] = Some(PUNCTS[i]);
+        i += 1;
+    }
+    table
+};
+
+/// Lookup table classification
+#[cfg_attr(feature = "portable_simd", allow(dead_code))]
+#[inline]
+fn is_punct(value: u8) -> bool {
+    PUNCT_TABLE[value as usize].is_some()
+}
+
+/// SIMD classification, only on nightly
+#[cfg(feature = "portable_simd")]
+#[inline]
+fn is_punct_simd(value: u8) -> bool {
+    use std::simd::{cmp::SimdPartialEq, u8x32};
+
+    // Recognized padded with repeated bytes up to the lanes
+    const LANES: [u8; 32] = *b"!#$%&'*+,-./:;<=>?@^_`|~!#$%&'*+";
+    u8x32::from_array(LANES).simd_eq(u8x32::splat(value)).any()
+}
+
 impl TryFrom<u8> for Punct {
     type Error = ();
 
     fn try_from(value: u8) -> Result<Self, Self::Error> {
-        const RECOGNIZED: [u8; 32] = *b"!#$%&'*+,-./:;<=>?@^_`|~!#$%&'*+";
-        let r = u8x32::from_array(RECOGNIZED);
-        let v = u8x32::from_array([value; 32]);
+        #[cfg(feature = "portable_simd")]
+        let recognized = is_punct_simd(value);
+        #[cfg(not(feature = "portable_simd"))]
+        let recognized = is_punct(value);
 
-        if r.simd_eq(v).any() {
-            // Safety: RECOGNIZED contains value is safe convert because have the same representation
-            Ok(unsafe { transmute(value) })
+        if recognized {
+            PUNCT_TABLE[value as usize].ok_or(())
         } else {
             Err(())
         }
     }
 }
 
+impl From<Punct> for char {
+    fn from(punct: Punct) -> Self {
+        punct as u8 as char
+    }
+}
+
 impl<'de> Deserialize<'de> for Punct {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -102,4 +164,61 @@ mod tests {
         assert_eq!(Punct::try_from(b'!').unwrap(), Punct::Exclamation);
         assert_eq!(Punct::try_from(b'/').unwrap(), Punct::Slash);
     }
+
+    /// Per byte classification before the lookup table
+    fn punct_match(value: u8) -> Option<Punct> {
+        use Punct::*;
+        Some(match value {
+            b'!' => Exclamation,
+            b'#' => Hash,
+            b'$' => Dollar,
+            b'%' => Percent,
+            b'&' => And,
+            b'\'' => Apostrophe,
+            b'*' => Asterisk,
+            b'+' => Plus,
+            b',' => Comma,
+            b'-' => Hyphen,
+            b'.' => Dot,
+            b'/' => Slash,
+            b':' => Colon,
+            b';' => SemiColon,
+            b'<' => GreaterThan,
+            b'=' => Equal,
+            b'>' => LessThan,
+            b'?' => Question,
+            b'@' => At,
+            b'^' => Circumflex,
+            b'_' => Underscore,
+            b'`' => Backtick,
+            b'|' => Bar,
+            b'~' => Tilde,
+            _ => return None,
+        })
+    }
+
+    #[test]
+    fn punct_table() {
+        for value in 0..=u8::MAX {
+            assert_eq!(Punct::try_from(value).ok(), punct_match(value), "{value}");
+            assert_eq!(is_punct(value), punct_match(value).is_some(), "{value}");
+        }
+    }
+
+    #[cfg(feature = "portable_simd")]
+    #[test]
+    fn punct_simd() {
+        for value in 0..=u8::MAX {
+            assert_eq!(is_punct(value), is_punct_simd(value), "{value}");
+        }
+
+        let fixtures = [
+            include_str!("../tests/fixtures/literals/literals.ron"),
+            include_str!("../tests/fixtures/expr/simple_function.ron"),
+            include_str!("../tests/fixtures/expr/idents.ron"),
+        ];
+        for value in fixtures.iter().flat_map(|x| x.bytes()) {
+            assert_eq!(is_punct(value), is_punct_simd(value), "{value}");
+        }
+    }
 }
@@ -9,7 +9,11 @@ use annotate_snippets::snippet::{Annotation, AnnotationType, Slice, Snippet, Sou
 
 use derive_more::Display;
 
-use crate::{get_bytes_to_chars, source_map::Span, Cursor};
+use crate::{
+    get_bytes_to_chars,
+    source_map::{SourceMap, Span},
+    Cursor,
+};
 
 #[allow(clippy::declare_interior_mutable_const)]
 pub trait KiError: Error + PartialEq + Clone {
@@ -165,7 +169,7 @@ impl<'a> Emitter for EmitterConfig<'a> {
 
 // TODO: Warnings and another types
 // TODO: Check annotate snippets
-pub fn emitter<Who, E, M, I>(who: Who, errors: I) -> String
+pub fn emitter<Who, E, M, I>(who: Who, sm: &SourceMap, errors: I) -> String
 where
     Who: Emitter,
     E: Into<ErrorMessage<M>>,
@@ -179,13 +183,13 @@ where
     errors.sort_by(|a, b| a.span.lo.cmp(&b.span.lo));
     let slices: Vec<(String, PathBuf, Span)> = errors
         .into_iter()
-        .map(|err| (err.message.to_string(), err.span.file_path(), err.span))
+        .map(|err| (err.message.to_string(), err.span.file_path(sm), err.span))
         .collect();
     let slices = slices
         .iter()
         .map(|(label, origin, span)| {
-            let ((lo_line, hi_line), (lo, hi)) = span.range_in_file();
-            let start = span.start();
+            let ((lo_line, hi_line), (lo, hi)) = span.range_in_file(sm);
+            let start = span.start(sm);
             let source = who.get(origin).expect("Who get source");
             let source = &source[lo_line..hi_line];
 
@@ -234,8 +238,6 @@ mod test {
     use std::fmt::Display;
     use std::iter::once;
 
-    use crate::source_map::get_cursor;
-
     #[derive(Debug)]
     struct Errr(&'static str);
     impl Error for Errr {}
@@ -252,8 +254,9 @@ mod test {
 
         let src = "foó bañ tuú foú";
         let l = src.len() as u32;
+        let mut sm = SourceMap::default();
         let mut sources = BTreeMap::new();
-        let _ = get_cursor(&path, src);
+        let _ = sm.get_cursor(&path, src);
         sources.insert(path, src.to_owned());
 
         let expected =
@@ -268,6 +271,7 @@ mod test {
 
         let result = emitter(
             who,
+            &sm,
             once(ErrorMessage {
                 message: Errr("bar"),
                 span: Span { lo: 10, hi: 14 },
@@ -280,7 +284,7 @@ mod test {
 
         let src = "foó bañ \ntuú\n foú";
         let mut sources = BTreeMap::new();
-        let _ = get_cursor(&path, src);
+        let _ = sm.get_cursor(&path, src);
         sources.insert(path, src.to_owned());
         // TODO: check annotated-snipped
         let expected = "error\n --> bars.hbs:1:5\n  |\n1 |   foó bañ \n  |  _____^\n2 | | tuú\n3 | |  foú\n  | |___^ bar\n  |";
@@ -294,6 +298,7 @@ mod test {
 
         let result = emitter(
             who,
+            &sm,
             once(ErrorMessage {
                 message: Errr("bar"),
                 span: Span {
@@ -5,5 +5,5 @@ pub mod error;
 pub mod source_map;
 
 pub use error::LexError;
-pub use source_map::{get_cursor, Span};
+pub use source_map::{SourceMap, Span};
 pub use strnom::*;
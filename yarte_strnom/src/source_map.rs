@@ -1,32 +1,9 @@
 //! Adapted from [`proc-macro2`](https://github.com/alexcrichton/proc-macro2).
-use std::borrow::{Borrow, BorrowMut};
 use std::fmt::{self, Debug};
 use std::path::{Path, PathBuf};
 
-use parking_lot::RwLock;
-
 use crate::error::{KiError, Result};
 use crate::strnom::Cursor;
-static SOURCE_MAP: RwLock<SourceMap> = RwLock::new(new_sm());
-
-/// Add file to source map and return lower bound
-///
-/// Use in the same thread
-pub fn get_cursor<'a>(p: &Path, rest: &'a str) -> Cursor<'a> {
-    let mut x = SOURCE_MAP.write();
-    Cursor {
-        rest,
-        off: x.borrow_mut().add_file(p, rest).lo,
-    }
-}
-
-/// Clear sourcemap and all existing Cursor and Span will be invalidate
-/// Use carefully
-pub fn clear() {
-    // Safety: set len to 0 is always safe because ondrop dealloc use capacity
-    //  and not is sensible data
-    unsafe { SOURCE_MAP.write().files.set_len(0) };
-}
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct LineColumn {
@@ -95,15 +72,24 @@ fn lines_offsets(s: &str) -> Vec<usize> {
     lines
 }
 
-struct SourceMap {
+/// Files with disjoint offsets, spans of cursors of a source map are resolved with it
+#[derive(Debug, Default)]
+pub struct SourceMap {
     files: Vec<FileInfo>,
 }
 
-const fn new_sm() -> SourceMap {
-    SourceMap { files: vec![] }
-}
-
 impl SourceMap {
+    /// Add file to source map and return its cursor
+    ///
+    /// # Panics
+    /// When the file is already registered
+    pub fn get_cursor<'a>(&mut self, p: &Path, rest: &'a str) -> Cursor<'a> {
+        Cursor {
+            rest,
+            off: self.add_file(p, rest).lo,
+        }
+    }
+
     fn next_start_pos(&self) -> u32 {
         // Add 1 so there's always space between files.
         self.files.last().map(|f| f.span.hi + 1).unwrap_or(0)
@@ -146,10 +132,6 @@ pub struct Span {
     pub hi: u32,
 }
 
-// Don't allow `Span` to transfer between thread
-// impl !Send for Span {}
-// impl !Sync for Span {}
-
 impl Span {
     pub const fn new(lo: u32, hi: u32) -> Self {
         Span { lo, hi }
@@ -179,50 +161,27 @@ impl Span {
         }
     }
 
+    /// Span of `proc` inside the expression starting at `self`
     pub fn join_proc(self, proc: proc_macro2::Span) -> Self {
-        let start = self.start();
-        let p_start = proc.start();
-        let p_end = proc.end();
-        let lo = if p_start.line == 1 {
-            self.lo + p_start.column as u32
-        } else {
-            let cm = SOURCE_MAP.read();
-            let cm = cm.borrow();
-            let fi = cm.file_info(self);
-            fi.lines[start.line + p_start.line - 2] as u32 + p_start.column as u32
-        };
-        let hi = if p_end.line == 1 {
-            self.lo + p_end.column as u32
-        } else {
-            let cm = SOURCE_MAP.read();
-            let cm = cm.borrow();
-            let fi = cm.file_info(self);
-            fi.lines[start.line + p_end.line - 2] as u32 + p_end.column as u32
-        };
+        let range = proc.byte_range();
 
-        Self { lo, hi }
+        Self {
+            lo: self.lo + range.start as u32,
+            hi: self.lo + range.end as u32,
+        }
     }
 
     /// Returns line bounds and range in bounds
-    pub fn range_in_file(self) -> ((usize, usize), (usize, usize)) {
-        let cm = SOURCE_MAP.read();
-        let cm = cm.borrow();
-        let fi = cm.file_info(self);
-        fi.get_ranges(self)
+    pub fn range_in_file(self, sm: &SourceMap) -> ((usize, usize), (usize, usize)) {
+        sm.file_info(self).get_ranges(self)
     }
 
-    pub fn file_path(self) -> PathBuf {
-        let cm = SOURCE_MAP.read();
-        let cm = cm.borrow();
-        let fi = cm.file_info(self);
-        fi.name.clone()
+    pub fn file_path(self, sm: &SourceMap) -> PathBuf {
+        sm.file_info(self).name.clone()
     }
 
-    pub fn start(self) -> LineColumn {
-        let cm = SOURCE_MAP.read();
-        let cm = cm.borrow();
-        let fi = cm.file_info(self);
-        fi.offset_line_column(self.lo as usize)
+    pub fn start(self, sm: &SourceMap) -> LineColumn {
+        sm.file_info(self).offset_line_column(self.lo as usize)
     }
 }
 
@@ -16,7 +16,7 @@ impl<'a> Cursor<'a> {
     /// Create new unregistered cursor
     ///
     /// # Safety
-    /// Use get_cursor function instead for registered cursor
+    /// Use `SourceMap::get_cursor` instead for registered cursor
     #[inline]
     pub unsafe fn new(rest: &str, off: u32) -> Cursor {
         Cursor { rest, off }
@@ -59,12 +59,12 @@ impl<'a> Cursor<'a> {
     }
 
     #[inline]
-    pub fn adv_starts_with(&self, amt: usize, s: &'static str) -> bool {
+    pub fn adv_starts_with(&self, amt: usize, s: &str) -> bool {
         start_with(&self.as_bytes()[amt..], s.as_bytes())
     }
 
     #[inline]
-    pub fn adv_starts_with_bytes(&self, amt: usize, s: &'static [u8]) -> bool {
+    pub fn adv_starts_with_bytes(&self, amt: usize, s: &[u8]) -> bool {
         start_with(&self.as_bytes()[amt..], s)
     }
 
@@ -74,7 +74,7 @@ impl<'a> Cursor<'a> {
     }
 
     #[inline]
-    pub fn starts_with_bytes(&self, s: &'static [u8]) -> bool {
+    pub fn starts_with_bytes(&self, s: &[u8]) -> bool {
         start_with(self.as_bytes(), s)
     }
 
@@ -196,22 +196,23 @@ macro_rules! call {
 ///
 /// # Syntax:
 /// ```rust
-/// # use yarte_strnom::{pipes, do_parse, ws, tac, Cursor, get_cursor};
+/// # use yarte_strnom::{pipes, do_parse, ws, tac, Cursor, SourceMap};
 /// # use yarte_strnom::pipes::*;
 /// # use yarte_strnom::error::{Empty, Result, LexError};
 /// # use std::path::PathBuf;
 /// # let path = PathBuf::from("FooFile");
 /// # let path2 = PathBuf::from("FooFile2");
+/// # let mut sm = SourceMap::default();
 ///
 /// let stmt = |i| pipes!(i, ws:is_empty:_false);
 /// let parser = |i| do_parse!(i, ws= stmt:important => tac[b'b'] => (ws));
-/// let result: Result<bool, Empty> = parser(get_cursor(&path, " b"));
+/// let result: Result<bool, Empty> = parser(sm.get_cursor(&path, " b"));
 /// let (c, result) = result.unwrap();
 ///
 /// assert!(!result);
 /// assert!(c.is_empty());
 ///
-/// let result: Result<bool, Empty> = parser(get_cursor(&path2, "b"));
+/// let result: Result<bool, Empty> = parser(sm.get_cursor(&path2, "b"));
 /// assert!(matches!(result.err().unwrap(), LexError::Fail(..)))
 /// ```
 // TODO: remove unnecessary on pipe [] from do_parse
@@ -290,16 +291,17 @@ macro_rules! alt {
 ///
 /// # Syntax
 /// ```rust
-/// # use yarte_strnom::{pipes, do_parse, ws, tac, Cursor, get_cursor};
+/// # use yarte_strnom::{pipes, do_parse, ws, tac, Cursor, SourceMap};
 /// # use yarte_strnom::pipes::*;
 /// # use yarte_strnom::error::{Empty, Result};
 /// # use std::path::PathBuf;
 /// # let path = PathBuf::from("FooFile");
 /// # const B: u8 = b'b';
+/// # let mut sm = SourceMap::default();
 ///
 /// let stmt = |i| pipes!(i, ws:is_empty:map[|x| !x]);
 /// let parser = |i| do_parse!(i, ws= stmt => tac[B] => (ws));
-/// let result: Result<bool, Empty> = parser(get_cursor(&path, " b"));
+/// let result: Result<bool, Empty> = parser(sm.get_cursor(&path, " b"));
 /// let (c, result) = result.unwrap();
 ///
 /// assert!(result);